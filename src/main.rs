@@ -1,17 +1,40 @@
 mod state;
 
+use std::collections::BTreeSet;
 use std::error::Error;
 use std::io::ErrorKind;
 
 use chrono::{DateTime, Duration, TimeDelta, Utc};
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
+use serde::{Deserialize, Serialize};
 use track_work::{
-    Change, CompleteWorkSlice, CompleteWorkSliceData, IncompleteWorkSlice, IncompleteWorkSliceData,
-    Money, Project, ProjectData, ProjectId, State, WorkEndError, WorkSlice, WorkSliceId,
-    WorkStartError,
+    AddWorkSliceError, Annotation, ArchiveProjectError, Balance, Break, Change, Client, ClientId,
+    CompleteWorkSlice, CreateInvoiceError, EditWorkSliceError, HistoryError, IncompleteWorkSlice,
+    Invoice, JsonFileStorage, Money, Overlap, OverlapPolicy, Project, ProjectId, ReceivedPayment,
+    ReceivedPaymentId, RecordPaymentError, RenderFormat, ReopenProjectError, Report, ReportError,
+    ReportFilter, ReportPeriod, RestoreError, SqliteStorage, State, StateData, Statement, Storage,
+    Template, TemplateError, TrashEntry, TrashedItem, WorkEndError, WorkInRange, WorkPauseError,
+    WorkResumeError, WorkSlice, WorkSliceData, WorkSliceId, WorkStartError,
 };
 
-use track_work::{MoneyExact, Payment};
+use track_work::{BillingPolicy, Currency, DurationRounding, MoneyExact, MoneyTotals, Payment};
+
+/// The number of journal entries after which the journal is folded into the save file,
+/// if `JOURNAL_COMPACT_AFTER` isn't set.
+const DEFAULT_JOURNAL_COMPACT_AFTER: usize = 1000;
+
+/// The number of backups of the save file which are kept, if `BACKUP_COUNT` isn't set.
+const DEFAULT_BACKUP_COUNT: usize = 5;
+
+/// The number of changes which can be undone, if `HISTORY_LIMIT` isn't set.
+const DEFAULT_HISTORY_LIMIT: usize = 100;
+
+/// The undo and redo history, which is saved between invocations.
+#[derive(Serialize, Deserialize, Default)]
+struct History {
+    undo: Vec<Change>,
+    redo: Vec<Change>,
+}
 
 #[derive(Parser)]
 struct Cli {
@@ -19,6 +42,172 @@ struct Cli {
     command: Command,
 }
 
+/// The flags which describe a payment. No payment is given unless `--payment` is.
+#[derive(Args)]
+#[group(requires = "payment", multiple = true)]
+struct PaymentArgs {
+    #[arg(short = 'f', long)]
+    payment_fixed: bool,
+    /// The payment, in the smallest unit of the currency (like pence or cents)
+    #[arg(short, long)]
+    payment: Option<u32>,
+    /// The currency of the payment, like GBP, EUR, USD or JPY
+    #[arg(short, long, default_value = "GBP")]
+    currency: Currency,
+    /// Bill hourly work in increments of this many minutes
+    #[arg(short, long, conflicts_with = "payment_fixed")]
+    increment: Option<u32>,
+    /// How hourly work is rounded to a whole number of increments: up, down or nearest
+    #[arg(short, long, default_value = "up", requires = "increment")]
+    rounding: DurationRounding,
+    /// The minimum number of minutes of hourly work which is billed
+    #[arg(short, long, conflicts_with = "payment_fixed")]
+    minimum: Option<u32>,
+    /// Pay the payment for every day of work which is started, instead of hourly
+    #[arg(long, conflicts_with_all = ["payment_fixed", "increment", "minimum"])]
+    daily: bool,
+    /// Pay `--overtime-rate` per hour instead of the payment after this many hours
+    #[arg(long, requires = "overtime_rate", conflicts_with_all = ["payment_fixed", "increment", "minimum", "daily"])]
+    overtime_after: Option<u32>,
+    /// The hourly payment after `--overtime-after` hours, in the same currency
+    #[arg(long, requires = "overtime_after")]
+    overtime_rate: Option<u32>,
+    /// The most that hourly work can earn in total, in the same currency
+    #[arg(long, conflicts_with_all = ["payment_fixed", "increment", "minimum", "daily", "overtime_after"])]
+    cap: Option<u32>,
+}
+impl PaymentArgs {
+    /// Returns the payment described by these flags, if `--payment` was given.
+    fn payment(&self) -> Option<Payment> {
+        let money = Money::new(self.payment?, self.currency);
+        Some(if self.payment_fixed {
+            Payment::Fixed(money)
+        } else if self.daily {
+            Payment::Daily(money)
+        } else if let (Some(hours), Some(overtime_amount)) =
+            (self.overtime_after, self.overtime_rate)
+        {
+            Payment::Tiered {
+                rate: money,
+                hours,
+                overtime_amount,
+            }
+        } else if let Some(cap_amount) = self.cap {
+            Payment::Capped {
+                rate: money,
+                cap_amount,
+            }
+        } else if self.increment.is_some() || self.minimum.is_some() {
+            Payment::HourlyBilled(
+                money,
+                BillingPolicy {
+                    increment_seconds: self.increment.unwrap_or(0) * 60,
+                    rounding: self.rounding,
+                    minimum_seconds: self.minimum.unwrap_or(0) * 60,
+                },
+            )
+        } else {
+            Payment::Hourly(money)
+        })
+    }
+}
+
+/// The flags which change the payment of a work slice.
+#[derive(Args)]
+struct EditPaymentArgs {
+    /// The new payment, which keeps the work slice's kind of payment
+    /// unless `--payment-fixed` or `--payment-hourly` is also given
+    #[arg(short, long)]
+    payment: Option<u32>,
+    /// Make the payment fixed
+    #[arg(short = 'f', long)]
+    payment_fixed: bool,
+    /// Make the payment hourly, without any billing increment or minimum
+    #[arg(long, conflicts_with = "payment_fixed")]
+    payment_hourly: bool,
+    /// The new currency of the payment, which keeps the current currency if not given
+    #[arg(short, long)]
+    currency: Option<Currency>,
+}
+impl EditPaymentArgs {
+    /// Returns the payment described by these flags, using the current payment for anything not given.
+    fn payment(&self, current: Payment) -> Payment {
+        let (Payment::Hourly(current_money)
+        | Payment::HourlyBilled(current_money, _)
+        | Payment::Fixed(current_money)
+        | Payment::Daily(current_money)
+        | Payment::Tiered {
+            rate: current_money,
+            ..
+        }
+        | Payment::Capped {
+            rate: current_money,
+            ..
+        }) = current;
+        let money = Money::new(
+            self.payment.unwrap_or(current_money.amount()),
+            self.currency.unwrap_or(current.currency()),
+        );
+        match current {
+            _ if self.payment_fixed => Payment::Fixed(money),
+            _ if self.payment_hourly => Payment::Hourly(money),
+            Payment::Hourly(_) => Payment::Hourly(money),
+            Payment::Fixed(_) => Payment::Fixed(money),
+            Payment::HourlyBilled(_, policy) => Payment::HourlyBilled(money, policy),
+            Payment::Daily(_) => Payment::Daily(money),
+            Payment::Tiered {
+                hours,
+                overtime_amount,
+                ..
+            } => Payment::Tiered {
+                rate: money,
+                hours,
+                overtime_amount,
+            },
+            Payment::Capped { cap_amount, .. } => Payment::Capped {
+                rate: money,
+                cap_amount,
+            },
+        }
+    }
+}
+
+/// The flags which describe what a work slice is for.
+#[derive(Args)]
+struct AnnotationArgs {
+    /// A note describing the work, which replaces any existing note
+    #[arg(short, long)]
+    note: Option<String>,
+    /// A tag for the work, which can be given more than once. Existing tags are kept
+    #[arg(long = "tag")]
+    tags: Vec<String>,
+}
+impl AnnotationArgs {
+    /// Returns the annotation given by these flags, added to the current note and tags,
+    /// or `None` if no flags were given.
+    fn annotation(self, note: Option<&str>, tags: &BTreeSet<String>) -> Option<Annotation> {
+        if self.note.is_none() && self.tags.is_empty() {
+            return None;
+        }
+        let mut tags = tags.clone();
+        tags.extend(
+            self.tags
+                .iter()
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty()),
+        );
+        Some(Annotation {
+            note: self.note.or_else(|| note.map(str::to_string)),
+            tags,
+        })
+    }
+
+    /// Returns the annotation given by these flags for a new work slice.
+    fn new_annotation(self) -> Annotation {
+        self.annotation(None, &BTreeSet::new()).unwrap_or_default()
+    }
+}
+
 #[derive(Subcommand)]
 enum Command {
     /// Create a new project
@@ -27,12 +216,28 @@ enum Command {
         name: String,
         #[arg(short, long)]
         description: String,
+        /// The default payment, used when work is started without a payment
+        #[command(flatten)]
+        payment: PaymentArgs,
+        /// The ID of the client which the project is for
+        #[arg(long)]
+        client: Option<u64>,
+    },
+    /// Create, list and view the clients who pay for projects
+    Client {
+        #[command(subcommand)]
+        command: ClientCommand,
     },
-    /// Delete a project or a work slice
+    /// Delete a project or a work slice, moving it to the trash
     Delete {
         #[command(subcommand)]
         command: DeleteCommand,
     },
+    /// Edit a project or a work slice
+    Edit {
+        #[command(subcommand)]
+        command: EditCommand,
+    },
     /// View your projects and work slices
     View {
         #[command(subcommand)]
@@ -48,16 +253,47 @@ enum Command {
         project: u64,
         #[arg(short, long)]
         time: Option<DateTime<Utc>>,
-        #[arg(short = 'f', long)]
-        payment_fixed: bool,
-        #[arg(short, long)]
-        payment: u32,
+        /// The payment for this work, which is the project's default payment if not given
+        #[command(flatten)]
+        payment: PaymentArgs,
+        #[command(flatten)]
+        annotation: AnnotationArgs,
     },
     /// Complete the current work slice for a certain project
     Complete {
         project: u64,
         #[arg(short, long)]
         time: Option<DateTime<Utc>>,
+        #[command(flatten)]
+        annotation: AnnotationArgs,
+    },
+    /// Pause the current work slice for a certain project, starting a break
+    Pause {
+        project: u64,
+        #[arg(short, long)]
+        time: Option<DateTime<Utc>>,
+    },
+    /// Resume the paused work slice for a certain project, ending its break
+    Resume {
+        project: u64,
+        #[arg(short, long)]
+        time: Option<DateTime<Utc>>,
+    },
+    /// Record work which has already been done as a completed work slice
+    Log {
+        project: u64,
+        #[arg(short, long)]
+        start: DateTime<Utc>,
+        #[arg(short, long, required_unless_present = "duration")]
+        end: Option<DateTime<Utc>>,
+        /// How many minutes the work lasted, instead of giving an end time
+        #[arg(short, long, conflicts_with = "end")]
+        duration: Option<u32>,
+        /// The payment for this work, which is the project's default payment if not given
+        #[command(flatten)]
+        payment: PaymentArgs,
+        #[command(flatten)]
+        annotation: AnnotationArgs,
     },
     /// Delete a work slice from a project
     DeleteWork {
@@ -67,6 +303,139 @@ enum Command {
     },
     /// Cancel the current work slice for a project
     CancelCurrentWork { project: u64 },
+    /// Archive a finished project, hiding it from `view all` and `list projects`
+    /// while keeping all of its work
+    Archive { project: u64 },
+    /// Make an archived project active again
+    Reopen { project: u64 },
+    /// Record money received for a project's work
+    Paid {
+        project: u64,
+        /// The amount received, in the smallest unit of the currency (like pence or cents)
+        #[arg(short, long)]
+        amount: u32,
+        /// The currency of the amount, like GBP, EUR, USD or JPY
+        #[arg(short, long, default_value = "GBP")]
+        currency: Currency,
+        /// When the money was received, which is now if not given
+        #[arg(short, long)]
+        date: Option<DateTime<Utc>>,
+        /// What the payment was for, like an invoice number or a bank reference
+        #[arg(short, long, default_value = "")]
+        reference: String,
+    },
+    /// Show how much money is owed for work, how much has been received and how much is outstanding.
+    /// Without a project or client, this shows every project with money owed or received, and the overall total
+    Balance {
+        /// Show the balance of this project, with the payments received for it
+        #[arg(conflicts_with = "client")]
+        project: Option<u64>,
+        /// Show the balance of each of this client's projects, and their total
+        #[arg(short, long)]
+        client: Option<u64>,
+    },
+    /// Copy all of the projects from a JSON save file into the storage configured in .env,
+    /// e.g. to move from a JSON file to a SQLite database
+    Migrate {
+        /// The JSON save file to copy projects from
+        from: String,
+        /// The journal file which goes with the JSON save file, if there is one
+        #[arg(short, long)]
+        journal: Option<String>,
+        /// Overwrite any projects which are already in the configured storage
+        #[arg(long)]
+        force: bool,
+    },
+    /// List, restore or permanently delete projects and work slices in the trash
+    Trash {
+        #[command(subcommand)]
+        command: TrashCommand,
+    },
+    /// Create, list, view or delete invoices
+    Invoice {
+        #[command(subcommand)]
+        command: InvoiceCommand,
+    },
+    /// List every pair of work slices which overlap in time, in any projects
+    Overlaps,
+    /// Add up the time worked and payment earned in each day, week or month
+    Report {
+        /// How long each period is: day, week or month
+        #[arg(default_value_t = ReportPeriod::Day)]
+        period: ReportPeriod,
+        /// The start of the report, which is when the earliest work started if not given
+        #[arg(short, long)]
+        from: Option<DateTime<Utc>>,
+        /// The end of the report, which is now if not given
+        #[arg(short, long)]
+        to: Option<DateTime<Utc>>,
+        /// Only include work for this project
+        #[arg(short, long, conflicts_with = "client")]
+        project: Option<u64>,
+        /// Only include work for this client's projects
+        #[arg(short, long)]
+        client: Option<u64>,
+        /// Also list periods with no work
+        #[arg(short, long)]
+        empty: bool,
+    },
+    /// Render the complete work of a project, a client or an invoice as a Markdown or HTML document,
+    /// with a line for each work slice and totals. Without a project, client or invoice, all work is included
+    Render {
+        /// Only include work for this project
+        #[arg(short, long, conflicts_with_all = ["client", "invoice"])]
+        project: Option<u64>,
+        /// Only include work for this client's projects
+        #[arg(short, long, conflicts_with = "invoice")]
+        client: Option<u64>,
+        /// Render the work slices on this invoice, with its number and dates
+        #[arg(short, long, conflicts_with_all = ["from", "to"])]
+        invoice: Option<u64>,
+        /// The start of the period, which is when the earliest work started if not given
+        #[arg(short, long)]
+        from: Option<DateTime<Utc>>,
+        /// The end of the period, which is now if not given
+        #[arg(short, long)]
+        to: Option<DateTime<Utc>>,
+        /// The kind of document: markdown or html
+        #[arg(long, default_value_t = RenderFormat::Markdown)]
+        format: RenderFormat,
+        /// A template file to lay the document out with, instead of the default one.
+        /// Use `--print-template` to get a template to start from
+        #[arg(long)]
+        template: Option<String>,
+        /// Write the document to this file instead of printing it
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Print the default template for the format, instead of rendering anything
+        #[arg(long, conflicts_with_all = ["project", "client", "invoice", "from", "to", "template", "output"])]
+        print_template: bool,
+    },
+    /// Undo the most recent change
+    Undo,
+    /// Redo the most recently undone change
+    Redo,
+}
+impl Command {
+    /// Returns true if this command only looks at data, and never changes it.
+    fn is_read_only(&self) -> bool {
+        match self {
+            Command::View { .. }
+            | Command::List { .. }
+            | Command::Balance { .. }
+            | Command::Overlaps
+            | Command::Report { .. }
+            | Command::Render { .. } => true,
+            Command::Client { command } => {
+                matches!(command, ClientCommand::List | ClientCommand::View { .. })
+            }
+            Command::Invoice { command } => {
+                matches!(command, InvoiceCommand::List | InvoiceCommand::View { .. })
+            }
+            Command::Trash { command } => matches!(command, TrashCommand::List),
+            _ => false,
+        }
+    }
 }
 
 #[derive(Subcommand)]
@@ -75,9 +444,33 @@ enum ListCommand {
     Projects {
         #[arg(short, long)]
         verbose: bool,
+        /// Include archived projects
+        #[arg(short, long)]
+        all: bool,
     },
     /// List data for all work slices for a specific project. See also `view all --verbose` and `list projects --verbose`
-    WorkSlices { project: Option<u64> },
+    WorkSlices {
+        project: Option<u64>,
+        /// Only list work slices with this tag
+        #[arg(long)]
+        tag: Option<String>,
+        /// Only list complete work slices which aren't on an invoice yet
+        #[arg(short, long)]
+        unbilled: bool,
+    },
+    /// List every tag, with the total time and payment of the complete work slices which have it
+    Tags,
+    /// List the work done between two times, clipping work slices which go over either of them
+    Range {
+        #[arg(short, long)]
+        from: DateTime<Utc>,
+        /// The end of the range, which is now if not given
+        #[arg(short, long)]
+        to: Option<DateTime<Utc>>,
+        /// Only list work for this project
+        #[arg(short, long)]
+        project: Option<u64>,
+    },
 }
 
 #[derive(Subcommand)]
@@ -90,6 +483,122 @@ enum DeleteCommand {
         #[arg(short, long = "project")]
         project_id: u64,
     },
+    /// Delete a received payment which was recorded by mistake
+    Payment { payment_id: u64 },
+}
+
+#[derive(Subcommand)]
+enum TrashCommand {
+    /// List everything in the trash
+    List,
+    /// Restore a project or a work slice from the trash, with its original ID
+    Restore {
+        #[command(subcommand)]
+        command: RestoreCommand,
+    },
+    /// Permanently delete everything in the trash
+    Purge {
+        /// Only delete things which have been in the trash for more than this many days
+        #[arg(short, long)]
+        older_than_days: Option<u32>,
+    },
+}
+
+#[derive(Subcommand)]
+enum RestoreCommand {
+    /// Restore a project and all of the work slices it had when it was deleted
+    Project { project_id: u64 },
+    /// Restore a work slice to its project
+    Work { work_slice_id: u64 },
+}
+
+#[derive(Subcommand)]
+enum EditCommand {
+    /// Change the name, description or default payment of a project
+    Project {
+        project_id: u64,
+        #[arg(short, long)]
+        name: Option<String>,
+        #[arg(short, long)]
+        description: Option<String>,
+        /// The new default payment
+        #[command(flatten)]
+        payment: PaymentArgs,
+        /// Remove the default payment, so work can't be started without a payment
+        #[arg(long, conflicts_with = "payment")]
+        no_default_payment: bool,
+        /// The ID of the client which the project is for
+        #[arg(long)]
+        client: Option<u64>,
+        /// Make the project not be for any client
+        #[arg(long, conflicts_with = "client")]
+        no_client: bool,
+    },
+    /// Change the start, end or payment of a complete work slice
+    Work {
+        work_slice_id: u64,
+        #[arg(short, long)]
+        start: Option<DateTime<Utc>>,
+        #[arg(short, long)]
+        end: Option<DateTime<Utc>>,
+        #[command(flatten)]
+        payment: EditPaymentArgs,
+    },
+}
+
+#[derive(Subcommand)]
+enum ClientCommand {
+    /// Create a new client
+    Create {
+        #[arg(short, long)]
+        name: String,
+        /// How to get in touch with the client, like an email address
+        #[arg(long, default_value = "")]
+        contact: String,
+        /// The address which invoices are sent to
+        #[arg(long, default_value = "")]
+        address: String,
+        /// The default payment, used for work on the client's projects
+        /// when no payment is given and the project has no default payment
+        #[command(flatten)]
+        payment: PaymentArgs,
+    },
+    /// List all clients, with the total time worked and money earned for each
+    List,
+    /// View a client's details, projects and totals
+    View { client_id: u64 },
+}
+
+#[derive(Subcommand)]
+enum InvoiceCommand {
+    /// Create an invoice, which stops its work slices from being edited, deleted or billed again.
+    /// Unless `--work-slice` is given, this includes all of the unbilled work for the client or project
+    Create {
+        /// The client which the invoice is for
+        #[arg(short, long)]
+        client: Option<u64>,
+        /// Only include work for this project
+        #[arg(short, long, conflicts_with = "work_slices")]
+        project: Option<u64>,
+        /// Include this work slice, instead of all unbilled work. Can be given more than once
+        #[arg(short, long = "work-slice")]
+        work_slices: Vec<u64>,
+        /// Only include work which was completed before this time
+        #[arg(short, long, conflicts_with = "work_slices")]
+        until: Option<DateTime<Utc>>,
+        /// When the invoice is issued, which is now if not given
+        #[arg(short, long)]
+        issued: Option<DateTime<Utc>>,
+        /// How many days after being issued the invoice is due
+        #[arg(short, long, default_value_t = 30)]
+        due_in: u32,
+    },
+    /// List all invoices
+    List,
+    /// View an invoice, with all of its work slices
+    View { number: u64 },
+    /// Delete an invoice, so its work slices can be changed and billed again
+    Delete { number: u64 },
 }
 
 #[derive(Subcommand)]
@@ -98,6 +607,9 @@ enum ViewCommand {
     All {
         #[arg(short, long)]
         verbose: bool,
+        /// Include archived projects
+        #[arg(short, long)]
+        all: bool,
     },
     /// View data for a certain project
     Project {
@@ -115,19 +627,99 @@ fn main() -> Result<(), ()> {
     path.push(".env");
     dotenvy::from_path(path).expect("Couldn't load .env");
     let save_file_name = std::env::var("SAVE_FILE").expect("Couldn't load the SAVE_FILE variable");
-    let initial_data = load_data(&save_file_name).expect("Failed to load data");
-
+    let journal_file_name = std::env::var("JOURNAL_FILE").ok();
+    let history_file_name =
+        std::env::var("HISTORY_FILE").unwrap_or_else(|_| format!("{save_file_name}.history"));
+    let history_limit = std::env::var("HISTORY_LIMIT")
+        .map(|x| x.parse().expect("HISTORY_LIMIT should be a whole number"))
+        .unwrap_or(DEFAULT_HISTORY_LIMIT);
     let cli = Cli::parse();
 
-    let mut state = State::new(initial_data.clone(), move |changes, final_data| {
-        save_data(&save_file_name, changes, final_data);
-    })
-    .expect("Failed to initialise State");
+    let mut storage: Box<dyn Storage> = match std::env::var("STORAGE").as_deref() {
+        Ok("json") | Err(_) => {
+            let backups = std::env::var("BACKUP_COUNT")
+                .map(|x| x.parse().expect("BACKUP_COUNT should be a whole number"))
+                .unwrap_or(DEFAULT_BACKUP_COUNT);
+            let storage = JsonFileStorage::new(save_file_name).with_backups(backups);
+            Box::new(match journal_file_name {
+                Some(journal_file_name) => {
+                    let compact_after = std::env::var("JOURNAL_COMPACT_AFTER")
+                        .map(|x| {
+                            x.parse()
+                                .expect("JOURNAL_COMPACT_AFTER should be a whole number")
+                        })
+                        .unwrap_or(DEFAULT_JOURNAL_COMPACT_AFTER);
+                    storage.with_journal(journal_file_name, compact_after)
+                }
+                None => storage,
+            })
+        }
+        Ok("sqlite") => {
+            Box::new(SqliteStorage::open(save_file_name).expect("Failed to open the database"))
+        }
+        Ok(other) => panic!("Unknown STORAGE ({other}) - it should be either json or sqlite"),
+    };
+
+    if let Command::Migrate {
+        from,
+        journal,
+        force,
+    } = cli.command
+    {
+        migrate(&mut storage, from, journal, force);
+        return Ok(());
+    }
+
+    let mut state = State::new(storage).expect("Failed to load data");
+    // the history is loaded after the data, so that with JSON storage it's protected by the lock file.
+    // SQLite storage doesn't take a lock, so programs using the same database at once can overwrite each other's history
+    let history = load_history(&history_file_name).expect("Failed to load the undo history");
+    state.set_history(history.undo, history.redo);
+    if let Ok(policy) = std::env::var("OVERLAP_POLICY") {
+        let policy: OverlapPolicy = policy
+            .parse()
+            .expect("OVERLAP_POLICY should be allow, warn or forbid");
+        state.set_overlap_policy(policy);
+    }
+    if let Ok(days) = std::env::var("TRASH_RETENTION_DAYS") {
+        let days = days
+            .parse()
+            .expect("TRASH_RETENTION_DAYS should be a whole number");
+        // the trash is only expired by commands which change data, so looking at data never changes it
+        if !cli.command.is_read_only() {
+            state.expire_trash(TimeDelta::days(days));
+        }
+    }
 
     match cli.command {
-        Command::Create { name, description } => {
-            create_project(&mut state, name, description);
+        Command::Create {
+            name,
+            description,
+            payment,
+            client,
+        } => {
+            create_project(&mut state, name, description, payment.payment(), client);
         }
+        Command::Client { command } => match command {
+            ClientCommand::Create {
+                name,
+                contact,
+                address,
+                payment,
+            } => {
+                let id = state.new_client(name, contact, address, payment.payment());
+                println!("Created client {id}", id = unsafe { id.inner() });
+            }
+            ClientCommand::List => println!(
+                "{}",
+                state
+                    .all_clients()
+                    .map(|x| format_client(&state, x))
+                    .reduce(|acc, e| format!("{acc}\n{e}"))
+                    .unwrap_or_else(|| "No clients.".to_string())
+            ),
+            ClientCommand::View { client_id } => view_client(&state, client_id),
+        },
         Command::Delete { command } => match command {
             DeleteCommand::Project { project_id } => {
                 delete_project(&mut state, project_id);
@@ -138,27 +730,132 @@ fn main() -> Result<(), ()> {
             } => unsafe {
                 delete_work_slice(&mut state, work_slice_id, project_id);
             },
+            DeleteCommand::Payment { payment_id } => {
+                if state.delete_received_payment(unsafe { ReceivedPaymentId::new(payment_id) }) {
+                    println!("Deleted payment {payment_id}");
+                } else {
+                    eprintln!("Can't delete payment {payment_id} as it doesn't exist!");
+                }
+            }
+        },
+        Command::Edit { command } => match command {
+            EditCommand::Project {
+                project_id,
+                name,
+                description,
+                payment,
+                no_default_payment,
+                client,
+                no_client,
+            } => {
+                let id = unsafe { ProjectId::new(project_id) };
+                let client = client.map(|x| unsafe { ClientId::new(x) });
+                // the client is checked first, so nothing is changed if it's invalid
+                if client.is_some_and(|x| state.client_from_id(x).is_none()) {
+                    eprintln!("That client id ({}) is invalid!", unsafe {
+                        client.unwrap().inner()
+                    });
+                } else {
+                    let result = state
+                        .update_project(id, name, description)
+                        .and_then(|()| match (payment.payment(), no_default_payment) {
+                            (Some(payment), _) => {
+                                state.set_project_default_payment(id, Some(payment))
+                            }
+                            (None, true) => state.set_project_default_payment(id, None),
+                            (None, false) => Ok(()),
+                        });
+                    let result = result.map_err(|_| ()).and_then(|()| {
+                        match (client, no_client) {
+                            (Some(client), _) => state.set_project_client(id, Some(client)),
+                            (None, true) => state.set_project_client(id, None),
+                            (None, false) => Ok(()),
+                        }
+                        .map_err(|_| ())
+                    });
+                    match result {
+                        Ok(()) => view_project(&state, project_id, false),
+                        Err(()) => eprintln!("That project id ({project_id}) is invalid!"),
+                    }
+                }
+            }
+            EditCommand::Work {
+                work_slice_id,
+                start,
+                end,
+                payment,
+            } => edit_work_slice(&mut state, work_slice_id, start, end, payment),
         },
         Command::View { command } => match command {
-            ViewCommand::All { verbose } => {
-                view_all_projects(verbose, &state);
+            ViewCommand::All { verbose, all } => {
+                view_all_projects(verbose, all, &state);
             }
             ViewCommand::Project {
                 project_id,
                 verbose,
             } => view_project(&state, project_id, verbose),
             ViewCommand::Work { work_slice_id } => {
-                view_work_slice(state, work_slice_id);
+                view_work_slice(&state, work_slice_id);
             }
         },
         Command::List { command } => match command {
-            ListCommand::Projects { verbose } => view_all_projects(verbose, &state),
-            ListCommand::WorkSlices { project: None } => {
+            ListCommand::Projects { verbose, all } => view_all_projects(verbose, all, &state),
+            ListCommand::Tags => println!(
+                "{}",
+                state
+                    .tag_totals()
+                    .iter()
+                    .map(|(tag, total)| format!(
+                        "{tag}: {count} work slice{s}, lasting {duration} and earning {payment}",
+                        count = total.work_slices,
+                        s = if total.work_slices == 1 { "" } else { "s" },
+                        duration = format_duration(total.duration),
+                        payment = total.payment,
+                    ))
+                    .reduce(|acc, e| format!("{acc}\n{e}"))
+                    .unwrap_or_else(|| String::from("No tagged work."))
+            ),
+            ListCommand::Range { from, to, project } => {
+                let to = to.unwrap_or_else(Utc::now);
+                let work = match project {
+                    None => Some(state.work_in_range(from, to)),
+                    Some(project_id) => state
+                        .project_from_id(unsafe { ProjectId::new(project_id) })
+                        .map(|x| x.work_in_range(from, to)),
+                };
+                match work {
+                    None => eprintln!("That project ID ({}) is invalid!", project.unwrap()),
+                    Some(work) if work.is_empty() => println!("No work in that range."),
+                    Some(work) => {
+                        println!(
+                            "{}",
+                            work.iter()
+                                .map(|x| format_work_in_range(&state, x))
+                                .reduce(|acc, e| format!("{acc}\n{e}"))
+                                .unwrap()
+                        );
+                        println!(
+                            "Total: {duration}, earning {payment}",
+                            duration = format_duration(work.iter().map(|x| x.duration).sum()),
+                            payment = work.iter().map(|x| x.payment).sum::<MoneyTotals>(),
+                        );
+                    }
+                }
+            }
+            ListCommand::WorkSlices {
+                project: None,
+                tag,
+                unbilled,
+            } => {
+                let has_tag =
+                    |tags: &BTreeSet<String>| tag.as_ref().is_none_or(|x| tags.contains(x));
                 println!(
                     "{}",
                     state
                         .all_projects()
                         .flat_map(|x| x.complete_work_slices())
+                        .filter(|x| has_tag(x.tags()))
+                        .filter(|x| !unbilled || !state.is_invoiced(x.id()))
                         .map(|x| view_single_complete_work_slice(&state, x))
                         .reduce(|acc, e| format!("{acc}\n{e}"))
                         .unwrap_or_else(|| String::from("No recorded work."))
@@ -168,6 +865,7 @@ fn main() -> Result<(), ()> {
                     state
                         .all_projects()
                         .filter_map(|x| x.current_work_slice())
+                        .filter(|x| has_tag(x.tags()))
                         .map(|x| view_single_incomplete_work_slice(&state, x))
                         .reduce(|acc, e| format!("{acc}\n{e}"))
                         .unwrap_or_else(|| String::from("No ongoing work."))
@@ -175,7 +873,11 @@ fn main() -> Result<(), ()> {
             }
             ListCommand::WorkSlices {
                 project: Some(project_id),
+                tag,
+                unbilled,
             } => {
+                let has_tag =
+                    |tags: &BTreeSet<String>| tag.as_ref().is_none_or(|x| tags.contains(x));
                 let project = state.project_from_id(unsafe { ProjectId::new(project_id) });
                 match project {
                     None => eprintln!("That project ID ({project_id}) is invalid!"),
@@ -184,6 +886,8 @@ fn main() -> Result<(), ()> {
                             "{}",
                             project
                                 .complete_work_slices()
+                                .filter(|x| has_tag(x.tags()))
+                                .filter(|x| !unbilled || !state.is_invoiced(x.id()))
                                 .map(|x| view_single_complete_work_slice(&state, x))
                                 .reduce(|acc, e| format!("{acc}\n{e}"))
                                 .unwrap_or_else(|| String::from(
@@ -194,6 +898,7 @@ fn main() -> Result<(), ()> {
                             "{}",
                             project
                                 .current_work_slice()
+                                .filter(|x| has_tag(x.tags()))
                                 .map(|x| view_single_incomplete_work_slice(&state, x))
                                 .unwrap_or_else(|| String::from(
                                     "No ongoing work for project {project_id}."
@@ -206,20 +911,18 @@ fn main() -> Result<(), ()> {
         Command::Start {
             project,
             time,
-            payment_fixed,
             payment,
+            annotation,
         } => {
-            let payment = if payment_fixed {
-                Payment::Fixed(Money::new(payment))
-            } else {
-                Payment::Hourly(Money::new(payment))
-            };
+            let id = unsafe { ProjectId::new(project) };
             let time = time.unwrap_or_else(Utc::now);
-            match state.start_work(unsafe { ProjectId::new(project) }, payment, time) {
-                Ok(()) => println!(
-                    "Started work for project {project} at time {time}.",
-                    time = time.to_rfc2822()
-                ),
+            match state.start_work(id, payment.payment(), time, annotation.new_annotation()) {
+                Ok(()) => {
+                    println!(
+                        "Started work for project {project} at time {time}.",
+                        time = time.to_rfc2822()
+                    )
+                }
                 Err(err) => match err {
                     WorkStartError::AlreadyStarted => eprintln!(
                         "Can't start work for project {project} as some work is already ongoing!"
@@ -230,13 +933,31 @@ fn main() -> Result<(), ()> {
                     WorkStartError::InvalidStartTime => {
                         eprintln!("The start time for work can't be in the future!")
                     }
+                    WorkStartError::ProjectArchived => eprintln!(
+                        "Can't start work for project {project} as it's archived! Reopen it first."
+                    ),
+                    WorkStartError::NoPayment => eprintln!(
+                        "Project {project} has no default payment, so a payment must be given with `--payment`!"
+                    ),
+                    WorkStartError::Overlap(ids) => eprintln!(
+                        "Can't start work for project {project} as it would overlap with {}! Only one work slice can be ongoing at a time.",
+                        format_work_slice_ids(&ids)
+                    ),
                 },
             }
         }
-        Command::Complete { project, time } => {
+        Command::Complete {
+            project,
+            time,
+            annotation,
+        } => {
             let id = unsafe { ProjectId::new(project) };
             let time = time.unwrap_or_else(Utc::now);
-            match state.end_work(id, time) {
+            let annotation = state
+                .project_from_id(id)
+                .and_then(Project::current_work_slice)
+                .and_then(|x| annotation.annotation(x.note(), x.tags()));
+            match state.end_work(id, time, annotation) {
                 Ok(()) => {
                     println!("Successfully marked work for project {project} as complete!");
                 }
@@ -253,19 +974,539 @@ fn main() -> Result<(), ()> {
                 },
             }
         }
+        Command::Pause { project, time } => {
+            let id = unsafe { ProjectId::new(project) };
+            let time = time.unwrap_or_else(Utc::now);
+            match state.pause_work(id, time) {
+                Ok(()) => {
+                    println!("Successfully paused work for project {project}!");
+                }
+                Err(err) => match err {
+                    WorkPauseError::InvalidProjectId => {
+                        eprintln!("That project ID ({project}) is invalid!")
+                    }
+                    WorkPauseError::NoWorkToPause => {
+                        eprintln!("There is no ongoing work to pause!")
+                    }
+                    WorkPauseError::AlreadyPaused => {
+                        eprintln!("The work for project {project} is already paused!")
+                    }
+                    WorkPauseError::InvalidTime => {
+                        eprintln!("Work can't be paused in the future, or before it started or was last resumed!")
+                    }
+                },
+            }
+        }
+        Command::Resume { project, time } => {
+            let id = unsafe { ProjectId::new(project) };
+            let time = time.unwrap_or_else(Utc::now);
+            match state.resume_work(id, time) {
+                Ok(()) => {
+                    println!("Successfully resumed work for project {project}!");
+                }
+                Err(err) => match err {
+                    WorkResumeError::InvalidProjectId => {
+                        eprintln!("That project ID ({project}) is invalid!")
+                    }
+                    WorkResumeError::NotPaused => {
+                        eprintln!("There is no paused work to resume!")
+                    }
+                    WorkResumeError::InvalidTime => {
+                        eprintln!("Work can't be resumed in the future, or before it was paused!")
+                    }
+                },
+            }
+        }
+        Command::Log {
+            project,
+            start,
+            end,
+            duration,
+            payment,
+            annotation,
+        } => {
+            let end = end.unwrap_or_else(|| start + TimeDelta::minutes(duration.unwrap().into()));
+            match state.add_complete_work_slice(
+                unsafe { ProjectId::new(project) },
+                start,
+                end,
+                payment.payment(),
+                annotation.new_annotation(),
+            ) {
+                Ok(id) => {
+                    println!(
+                        "{}",
+                        view_single_complete_work_slice(
+                            &state,
+                            state.work_slice_from_id(id).unwrap().unwrap()
+                        )
+                    )
+                }
+                Err(AddWorkSliceError::InvalidProjectId) => {
+                    eprintln!("That project ID ({project}) is invalid!")
+                }
+                Err(AddWorkSliceError::NoPayment) => eprintln!(
+                    "Project {project} has no default payment, so a payment must be given with `--payment`!"
+                ),
+                Err(AddWorkSliceError::ProjectArchived) => eprintln!(
+                    "Can't log work for project {project} as it's archived! Reopen it first."
+                ),
+                Err(AddWorkSliceError::StartTimeAfterNow) => {
+                    eprintln!("The start time for work can't be in the future!")
+                }
+                Err(AddWorkSliceError::EndTimeBeforeStart) => {
+                    eprintln!("The end time of work must be after the start time!")
+                }
+                Err(AddWorkSliceError::Overlap(ids)) => eprintln!(
+                    "Can't log work for project {project} as it overlaps with {}!",
+                    format_work_slice_ids(&ids)
+                ),
+            }
+        }
         Command::DeleteWork {
             project,
             work_slice,
         } => delete_work_slice_from_project(project, work_slice, &mut state),
         Command::CancelCurrentWork { project } => {
-            cancel_incomplete_work_slice_for_project(state, project)
+            cancel_incomplete_work_slice_for_project(&mut state, project)
         }
+        Command::Archive { project } => {
+            match state.archive_project(unsafe { ProjectId::new(project) }) {
+                Ok(()) => println!("Archived project {project}"),
+                Err(ArchiveProjectError::InvalidProjectId) => {
+                    eprintln!("That project ID ({project}) is invalid!")
+                }
+                Err(ArchiveProjectError::AlreadyArchived) => {
+                    eprintln!("Project {project} is already archived!")
+                }
+                Err(ArchiveProjectError::WorkOngoing) => eprintln!(
+                    "Can't archive project {project} as some work is ongoing! Complete or cancel it first."
+                ),
+            }
+        }
+        Command::Reopen { project } => {
+            match state.reopen_project(unsafe { ProjectId::new(project) }) {
+                Ok(()) => println!("Reopened project {project}"),
+                Err(ReopenProjectError::InvalidProjectId) => {
+                    eprintln!("That project ID ({project}) is invalid!")
+                }
+                Err(ReopenProjectError::NotArchived) => {
+                    eprintln!("Project {project} isn't archived!")
+                }
+            }
+        }
+        Command::Paid {
+            project,
+            amount,
+            currency,
+            date,
+            reference,
+        } => match state.record_received_payment(
+            unsafe { ProjectId::new(project) },
+            Money::new(amount, currency),
+            date.unwrap_or_else(Utc::now),
+            reference,
+        ) {
+            Ok(id) => println!(
+                "Recorded payment {id} for project {project}: {balance}",
+                id = unsafe { id.inner() },
+                balance = format_balance(
+                    &state
+                        .project_balance(unsafe { ProjectId::new(project) })
+                        .unwrap()
+                ),
+            ),
+            Err(RecordPaymentError::InvalidProjectId) => {
+                eprintln!("That project ID ({project}) is invalid!")
+            }
+            Err(RecordPaymentError::ZeroAmount) => {
+                eprintln!("The amount received must be more than zero!")
+            }
+        },
+        Command::Balance { project, client } => view_balance(&state, project, client),
+        Command::Trash { command } => match command {
+            TrashCommand::List => view_trash(&state),
+            TrashCommand::Restore {
+                command: RestoreCommand::Project { project_id },
+            } => match state.restore_project(unsafe { ProjectId::new(project_id) }) {
+                Ok(()) => println!("Restored project {project_id}"),
+                Err(RestoreError::NotInTrash) => {
+                    eprintln!("Project {project_id} isn't in the trash!")
+                }
+                Err(RestoreError::Conflict(err)) => {
+                    eprintln!("Couldn't restore project {project_id}: {err}")
+                }
+            },
+            TrashCommand::Restore {
+                command: RestoreCommand::Work { work_slice_id },
+            } => match state.restore_work_slice(unsafe { WorkSliceId::new(work_slice_id) }) {
+                Ok(()) => println!("Restored work slice {work_slice_id}"),
+                Err(RestoreError::NotInTrash) => {
+                    eprintln!("Work slice {work_slice_id} isn't in the trash!")
+                }
+                Err(RestoreError::Conflict(err)) => {
+                    eprintln!("Couldn't restore work slice {work_slice_id}: {err}")
+                }
+            },
+            TrashCommand::Purge { older_than_days } => {
+                let count = match older_than_days {
+                    Some(days) => {
+                        state.purge_trash_before(Utc::now() - TimeDelta::days(days.into()))
+                    }
+                    None => state.purge_trash(),
+                };
+                println!("Permanently deleted {count} items from the trash.");
+            }
+        },
+        Command::Invoice { command } => match command {
+            InvoiceCommand::Create {
+                client,
+                project,
+                work_slices,
+                until,
+                issued,
+                due_in,
+            } => create_invoice(
+                &mut state,
+                client,
+                project,
+                work_slices,
+                until,
+                issued,
+                due_in,
+            ),
+            InvoiceCommand::List => println!(
+                "{}",
+                state
+                    .invoices()
+                    .map(|x| format_invoice(&state, x))
+                    .reduce(|acc, e| format!("{acc}\n{e}"))
+                    .unwrap_or_else(|| "No invoices.".to_string())
+            ),
+            InvoiceCommand::View { number } => view_invoice(&state, number),
+            InvoiceCommand::Delete { number } => {
+                if state.delete_invoice(number) {
+                    println!("Deleted invoice {number}");
+                } else {
+                    eprintln!("Can't delete invoice {number} as it doesn't exist!");
+                }
+            }
+        },
+        Command::Overlaps => println!(
+            "{}",
+            state
+                .overlaps()
+                .iter()
+                .map(|x| format_overlap(&state, x))
+                .reduce(|acc, e| format!("{acc}\n{e}"))
+                .unwrap_or_else(|| "No overlapping work.".to_string())
+        ),
+        Command::Report {
+            period,
+            from,
+            to,
+            project,
+            client,
+            empty,
+        } => {
+            let filter = match (project, client) {
+                (Some(id), _) => ReportFilter::Project(unsafe { ProjectId::new(id) }),
+                (None, Some(id)) => ReportFilter::Client(unsafe { ClientId::new(id) }),
+                (None, None) => ReportFilter::All,
+            };
+            match state.report(period, filter, from, to) {
+                Ok(report) => println!("{}", format_report(&report, empty)),
+                Err(ReportError::InvalidProjectId) => {
+                    eprintln!("That project ID ({}) is invalid!", project.unwrap())
+                }
+                Err(ReportError::InvalidClientId) => {
+                    eprintln!("That client ID ({}) is invalid!", client.unwrap())
+                }
+            }
+        }
+        Command::Render {
+            print_template: true,
+            format,
+            ..
+        } => print!("{}", format.default_template()),
+        Command::Render {
+            project,
+            client,
+            invoice,
+            from,
+            to,
+            format,
+            template,
+            output,
+            print_template: false,
+        } => {
+            let statement = match (invoice, project, client) {
+                (Some(number), _, _) => state
+                    .invoice_statement(number)
+                    .ok_or_else(|| format!("Invoice {number} doesn't exist!")),
+                (None, Some(id), _) => state
+                    .statement(
+                        ReportFilter::Project(unsafe { ProjectId::new(id) }),
+                        from,
+                        to,
+                    )
+                    .map_err(|_| format!("That project ID ({id}) is invalid!")),
+                (None, None, Some(id)) => state
+                    .statement(ReportFilter::Client(unsafe { ClientId::new(id) }), from, to)
+                    .map_err(|_| format!("That client ID ({id}) is invalid!")),
+                (None, None, None) => Ok(state
+                    .statement(ReportFilter::All, from, to)
+                    .expect("There is no project or client to be invalid")),
+            };
+            match statement {
+                Ok(statement) => render_statement(&statement, format, template, output),
+                Err(err) => eprintln!("{err}"),
+            }
+        }
+        Command::Migrate { .. } => unreachable!("Migrations are handled before loading data"),
+        Command::Undo => match state.undo() {
+            Ok(change) => println!("Undid: {}", describe_change(&change)),
+            Err(HistoryError::NothingToUndo) => eprintln!("There is nothing to undo!"),
+            Err(err) => eprintln!("Couldn't undo the most recent change: {err}"),
+        },
+        Command::Redo => match state.redo() {
+            Ok(change) => println!("Redid: {}", describe_change(&change)),
+            Err(HistoryError::NothingToRedo) => eprintln!("There is nothing to redo!"),
+            Err(err) => eprintln!("Couldn't redo the most recently undone change: {err}"),
+        },
+    }
+
+    for overlap in state.take_overlap_warnings() {
+        eprintln!("Warning: {}", format_overlap(&state, &overlap));
     }
 
+    // the data is saved first, so the history never describes changes which weren't saved
+    state.save().expect("Failed to save data");
+    save_history(&history_file_name, &state, history_limit);
+
     Ok(())
 }
 
-fn cancel_incomplete_work_slice_for_project(mut state: State, project: u64) {
+fn describe_change(change: &Change) -> String {
+    match change {
+        Change::ProjectCreated { name, id, .. } => format!("create project {id} ({name})"),
+        Change::ProjectUpdated { id, name, .. } => format!("update project {id} ({name})"),
+        Change::ProjectDefaultPaymentChanged { id, payment, .. } => match payment {
+            Some(payment) => format!("set the default payment of project {id} to {payment}"),
+            None => format!("remove the default payment of project {id}"),
+        },
+        Change::ProjectClientChanged { id, client, .. } => match client {
+            Some(client) => format!("make project {id} be for client {client}"),
+            None => format!("remove the client of project {id}"),
+        },
+        Change::ClientCreated { client } => {
+            format!(
+                "create client {id} ({name})",
+                id = client.id,
+                name = client.name
+            )
+        }
+        Change::ClientDeleted { client } => {
+            format!(
+                "delete client {id} ({name})",
+                id = client.id,
+                name = client.name
+            )
+        }
+        Change::InvoiceCreated { invoice } => format!("create invoice {}", invoice.number),
+        Change::InvoiceDeleted { invoice } => format!("delete invoice {}", invoice.number),
+        Change::PaymentReceived {
+            project_id,
+            payment,
+        } => format!(
+            "record payment {id} of {amount} for project {project_id}",
+            id = payment.id,
+            amount = payment.amount
+        ),
+        Change::ReceivedPaymentDeleted {
+            project_id,
+            payment,
+        } => format!(
+            "delete payment {id} of {amount} for project {project_id}",
+            id = payment.id,
+            amount = payment.amount
+        ),
+        Change::ProjectArchived { id } => format!("archive project {id}"),
+        Change::ProjectReopened { id } => format!("reopen project {id}"),
+        Change::ProjectDeleted { project, .. } => {
+            format!(
+                "delete project {id} ({name})",
+                id = project.id,
+                name = project.name
+            )
+        }
+        Change::ProjectRestored { project, .. } => {
+            format!(
+                "restore project {id} ({name})",
+                id = project.id,
+                name = project.name
+            )
+        }
+        Change::WorkSliceCompleted {
+            project_id,
+            work_slice_id,
+            ..
+        } => format!("complete work slice {work_slice_id} for project {project_id}"),
+        Change::WorkSliceUncompleted {
+            project_id,
+            work_slice_id,
+            ..
+        } => format!("mark work slice {work_slice_id} for project {project_id} as ongoing"),
+        Change::WorkSliceStarted {
+            project_id,
+            work_slice_id,
+            ..
+        } => format!("start work slice {work_slice_id} for project {project_id}"),
+        Change::WorkSlicePaused {
+            project_id,
+            work_slice_id,
+            ..
+        } => format!("pause work slice {work_slice_id} for project {project_id}"),
+        Change::WorkSlicePauseReverted {
+            project_id,
+            work_slice_id,
+            ..
+        } => format!("undo pausing work slice {work_slice_id} for project {project_id}"),
+        Change::WorkSliceResumed {
+            project_id,
+            work_slice_id,
+            ..
+        } => format!("resume work slice {work_slice_id} for project {project_id}"),
+        Change::WorkSliceResumeReverted {
+            project_id,
+            work_slice_id,
+            ..
+        } => format!("undo resuming work slice {work_slice_id} for project {project_id}"),
+        Change::WorkSliceLogged {
+            project_id,
+            work_slice,
+        } => format!(
+            "log work slice {id} for project {project_id}",
+            id = work_slice.id
+        ),
+        Change::WorkSliceEdited {
+            project_id,
+            work_slice_id,
+            ..
+        } => format!("edit work slice {work_slice_id} for project {project_id}"),
+        Change::WorkSliceAnnotated {
+            project_id,
+            work_slice_id,
+            ..
+        } => format!(
+            "change the note and tags of work slice {work_slice_id} for project {project_id}"
+        ),
+        Change::WorkSliceDeleted {
+            project_id,
+            work_slice,
+            ..
+        } => format!(
+            "delete work slice {id} from project {project_id}",
+            id = work_slice.id()
+        ),
+        Change::WorkSliceRestored {
+            project_id,
+            work_slice,
+            ..
+        } => format!(
+            "restore work slice {id} to project {project_id}",
+            id = work_slice.id()
+        ),
+        Change::TrashPurged { entries } => {
+            format!("permanently delete {} items from the trash", entries.len())
+        }
+        Change::TrashPurgeReverted { entries } => {
+            format!("put {} items back in the trash", entries.len())
+        }
+    }
+}
+
+fn migrate(storage: &mut Box<dyn Storage>, from: String, journal: Option<String>, force: bool) {
+    let existing = storage.load().expect("Failed to load data");
+    if !existing.is_empty() && !force {
+        eprintln!("The configured storage already has data in it! Use --force to overwrite it.");
+        return;
+    }
+    let source = JsonFileStorage::new(from);
+    let source = match journal {
+        Some(journal) => source.with_journal(journal, usize::MAX),
+        None => source,
+    };
+    let state = State::new(source).expect("Failed to load the data to migrate");
+    let data = StateData {
+        projects: state.all_projects().map(Into::into).collect(),
+        clients: state.all_clients().map(Into::into).collect(),
+        invoices: state.invoices().map(Into::into).collect(),
+        trash: state.trash().to_vec(),
+        previous_invoice_number: state.previous_invoice_number(),
+    };
+    let count = data.projects.len();
+    storage
+        .snapshot(data)
+        .expect("Failed to save the migrated data");
+    println!("Migrated {count} projects.");
+}
+
+fn edit_work_slice(
+    state: &mut State,
+    work_slice_id: u64,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+    payment: EditPaymentArgs,
+) {
+    let id = unsafe { WorkSliceId::new(work_slice_id) };
+    let Some(work_slice) = state.work_slice_from_id(id) else {
+        eprintln!("That work slice id ({work_slice_id}) is invalid!");
+        return;
+    };
+    let Some(work_slice) = work_slice.complete() else {
+        eprintln!("Only complete work slices can be edited!");
+        return;
+    };
+    let payment = payment.payment(work_slice.payment());
+    let start = start.unwrap_or(work_slice.start());
+    let end = end.unwrap_or(work_slice.completion());
+    match state.edit_work_slice(id, start, end, payment) {
+        Ok(()) => println!(
+            "{}",
+            format_complete_work_slice(state.work_slice_from_id(id).unwrap().unwrap())
+        ),
+        Err(err) => match err {
+            EditWorkSliceError::WorkSliceNotFound => {
+                eprintln!("That work slice id ({work_slice_id}) is invalid!")
+            }
+            EditWorkSliceError::WorkSliceIncomplete => {
+                eprintln!("Only complete work slices can be edited!")
+            }
+            EditWorkSliceError::StartTimeAfterNow => {
+                eprintln!("The start time for work can't be in the future!")
+            }
+            EditWorkSliceError::EndTimeBeforeStart => {
+                eprintln!("The end time of work must be after the start time!")
+            }
+            EditWorkSliceError::BreakOutsideWorkSlice => {
+                eprintln!("Can't edit work slice {work_slice_id} as some of its breaks would be outside it!")
+            }
+            EditWorkSliceError::Overlap(ids) => eprintln!(
+                "Can't edit work slice {work_slice_id} as it would overlap with {}!",
+                format_work_slice_ids(&ids)
+            ),
+            EditWorkSliceError::Invoiced => {
+                eprintln!(
+                    "Can't edit work slice {work_slice_id} as it's on invoice {number}!",
+                    number = state.invoice_of_work_slice(id).unwrap().number()
+                )
+            }
+        },
+    }
+}
+
+fn cancel_incomplete_work_slice_for_project(state: &mut State, project: u64) {
     match state.project_from_id(unsafe { ProjectId::new(project) }) {
         Some(project_data) => match project_data.current_work_slice().map(|x| x.id()) {
             Some(id) => { state.delete_work_slice_from_project(project_data.id(), id); },
@@ -278,6 +1519,13 @@ fn cancel_incomplete_work_slice_for_project(mut state: State, project: u64) {
 fn delete_work_slice_from_project(project: u64, work_slice: u64, state: &mut State) {
     let (project_id, work_slice_id) =
         unsafe { (ProjectId::new(project), WorkSliceId::new(work_slice)) };
+    if let Some(invoice) = state.invoice_of_work_slice(work_slice_id) {
+        eprintln!(
+            "Can't delete work slice {work_slice} as it's on invoice {number}!",
+            number = invoice.number()
+        );
+        return;
+    }
     let data = match state.project_from_id(project_id) {
         Some(project_data) => {
             if let Some(work_slice) = project_data.work_slice_from_id(work_slice_id) {
@@ -303,13 +1551,13 @@ fn delete_work_slice_from_project(project: u64, work_slice: u64, state: &mut Sta
     }
 }
 
-fn view_work_slice(state: State, work_slice_id: u64) {
+fn view_work_slice(state: &State, work_slice_id: u64) {
     match state.work_slice_from_id(unsafe { WorkSliceId::new(work_slice_id) }) {
         Some(WorkSlice::Complete(complete)) => {
-            println!("{}", view_single_complete_work_slice(&state, complete));
+            println!("{}", view_single_complete_work_slice(state, complete));
         }
         Some(WorkSlice::Incomplete(incomplete)) => {
-            println!("{}", view_single_incomplete_work_slice(&state, incomplete));
+            println!("{}", view_single_incomplete_work_slice(state, incomplete));
         }
         None => eprintln!("That work slice id ({work_slice_id}) is invalid!"),
     }
@@ -322,29 +1570,59 @@ fn view_single_incomplete_work_slice(state: &State, incomplete: &IncompleteWorkS
     let duration = format_duration(incomplete.duration()).to_string();
     let total_payment = incomplete.calculate_payment_so_far();
     format!(
-        "Current work slice {work_slice_id} for project {project_id}: Payment is {payment} - started at {start}, lasting {duration} and earning {total_payment}",
+        "Current work slice {work_slice_id} for project {project_id}: Payment is {payment} - started at {start}, lasting {duration} and earning {total_payment}{breaks}{annotation}",
         project_id = unsafe { project_id.inner() },
         work_slice_id = unsafe { incomplete.id().inner() },
+        breaks = format_breaks(incomplete.breaks(), incomplete.paused_at()),
+        annotation = format_annotation(incomplete.note(), incomplete.tags()),
     )
 }
 
 fn view_single_complete_work_slice(state: &State, complete: &CompleteWorkSlice) -> String {
     let project_id = state.project_id_from_work_slice(complete.id()).unwrap();
-    let payment = match complete.payment() {
-        Payment::Hourly(rate) => format!("{rate} / hour"),
-        Payment::Fixed(payment) => format!("fixed at {payment}"),
-    };
+    let payment = complete.payment();
     let start = complete.start().to_rfc2822();
     let duration = format_duration(complete.duration());
-    let total_payment = complete.calculate_payment().as_pence();
+    let total_payment = complete.calculate_payment();
     let completion = complete.completion().to_rfc2822();
     format!(
-        "Completed work slice {work_slice_id} for project {project_id}: Payment is {payment} - started at {start}, lasting {duration}, ending at {completion} and earning {total_payment}",
+        "Completed work slice {work_slice_id} for project {project_id}: Payment is {payment} - started at {start}, lasting {duration}, ending at {completion} and earning {total_payment}{breaks}{annotation}",
         project_id = unsafe { project_id.inner() },
         work_slice_id = unsafe { complete.id().inner() },
+        breaks = format_breaks(complete.breaks(), None),
+        annotation = format_annotation(complete.note(), complete.tags()),
     )
 }
 
+/// Describes the tags and note of a work slice, or returns an empty string if it has neither.
+fn format_annotation(note: Option<&str>, tags: &BTreeSet<String>) -> String {
+    let tags = if tags.is_empty() {
+        String::new()
+    } else {
+        format!(" [{}]", tags.iter().cloned().collect::<Vec<_>>().join(", "))
+    };
+    match note {
+        Some(note) => format!("{tags} - {note}"),
+        None => tags,
+    }
+}
+
+/// Describes the breaks taken during a work slice, or returns an empty string if there weren't any.
+fn format_breaks(breaks: &[Break], paused_at: Option<DateTime<Utc>>) -> String {
+    let taken = match breaks.len() {
+        0 => String::new(),
+        len => format!(
+            " - {len} break{s} lasting {duration}",
+            s = if len == 1 { "" } else { "s" },
+            duration = format_duration(breaks.iter().map(Break::duration).sum()),
+        ),
+    };
+    match paused_at {
+        Some(paused_at) => format!("{taken} - paused since {}", paused_at.to_rfc2822()),
+        None => taken,
+    }
+}
+
 fn view_project(state: &State, project_id: u64, verbose: bool) {
     match state.project_from_id(unsafe { ProjectId::new(project_id) }) {
         Some(project) => {
@@ -361,26 +1639,26 @@ fn view_project(state: &State, project_id: u64, verbose: bool) {
     }
 }
 
-fn view_all_projects(verbose: bool, state: &State) {
-    if verbose {
-        println!(
-            "{}",
-            state
-                .all_projects()
-                .map(format_project_verbose)
-                .reduce(|acc, e| format!("{acc}\n\n{e}"))
-                .unwrap_or_else(|| "No current projects.".to_string())
-        );
+/// Shows every project, except archived projects unless `all` is true.
+fn view_all_projects(verbose: bool, all: bool, state: &State) {
+    let format = if verbose {
+        format_project_verbose
     } else {
-        println!(
-            "{}",
-            state
-                .all_projects()
-                .map(format_project_not_verbose)
-                .reduce(|acc, e| format!("{acc}\n\n{e}"))
-                .unwrap_or_else(|| "No current projects.".to_string())
-        );
-    }
+        format_project_not_verbose
+    };
+    let projects: Vec<_> = if all {
+        state.all_projects().collect()
+    } else {
+        state.active_projects().collect()
+    };
+    println!(
+        "{}",
+        projects
+            .into_iter()
+            .map(format)
+            .reduce(|acc, e| format!("{acc}\n\n{e}"))
+            .unwrap_or_else(|| "No current projects.".to_string())
+    );
 }
 
 unsafe fn delete_work_slice(state: &mut State, work_slice_id: u64, project_id: u64) {
@@ -388,11 +1666,19 @@ unsafe fn delete_work_slice(state: &mut State, work_slice_id: u64, project_id: u
         .project_id_from_work_slice(WorkSliceId::new(work_slice_id))
         .map(|x| x.inner())
     {
+        Some(_) if state.is_invoiced(WorkSliceId::new(work_slice_id)) => eprintln!(
+            "Can't delete work slice {work_slice_id} as it's on invoice {number}!",
+            number = state
+                .invoice_of_work_slice(WorkSliceId::new(work_slice_id))
+                .unwrap()
+                .number()
+        ),
         Some(x) if x == project_id => {
             state.delete_work_slice_from_project(
                 ProjectId::new(project_id),
                 WorkSliceId::new(work_slice_id),
             );
+            println!("Moved work slice {work_slice_id} to the trash");
         }
         Some(other_project_id) => eprintln!(
             "That work slice ID ({work_slice_id}) belongs to another project ({other_project_id})!"
@@ -408,74 +1694,489 @@ unsafe fn delete_work_slice(state: &mut State, work_slice_id: u64, project_id: u
 }
 
 fn delete_project(state: &mut State, project_id: u64) {
-    if state.delete_project(unsafe { ProjectId::new(project_id) }) {
-        println!("Deleted project {project_id}");
+    let id = unsafe { ProjectId::new(project_id) };
+    if state.delete_project(id) {
+        println!("Moved project {project_id} to the trash");
+    } else if state.project_exists(id) {
+        eprintln!("Can't delete project {project_id} as some of its work is on an invoice!");
     } else {
         eprintln!("Can't delete project {project_id} as it doesn't exist!");
     }
 }
 
-fn create_project(state: &mut State, name: String, description: String) {
-    let id = state.new_project(name, description);
-    println!("Created project {id}", id = unsafe { id.inner() });
+fn create_invoice(
+    state: &mut State,
+    client: Option<u64>,
+    project: Option<u64>,
+    work_slices: Vec<u64>,
+    until: Option<DateTime<Utc>>,
+    issued: Option<DateTime<Utc>>,
+    due_in: u32,
+) {
+    let client = client.map(|x| unsafe { ClientId::new(x) });
+    let work_slices: Vec<_> = if !work_slices.is_empty() {
+        work_slices
+            .into_iter()
+            .map(|x| unsafe { WorkSliceId::new(x) })
+            .collect()
+    } else {
+        let projects: Vec<&Project> = match (project, client) {
+            (Some(project_id), _) => {
+                match state.project_from_id(unsafe { ProjectId::new(project_id) }) {
+                    Some(project) => vec![project],
+                    None => {
+                        eprintln!("That project ID ({project_id}) is invalid!");
+                        return;
+                    }
+                }
+            }
+            (None, Some(client)) => state.client_projects(client).collect(),
+            (None, None) => state.all_projects().collect(),
+        };
+        projects
+            .into_iter()
+            .flat_map(|x| x.complete_work_slices())
+            .filter(|x| !state.is_invoiced(x.id()))
+            .filter(|x| until.is_none_or(|until| x.completion() <= until))
+            .map(|x| x.id())
+            .collect()
+    };
+    let issued = issued.unwrap_or_else(Utc::now);
+    let due = issued + TimeDelta::days(due_in.into());
+    match state.create_invoice(client, work_slices, issued, due) {
+        Ok(number) => println!(
+            "Created {}",
+            format_invoice(state, state.invoice_from_number(number).unwrap())
+        ),
+        Err(CreateInvoiceError::NoWorkSlices) => eprintln!("There is no unbilled work to invoice!"),
+        Err(CreateInvoiceError::DueBeforeIssued) => {
+            eprintln!("An invoice can't be due before it's issued!")
+        }
+        Err(CreateInvoiceError::InvalidClientId) => {
+            eprintln!("That client ID ({}) is invalid!", unsafe {
+                client.unwrap().inner()
+            })
+        }
+        Err(CreateInvoiceError::WorkSliceNotFound) => {
+            eprintln!("Some of those work slice IDs are invalid!")
+        }
+        Err(CreateInvoiceError::WorkSliceIncomplete) => {
+            eprintln!("Only complete work slices can be invoiced!")
+        }
+        Err(CreateInvoiceError::AlreadyInvoiced) => {
+            eprintln!("Some of those work slices are already on an invoice!")
+        }
+        Err(CreateInvoiceError::WrongClient) => {
+            eprintln!("Some of those work slices are for projects which aren't for that client!")
+        }
+    }
 }
 
-fn load_data(file_name: &str) -> Result<Vec<ProjectData>, Box<dyn Error>> {
-    let file = match std::fs::read_to_string(file_name) {
-        Ok(x) => x,
-        Err(x) => match x.kind() {
-            ErrorKind::NotFound => {
-                std::fs::write(file_name, "[]")?;
-                "[]".to_string()
-            }
-            _ => {
-                return Err(Box::new(x));
+fn format_invoice(state: &State, invoice: &Invoice) -> String {
+    format!(
+        "invoice {number}{client} - issued {issued}, due {due}, for {count} work slice{s} earning {total}",
+        number = invoice.number(),
+        client = invoice
+            .client()
+            .and_then(|x| state.client_from_id(x))
+            .map_or_else(String::new, |x| format!(" for {}", x.name())),
+        issued = invoice.issued().to_rfc2822(),
+        due = invoice.due().to_rfc2822(),
+        count = invoice.work_slices().len(),
+        s = if invoice.work_slices().len() == 1 { "" } else { "s" },
+        total = invoice.total(),
+    )
+}
+
+fn view_invoice(state: &State, number: u64) {
+    let Some(invoice) = state.invoice_from_number(number) else {
+        eprintln!("That invoice number ({number}) is invalid!");
+        return;
+    };
+    let client = invoice.client().and_then(|x| state.client_from_id(x));
+    let work_slices = invoice
+        .work_slices()
+        .iter()
+        .zip(invoice.amounts())
+        .filter_map(|(&id, amount)| Some((state.work_slice_from_id(id)?.complete()?, amount)))
+        .map(|(x, amount)| format!("  {} - billed {amount}", format_complete_work_slice(x)))
+        .reduce(|acc, e| format!("{acc}\n{e}"))
+        .unwrap_or_default();
+    println!(
+        "{top_line}{billing}\n- work slices:\n{work_slices}",
+        top_line = format_invoice(state, invoice),
+        billing = client.map_or_else(String::new, |x| format!(
+            "\n- contact: {}\n- billing address: {}",
+            x.contact(),
+            x.billing_address()
+        )),
+    );
+}
+
+/// Shows how much is owed and has been received for a project, a client's projects,
+/// or every project with money owed or received if neither is given.
+fn view_balance(state: &State, project: Option<u64>, client: Option<u64>) {
+    let format_project = |x: &Project| {
+        format!(
+            "Project {id}: {name} - {balance}",
+            id = unsafe { x.id().inner() },
+            name = x.name(),
+            balance = format_balance(&x.balance()),
+        )
+    };
+    if let Some(project_id) = project {
+        let Some(project) = state.project_from_id(unsafe { ProjectId::new(project_id) }) else {
+            eprintln!("That project id ({project_id}) is invalid!");
+            return;
+        };
+        let payments = project
+            .received_payments()
+            .map(|x| format!("  {}", format_received_payment(x)))
+            .reduce(|acc, e| format!("{acc}\n{e}"))
+            .unwrap_or_else(|| "  none".to_string());
+        println!(
+            "{}\n- received payments:\n{payments}",
+            format_project(project)
+        );
+        return;
+    }
+    let (projects, total): (Vec<_>, _) = match client {
+        Some(client_id) => match state.client_balance(unsafe { ClientId::new(client_id) }) {
+            Ok(total) => (
+                state
+                    .client_projects(unsafe { ClientId::new(client_id) })
+                    .collect(),
+                total,
+            ),
+            Err(_) => {
+                eprintln!("That client id ({client_id}) is invalid!");
+                return;
             }
         },
+        None => (
+            state
+                .all_projects()
+                .filter(|x| x.balance() != Balance::default())
+                .collect(),
+            state.balance(),
+        ),
+    };
+    let projects = projects
+        .into_iter()
+        .map(format_project)
+        .reduce(|acc, e| format!("{acc}\n{e}"))
+        .unwrap_or_else(|| "No money owed or received.".to_string());
+    println!("{projects}\nTotal - {}", format_balance(&total));
+}
+
+/// Describes a balance in each currency, like "£40.00 owed, £10.00 received, £30.00 outstanding".
+fn format_balance(balance: &Balance) -> String {
+    balance
+        .currencies()
+        .into_iter()
+        .map(|currency| {
+            let outstanding = balance.outstanding(currency);
+            let overpaid = balance.overpaid(currency);
+            let status = if outstanding != MoneyExact::zero(currency) {
+                format!("{outstanding} outstanding")
+            } else if overpaid != MoneyExact::zero(currency) {
+                format!("{overpaid} overpaid")
+            } else {
+                "settled".to_string()
+            };
+            format!(
+                "{owed} owed, {received} received, {status}",
+                owed = balance.owed.get(currency),
+                received = balance.received.get(currency),
+            )
+        })
+        .reduce(|acc, e| format!("{acc}; {e}"))
+        .unwrap_or_else(|| "nothing owed".to_string())
+}
+
+fn format_received_payment(payment: &ReceivedPayment) -> String {
+    let reference = match payment.reference() {
+        "" => String::new(),
+        reference => format!(" - {reference}"),
+    };
+    format!(
+        "{id} - {amount} on {date}{reference}",
+        id = unsafe { payment.id().inner() },
+        amount = payment.amount(),
+        date = payment.date().to_rfc2822(),
+    )
+}
+
+/// Writes some work slices' IDs, like "work slice 3" or "work slices 3, 5".
+fn format_work_slice_ids(ids: &[WorkSliceId]) -> String {
+    let ids: Vec<_> = ids
+        .iter()
+        .map(|x| unsafe { x.inner() }.to_string())
+        .collect();
+    format!(
+        "work slice{s} {ids}",
+        s = if ids.len() == 1 { "" } else { "s" },
+        ids = ids.join(", ")
+    )
+}
+
+fn format_overlap(state: &State, overlap: &Overlap) -> String {
+    let describe = |id: WorkSliceId| match state.project_id_from_work_slice(id) {
+        Some(project_id) => format!(
+            "{id} (project {project_id})",
+            id = unsafe { id.inner() },
+            project_id = unsafe { project_id.inner() }
+        ),
+        None => unsafe { id.inner() }.to_string(),
+    };
+    let (end, duration) = match overlap.end {
+        Some(end) => (end.to_rfc2822(), end - overlap.start),
+        None => ("now".to_string(), Utc::now() - overlap.start),
+    };
+    format!(
+        "Work slices {first} and {second} overlap from {start} to {end}, for {duration}",
+        first = describe(overlap.first),
+        second = describe(overlap.second),
+        start = overlap.start.to_rfc2822(),
+        duration = format_duration(duration),
+    )
+}
+
+fn view_trash(state: &State) {
+    println!(
+        "{}",
+        state
+            .trash()
+            .iter()
+            .map(format_trash_entry)
+            .reduce(|acc, e| format!("{acc}\n{e}"))
+            .unwrap_or_else(|| "The trash is empty.".to_string())
+    );
+}
+
+fn format_trash_entry(entry: &TrashEntry) -> String {
+    let deleted_at = entry.deleted_at.to_rfc2822();
+    match &entry.item {
+        TrashedItem::Project(project) => format!(
+            "Project {id} ({name}) with {count} work slices - deleted at {deleted_at}",
+            id = project.id,
+            name = project.name,
+            count = project.work_slices.len() + usize::from(project.current_slice.is_some()),
+        ),
+        TrashedItem::WorkSlice {
+            project_id,
+            work_slice,
+        } => {
+            let (start, payment) = match work_slice {
+                WorkSliceData::Complete(x) => (x.start, x.payment),
+                WorkSliceData::Incomplete(x) => (x.start, x.payment),
+            };
+            format!(
+                "Work slice {id} for project {project_id}: Payment is {payment} - started at {start} - deleted at {deleted_at}",
+                id = work_slice.id(),
+                start = start.to_rfc2822(),
+            )
+        }
+    }
+}
+
+fn create_project(
+    state: &mut State,
+    name: String,
+    description: String,
+    default_payment: Option<Payment>,
+    client: Option<u64>,
+) {
+    let client = client.map(|x| unsafe { ClientId::new(x) });
+    if client.is_some_and(|x| state.client_from_id(x).is_none()) {
+        eprintln!("That client id ({}) is invalid!", unsafe {
+            client.unwrap().inner()
+        });
+        return;
+    }
+    let id = state.new_project(name, description, default_payment);
+    if client.is_some() {
+        state
+            .set_project_client(id, client)
+            .expect("The project and client both exist");
+    }
+    println!("Created project {id}", id = unsafe { id.inner() });
+}
+
+/// Describes a client on one line, with the totals for all of its projects.
+fn format_client(state: &State, client: &Client) -> String {
+    let projects = state.client_projects(client.id()).count();
+    format!(
+        "Client {id}: {name} ({projects} project{s}, {duration}, {payment})",
+        id = unsafe { client.id().inner() },
+        name = client.name(),
+        s = if projects == 1 { "" } else { "s" },
+        duration = format_duration(state.client_duration(client.id()).unwrap()),
+        payment = state.client_total_payment(client.id()).unwrap(),
+    )
+}
+
+fn view_client(state: &State, client_id: u64) {
+    let Some(client) = state.client_from_id(unsafe { ClientId::new(client_id) }) else {
+        eprintln!("That client id ({client_id}) is invalid!");
+        return;
     };
-    let data: Vec<ProjectData> = serde_json::from_str(&file)?;
-    Ok(data)
+    let projects = state
+        .client_projects(client.id())
+        .map(|x| {
+            format!(
+                "  {id} - {name}{archived}",
+                id = unsafe { x.id().inner() },
+                name = x.name(),
+                archived = if x.is_archived() { " [archived]" } else { "" },
+            )
+        })
+        .reduce(|acc, e| format!("{acc}\n{e}"))
+        .unwrap_or_else(|| "  none".to_string());
+    println!(
+        "{top_line}\n- contact: {contact}\n- billing address: {address}\n- default payment: {payment}\n- projects:\n{projects}",
+        top_line = format_client(state, client),
+        contact = client.contact(),
+        address = client.billing_address(),
+        payment = client
+            .default_payment()
+            .map_or_else(|| "none".to_string(), |x| x.to_string()),
+    );
+}
+
+fn load_history(file_name: &str) -> Result<History, Box<dyn Error>> {
+    match std::fs::read_to_string(file_name) {
+        Ok(x) => Ok(serde_json::from_str(&x)?),
+        Err(x) if x.kind() == ErrorKind::NotFound => Ok(History::default()),
+        Err(x) => Err(Box::new(x)),
+    }
 }
-fn save_data(file_name: &str, _changes: Vec<Change>, final_data: Vec<&Project>) {
+
+/// Saves the undo and redo history, keeping at most `limit` changes of each.
+fn save_history(file_name: &str, state: &State, limit: usize) {
+    let recent = |changes: &[Change]| changes[changes.len().saturating_sub(limit)..].to_vec();
+    let history = History {
+        undo: recent(state.undo_history()),
+        redo: recent(state.redo_history()),
+    };
+    let temp_file_name = format!("{file_name}.tmp");
     std::fs::write(
-        file_name,
-        serde_json::to_string(
-            &final_data
-                .into_iter()
-                .map(|x| ProjectData {
-                    name: x.name().to_string(),
-                    description: x.description().to_string(),
-                    work_slices: x
-                        .complete_work_slices()
-                        .map(|x| CompleteWorkSliceData {
-                            start: x.start(),
-                            end: x.completion(),
-                            payment: x.payment(),
-                            id: unsafe { x.id().inner() },
-                        })
-                        .collect(),
-                    current_slice: x.current_work_slice().map(|x| IncompleteWorkSliceData {
-                        start: x.start(),
-                        payment: x.payment(),
-                        id: unsafe { x.id().inner() },
-                    }),
-                    id: unsafe { x.id().inner() },
-                })
-                .collect::<Vec<_>>(),
-        )
-        .expect("Failed to serialize data"),
+        &temp_file_name,
+        serde_json::to_string(&history).expect("Failed to serialize the undo history"),
+    )
+    .and_then(|()| std::fs::rename(&temp_file_name, file_name))
+    .expect("Failed to save the undo history");
+}
+
+/// Renders a statement with a template file, or the format's default template if none is given,
+/// and writes it to a file, or prints it if no output file is given.
+fn render_statement(
+    statement: &Statement,
+    format: RenderFormat,
+    template: Option<String>,
+    output: Option<String>,
+) {
+    let template = match template {
+        Some(file_name) => {
+            let parsed = std::fs::read_to_string(&file_name)
+                .map_err(|err| err.to_string())
+                .and_then(|x| {
+                    x.parse::<Template>()
+                        .map_err(|x| describe_template_error(&x))
+                });
+            match parsed {
+                Ok(template) => template,
+                Err(err) => {
+                    eprintln!("Couldn't use the template {file_name}: {err}");
+                    return;
+                }
+            }
+        }
+        None => Template::default_for(format),
+    };
+    let document = statement.render(&template, format);
+    match output {
+        Some(file_name) => match std::fs::write(&file_name, document) {
+            Ok(()) => println!(
+                "Rendered {count} line item{s} to {file_name}",
+                count = statement.lines.len(),
+                s = if statement.lines.len() == 1 { "" } else { "s" },
+            ),
+            Err(err) => eprintln!("Couldn't write to {file_name}: {err}"),
+        },
+        None => print!("{document}"),
+    }
+}
+
+fn describe_template_error(error: &TemplateError) -> String {
+    match error {
+        TemplateError::UnclosedTag { line } => {
+            format!("a {{{{ on line {line} isn't closed with }}}} on the same line")
+        }
+        TemplateError::UnknownField { line, name } => {
+            format!("{name} on line {line} isn't a field which can be used there")
+        }
+        TemplateError::UnclosedSection { line, name } => {
+            format!("the {name} section opened on line {line} is never closed")
+        }
+        TemplateError::UnexpectedClose { line, name } => {
+            format!("the {name} section closed on line {line} isn't the one most recently opened")
+        }
+    }
+}
+
+fn format_report(report: &Report, empty: bool) -> String {
+    let rows = report
+        .rows
+        .iter()
+        .filter(|x| empty || x.work_slices > 0)
+        .map(|x| {
+            format!(
+                "{name} - {count} work slice{s}, lasting {duration} and earning {payment}",
+                name = report.period.name_of(x.start),
+                count = x.work_slices,
+                s = if x.work_slices == 1 { "" } else { "s" },
+                duration = format_duration(x.duration),
+                payment = x.payment,
+            )
+        })
+        .reduce(|acc, e| format!("{acc}\n{e}"))
+        .unwrap_or_else(|| "No work in that range.".to_string());
+    format!(
+        "Work by {period} from {start} to {end}:\n{rows}\nTotal: {duration}, earning {payment}",
+        period = report.period,
+        start = report.start.to_rfc2822(),
+        end = report.end.to_rfc2822(),
+        duration = format_duration(report.total_duration),
+        payment = report.total_payment,
+    )
+}
+
+fn format_work_in_range(state: &State, work: &WorkInRange) -> String {
+    format!(
+        "{id} - {project} - {start} - {end}, {duration}, {payment}{clipped}{ongoing}",
+        id = unsafe { work.work_slice_id.inner() },
+        project = state
+            .project_from_id(work.project_id)
+            .map_or("unknown project", Project::name),
+        start = work.start.to_rfc2822(),
+        end = work.end.to_rfc2822(),
+        duration = format_duration(work.duration),
+        payment = work.payment,
+        clipped = if work.clipped { " [clipped]" } else { "" },
+        ongoing = if work.ongoing { " [ongoing]" } else { "" },
     )
-    .expect("Failed to save data");
 }
 
 fn format_complete_work_slice(work_slice: &CompleteWorkSlice) -> String {
     format!(
-        "{id} - {start} - {end}, {payment}, {total_payment}",
+        "{id} - {start} - {end}, {payment}, {total_payment}{annotation}",
         id = unsafe { work_slice.id().inner() },
         start = work_slice.start().to_rfc2822(),
         end = work_slice.completion().to_rfc2822(),
         payment = work_slice.payment(),
         total_payment = work_slice.calculate_payment(),
+        annotation = format_annotation(work_slice.note(), work_slice.tags()),
     )
 }
 
@@ -506,9 +2207,10 @@ fn format_project_not_verbose(project: &Project) -> String {
     };
     let incomplete_work_string = match &incomplete_work {
         Some(work) => format!(
-            "{id} - started {duration} ago",
+            "{id} - started {duration} ago{paused}",
             id = unsafe { work.id().inner() },
-            duration = format_duration(work.duration())
+            duration = format_duration(work.duration()),
+            paused = if work.is_paused() { ", paused" } else { "" },
         ),
         None => "not working".to_string(),
     };
@@ -525,14 +2227,17 @@ fn format_project_not_verbose(project: &Project) -> String {
     let total_payment = completed_work
         .iter()
         .map(|x| x.calculate_payment())
-        .sum::<MoneyExact>()
-        + incomplete_work
-            .map(|x| x.calculate_payment_so_far())
-            .unwrap_or(MoneyExact::new(0.0).unwrap());
+        .chain(incomplete_work.map(|x| x.calculate_payment_so_far()))
+        .sum::<MoneyTotals>();
     let top_line = format!(
-        "Project {id}: {name} ({total_duration}, {total_payment})",
+        "Project {id}: {name}{archived} ({total_duration}, {total_payment})",
         id = unsafe { project.id().inner() },
         name = project.name(),
+        archived = if project.is_archived() {
+            " [archived]"
+        } else {
+            ""
+        },
     );
     format!("{top_line}\n{completed_work_string}\n- current work: {incomplete_work_string}")
 }
@@ -575,17 +2280,24 @@ fn format_project_verbose(project: &Project) -> String {
     let total_payment = completed_work
         .iter()
         .map(|x| x.calculate_payment())
-        .sum::<MoneyExact>()
-        + incomplete_work
-            .map(|x| x.calculate_payment_so_far())
-            .unwrap_or(MoneyExact::new(0.0).unwrap());
+        .chain(incomplete_work.map(|x| x.calculate_payment_so_far()))
+        .sum::<MoneyTotals>();
     let top_line = format!(
-        "Project {id}: {name} ({total_duration}, {total_payment})",
+        "Project {id}: {name}{archived} ({total_duration}, {total_payment})",
         id = unsafe { project.id().inner() },
         name = project.name(),
+        archived = if project.is_archived() {
+            " [archived]"
+        } else {
+            ""
+        },
     );
     let description = project.description();
-    format!("{top_line}\n{description}\n{completed_work_string}\n- current work: {incomplete_work_string}")
+    let default_payment = match project.default_payment() {
+        Some(payment) => payment.to_string(),
+        None => "none".to_string(),
+    };
+    format!("{top_line}\n{description}\n- default payment: {default_payment}\n{completed_work_string}\n- current work: {incomplete_work_string}")
 }
 
 fn format_duration(duration: Duration) -> String {
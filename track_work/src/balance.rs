@@ -0,0 +1,355 @@
+use std::{collections::BTreeSet, iter::Sum, ops::Add};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    Change, ClientId, Currency, InvalidClientId, InvalidProjectId, Money, MoneyExact, MoneyTotals,
+    Project, ProjectId, ReceivedPaymentData, RecordPaymentError, State,
+};
+
+/// Represents the id of a payment received for a project
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ReceivedPaymentId(u64);
+impl ReceivedPaymentId {
+    /// # Safety
+    /// The ID must refer to a payment which exists (or existed) in a `State`,
+    /// otherwise it won't be found by any lookups.
+    pub unsafe fn new(id: u64) -> Self {
+        Self(id)
+    }
+
+    /// # Safety
+    /// The raw ID is only meaningful to the `State` that created it.
+    pub unsafe fn inner(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Money which has been paid for a project's work.
+#[derive(Debug, Clone)]
+pub struct ReceivedPayment {
+    amount: Money,
+    date: DateTime<Utc>,
+    reference: String,
+    id: ReceivedPaymentId,
+}
+impl PartialEq for ReceivedPayment {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+impl Eq for ReceivedPayment {}
+impl ReceivedPayment {
+    pub fn amount(&self) -> Money {
+        self.amount
+    }
+    /// Returns when the money was received.
+    pub fn date(&self) -> DateTime<Utc> {
+        self.date
+    }
+    /// Returns what the payment was for, like an invoice number or a bank reference.
+    pub fn reference(&self) -> &str {
+        &self.reference
+    }
+    pub fn id(&self) -> ReceivedPaymentId {
+        self.id
+    }
+}
+impl From<ReceivedPaymentData> for ReceivedPayment {
+    fn from(value: ReceivedPaymentData) -> Self {
+        Self {
+            amount: value.amount,
+            date: value.date,
+            reference: value.reference,
+            id: unsafe { ReceivedPaymentId::new(value.id) },
+        }
+    }
+}
+
+/// How much money is owed for work, and how much has been received, in each currency.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Balance {
+    /// The money earned by complete work slices.
+    pub owed: MoneyTotals,
+    pub received: MoneyTotals,
+}
+impl Balance {
+    /// Returns every currency which money is owed or has been received in, in order.
+    pub fn currencies(&self) -> BTreeSet<Currency> {
+        self.owed
+            .iter()
+            .chain(self.received.iter())
+            .map(|x| x.currency())
+            .collect()
+    }
+
+    /// Returns how much more is owed than has been received in a currency,
+    /// or zero if all of it has been paid.
+    pub fn outstanding(&self, currency: Currency) -> MoneyExact {
+        self.owed
+            .get(currency)
+            .saturating_sub(self.received.get(currency))
+            .unwrap()
+    }
+
+    /// Returns how much more has been received than is owed in a currency,
+    /// or zero if it hasn't all been paid yet.
+    pub fn overpaid(&self, currency: Currency) -> MoneyExact {
+        self.received
+            .get(currency)
+            .saturating_sub(self.owed.get(currency))
+            .unwrap()
+    }
+
+    /// Returns how much is outstanding in each currency which hasn't been fully paid.
+    pub fn total_outstanding(&self) -> MoneyTotals {
+        self.currencies()
+            .into_iter()
+            .map(|x| self.outstanding(x))
+            .filter(|x| *x != MoneyExact::zero(x.currency()))
+            .sum()
+    }
+
+    /// Returns true if nothing is outstanding in any currency.
+    pub fn is_settled(&self) -> bool {
+        self.total_outstanding().is_empty()
+    }
+}
+impl Add<Balance> for Balance {
+    type Output = Balance;
+
+    fn add(self, rhs: Balance) -> Self::Output {
+        Balance {
+            owed: self.owed + rhs.owed,
+            received: self.received + rhs.received,
+        }
+    }
+}
+impl Sum<Balance> for Balance {
+    fn sum<I: Iterator<Item = Balance>>(iter: I) -> Self {
+        iter.fold(Balance::default(), |acc, e| acc + e)
+    }
+}
+
+impl Project {
+    /// Returns how much is owed for this project's complete work slices,
+    /// and how much has been received for it, in each currency.
+    pub fn balance(&self) -> Balance {
+        Balance {
+            owed: self.total_payment(),
+            received: self.total_received(),
+        }
+    }
+
+    /// Returns the amount of money received for this project, in each currency.
+    pub fn total_received(&self) -> MoneyTotals {
+        self.received_payments()
+            .map(|x| MoneyExact::from(x.amount()))
+            .sum()
+    }
+}
+
+impl State {
+    /// Records money received for a project's work, and returns the payment's ID.
+    /// Payments can be recorded for archived projects.
+    /// Fails if the project doesn't exist or the amount is zero.
+    pub fn record_received_payment(
+        &mut self,
+        project_id: ProjectId,
+        amount: Money,
+        date: DateTime<Utc>,
+        reference: String,
+    ) -> Result<ReceivedPaymentId, RecordPaymentError> {
+        if amount.amount() == 0 {
+            return Err(RecordPaymentError::ZeroAmount);
+        }
+        let id = self.previous_received_payment_id + 1;
+        let data = ReceivedPaymentData {
+            amount,
+            date,
+            reference,
+            id,
+        };
+        self.project_from_id_mut(project_id)
+            .ok_or(RecordPaymentError::InvalidProjectId)?
+            .insert_received_payment(data.clone().into());
+        self.previous_received_payment_id = id;
+        self.record_change(Change::PaymentReceived {
+            project_id: unsafe { project_id.inner() },
+            payment: data,
+        });
+        Ok(unsafe { ReceivedPaymentId::new(id) })
+    }
+
+    /// Deletes a payment which was recorded by mistake.
+    /// Returns false if there isn't a payment with that ID.
+    pub fn delete_received_payment(&mut self, id: ReceivedPaymentId) -> bool {
+        let Some(project) = self
+            .projects
+            .iter_mut()
+            .find(|x| x.received_payment_from_id(id).is_some())
+        else {
+            return false;
+        };
+        let payment = project.take_received_payment(id).unwrap();
+        let project_id = unsafe { project.id().inner() };
+        self.record_change(Change::ReceivedPaymentDeleted {
+            project_id,
+            payment: (&payment).into(),
+        });
+        true
+    }
+
+    /// Returns the payment with the given ID, and the ID of the project it was for, if it exists.
+    pub fn received_payment_from_id(
+        &self,
+        id: ReceivedPaymentId,
+    ) -> Option<(ProjectId, &ReceivedPayment)> {
+        self.all_projects()
+            .find_map(|x| Some((x.id(), x.received_payment_from_id(id)?)))
+    }
+
+    /// Returns how much is owed for a project's complete work slices,
+    /// and how much has been received for it. Fails if the project doesn't exist.
+    pub fn project_balance(&self, id: ProjectId) -> Result<Balance, InvalidProjectId> {
+        Ok(self.project_from_id(id).ok_or(InvalidProjectId)?.balance())
+    }
+
+    /// Returns how much is owed for the complete work slices of all of a client's projects,
+    /// and how much has been received for them. Fails if the client doesn't exist.
+    pub fn client_balance(&self, id: ClientId) -> Result<Balance, InvalidClientId> {
+        self.client_from_id(id).ok_or(InvalidClientId)?;
+        Ok(self.client_projects(id).map(Project::balance).sum())
+    }
+
+    /// Returns how much is owed for the complete work slices of all projects,
+    /// including archived ones, and how much has been received for them.
+    pub fn balance(&self) -> Balance {
+        self.all_projects().map(Project::balance).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use crate::test_util::assert_replays;
+    use crate::{
+        Annotation, Currency, MemoryStorage, Money, MoneyExact, Payment, ProjectId,
+        RecordPaymentError, State,
+    };
+
+    fn gbp(amount: u32) -> MoneyExact {
+        Money::new(amount, Currency::Gbp).into()
+    }
+
+    /// Returns a state with two projects, which are owed £30 and £10.
+    fn projects_with_work() -> (State, [ProjectId; 2]) {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let payment = Some(Payment::Hourly(Money::new(1000, Currency::Gbp)));
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let first = state.new_project("first".to_string(), "".to_string(), None);
+        let second = state.new_project("second".to_string(), "".to_string(), None);
+        state
+            .add_complete_work_slice(
+                first,
+                now - hours(5),
+                now - hours(2),
+                payment,
+                Annotation::default(),
+            )
+            .unwrap();
+        state
+            .add_complete_work_slice(
+                second,
+                now - hours(2),
+                now - hours(1),
+                payment,
+                Annotation::default(),
+            )
+            .unwrap();
+        (state, [first, second])
+    }
+
+    #[test]
+    fn received_payments_reduce_balance() {
+        let (mut state, [first, _]) = projects_with_work();
+        assert_eq!(
+            state.record_received_payment(
+                first,
+                Money::new(0, Currency::Gbp),
+                Utc::now(),
+                "".to_string()
+            ),
+            Err(RecordPaymentError::ZeroAmount)
+        );
+        state
+            .record_received_payment(
+                first,
+                Money::new(1000, Currency::Gbp),
+                Utc::now(),
+                "INV-1".to_string(),
+            )
+            .unwrap();
+        let balance = state.project_balance(first).unwrap();
+        assert_eq!(balance.outstanding(Currency::Gbp), gbp(2000));
+        assert!(!balance.is_settled());
+    }
+
+    #[test]
+    fn overpayments_count_towards_overall_balance() {
+        let (mut state, [first, second]) = projects_with_work();
+        state
+            .record_received_payment(
+                first,
+                Money::new(1000, Currency::Gbp),
+                Utc::now(),
+                "INV-1".to_string(),
+            )
+            .unwrap();
+        state
+            .record_received_payment(
+                second,
+                Money::new(1500, Currency::Gbp),
+                Utc::now(),
+                "INV-2".to_string(),
+            )
+            .unwrap();
+        let balance = state.project_balance(second).unwrap();
+        assert_eq!(balance.outstanding(Currency::Gbp), gbp(0));
+        assert_eq!(balance.overpaid(Currency::Gbp), gbp(500));
+        assert!(balance.is_settled());
+        let balance = state.balance();
+        assert_eq!(balance.owed.get(Currency::Gbp), gbp(4000));
+        assert_eq!(balance.received.get(Currency::Gbp), gbp(2500));
+        assert_eq!(balance.outstanding(Currency::Gbp), gbp(1500));
+    }
+
+    #[test]
+    fn delete_received_payments() {
+        let (mut state, [_, second]) = projects_with_work();
+        let payment = state
+            .record_received_payment(
+                second,
+                Money::new(1000, Currency::Gbp),
+                Utc::now(),
+                "INV-2".to_string(),
+            )
+            .unwrap();
+        assert!(state.delete_received_payment(payment));
+        assert!(!state.delete_received_payment(payment));
+        assert_eq!(
+            state
+                .project_balance(second)
+                .unwrap()
+                .outstanding(Currency::Gbp),
+            gbp(1000)
+        );
+        let replayed = assert_replays(&mut state);
+        assert!(replayed.received_payment_from_id(payment).is_none());
+
+        state.undo().unwrap();
+        assert!(state.received_payment_from_id(payment).is_some());
+    }
+}
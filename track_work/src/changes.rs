@@ -1,30 +1,438 @@
+use std::collections::BTreeSet;
+
 use chrono::{DateTime, Utc};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-use super::Payment;
+use super::{
+    Annotation, ClientData, CompleteWorkSliceData, IncompleteWorkSliceData, InvoiceData, Payment,
+    ProjectData, ReceivedPaymentData, TrashEntry, WorkSliceData,
+};
 
+/// A single modification to a `State`.
+/// Each change carries enough data to be reversed, see `Change::inverse`.
 #[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum Change {
     ProjectCreated {
         name: String,
         description: String,
+        #[cfg_attr(feature = "serde", serde(default))]
+        default_payment: Option<Payment>,
         id: u64,
     },
-    ProjectDeleted {
+    /// The name or description of a project was changed.
+    ProjectUpdated {
+        id: u64,
+        old_name: String,
+        old_description: String,
+        name: String,
+        description: String,
+    },
+    /// The default payment of a project was changed or removed.
+    ProjectDefaultPaymentChanged {
+        id: u64,
+        old_payment: Option<Payment>,
+        payment: Option<Payment>,
+    },
+    /// The client which a project is for was changed or removed.
+    ProjectClientChanged {
+        id: u64,
+        old_client: Option<u64>,
+        client: Option<u64>,
+    },
+    /// A project was archived, so no new work can be started for it.
+    ProjectArchived {
         id: u64,
     },
+    /// An archived project was made active again.
+    ProjectReopened {
+        id: u64,
+    },
+    /// A project was deleted. This holds all of its data at the time it was deleted.
+    /// If `deleted_at` is set, the project was moved to the trash at that time.
+    ProjectDeleted {
+        project: ProjectData,
+        #[cfg_attr(feature = "serde", serde(default))]
+        deleted_at: Option<DateTime<Utc>>,
+    },
+    /// A deleted project was restored with all of its work slices.
+    /// If `deleted_at` is set, the project was taken out of the trash.
+    ProjectRestored {
+        project: ProjectData,
+        #[cfg_attr(feature = "serde", serde(default))]
+        deleted_at: Option<DateTime<Utc>>,
+    },
+    ClientCreated {
+        client: ClientData,
+    },
+    /// A client was deleted. This only happens when creating the client is undone.
+    ClientDeleted {
+        client: ClientData,
+    },
+    /// An invoice was created, locking its work slices.
+    InvoiceCreated {
+        invoice: InvoiceData,
+    },
+    /// An invoice was deleted, unlocking its work slices.
+    InvoiceDeleted {
+        invoice: InvoiceData,
+    },
+    /// Money was received for a project's work.
+    PaymentReceived {
+        project_id: u64,
+        payment: ReceivedPaymentData,
+    },
+    /// A received payment was deleted, as it was recorded by mistake.
+    ReceivedPaymentDeleted {
+        project_id: u64,
+        payment: ReceivedPaymentData,
+    },
+    /// The current work slice of a project was completed.
+    /// If `annotation` is set, its note and tags were replaced at the same time,
+    /// and `old_annotation` holds the ones it had before.
     WorkSliceCompleted {
         project_id: u64,
         work_slice_id: u64,
         end_time: DateTime<Utc>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        old_annotation: Option<Annotation>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        annotation: Option<Annotation>,
+    },
+    /// A complete work slice was turned back into the current work slice of its project.
+    /// If `annotation` is set, its note and tags were replaced at the same time.
+    WorkSliceUncompleted {
+        project_id: u64,
+        work_slice_id: u64,
+        end_time: DateTime<Utc>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        old_annotation: Option<Annotation>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        annotation: Option<Annotation>,
     },
     WorkSliceStarted {
         project_id: u64,
         work_slice_id: u64,
         start_time: DateTime<Utc>,
         payment: Payment,
+        #[cfg_attr(feature = "serde", serde(default))]
+        note: Option<String>,
+        #[cfg_attr(feature = "serde", serde(default))]
+        tags: BTreeSet<String>,
     },
-    WorkSliceDeleted {
+    /// The current work slice of a project was paused, starting a break.
+    WorkSlicePaused {
+        project_id: u64,
+        work_slice_id: u64,
+        time: DateTime<Utc>,
+    },
+    /// Pausing a work slice was undone, as if it had never been paused.
+    WorkSlicePauseReverted {
+        project_id: u64,
+        work_slice_id: u64,
+        time: DateTime<Utc>,
+    },
+    /// A paused work slice was resumed, ending the break which started at `paused_at`.
+    WorkSliceResumed {
         project_id: u64,
         work_slice_id: u64,
+        paused_at: DateTime<Utc>,
+        time: DateTime<Utc>,
     },
+    /// Resuming a work slice was undone, so it's paused again.
+    WorkSliceResumeReverted {
+        project_id: u64,
+        work_slice_id: u64,
+        paused_at: DateTime<Utc>,
+        time: DateTime<Utc>,
+    },
+    /// A complete work slice was added to a project, without being started and ended.
+    WorkSliceLogged {
+        project_id: u64,
+        work_slice: CompleteWorkSliceData,
+    },
+    /// The start, end or payment of a complete work slice was changed.
+    WorkSliceEdited {
+        project_id: u64,
+        work_slice_id: u64,
+        old_start: DateTime<Utc>,
+        old_end: DateTime<Utc>,
+        old_payment: Payment,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        payment: Payment,
+    },
+    /// The note or tags of a work slice were changed.
+    WorkSliceAnnotated {
+        project_id: u64,
+        work_slice_id: u64,
+        old_note: Option<String>,
+        old_tags: BTreeSet<String>,
+        note: Option<String>,
+        tags: BTreeSet<String>,
+    },
+    /// A work slice was deleted. This holds its data at the time it was deleted.
+    /// If `deleted_at` is set, the work slice was moved to the trash at that time.
+    WorkSliceDeleted {
+        project_id: u64,
+        work_slice: WorkSliceData,
+        #[cfg_attr(feature = "serde", serde(default))]
+        deleted_at: Option<DateTime<Utc>>,
+    },
+    /// A deleted work slice was put back into its project.
+    /// If `deleted_at` is set, the work slice was taken out of the trash.
+    WorkSliceRestored {
+        project_id: u64,
+        work_slice: WorkSliceData,
+        #[cfg_attr(feature = "serde", serde(default))]
+        deleted_at: Option<DateTime<Utc>>,
+    },
+    /// Items in the trash were permanently deleted.
+    TrashPurged {
+        entries: Vec<TrashEntry>,
+    },
+    /// Items which were permanently deleted from the trash were put back into it.
+    TrashPurgeReverted {
+        entries: Vec<TrashEntry>,
+    },
+}
+impl Change {
+    /// Returns the change which reverses this one.
+    pub fn inverse(&self) -> Change {
+        match self.clone() {
+            Change::ProjectCreated {
+                name,
+                description,
+                default_payment,
+                id,
+            } => Change::ProjectDeleted {
+                project: ProjectData {
+                    name,
+                    description,
+                    work_slices: Vec::new(),
+                    current_slice: None,
+                    default_payment,
+                    archived: false,
+                    client: None,
+                    received_payments: Vec::new(),
+                    id,
+                },
+                deleted_at: None,
+            },
+            Change::ProjectUpdated {
+                id,
+                old_name,
+                old_description,
+                name,
+                description,
+            } => Change::ProjectUpdated {
+                id,
+                old_name: name,
+                old_description: description,
+                name: old_name,
+                description: old_description,
+            },
+            Change::ProjectDefaultPaymentChanged {
+                id,
+                old_payment,
+                payment,
+            } => Change::ProjectDefaultPaymentChanged {
+                id,
+                old_payment: payment,
+                payment: old_payment,
+            },
+            Change::ProjectClientChanged {
+                id,
+                old_client,
+                client,
+            } => Change::ProjectClientChanged {
+                id,
+                old_client: client,
+                client: old_client,
+            },
+            Change::ProjectArchived { id } => Change::ProjectReopened { id },
+            Change::ProjectReopened { id } => Change::ProjectArchived { id },
+            Change::ProjectDeleted {
+                project,
+                deleted_at,
+            } => Change::ProjectRestored {
+                project,
+                deleted_at,
+            },
+            Change::ProjectRestored {
+                project,
+                deleted_at,
+            } => Change::ProjectDeleted {
+                project,
+                deleted_at,
+            },
+            Change::ClientCreated { client } => Change::ClientDeleted { client },
+            Change::ClientDeleted { client } => Change::ClientCreated { client },
+            Change::InvoiceCreated { invoice } => Change::InvoiceDeleted { invoice },
+            Change::InvoiceDeleted { invoice } => Change::InvoiceCreated { invoice },
+            Change::PaymentReceived {
+                project_id,
+                payment,
+            } => Change::ReceivedPaymentDeleted {
+                project_id,
+                payment,
+            },
+            Change::ReceivedPaymentDeleted {
+                project_id,
+                payment,
+            } => Change::PaymentReceived {
+                project_id,
+                payment,
+            },
+            Change::WorkSliceCompleted {
+                project_id,
+                work_slice_id,
+                end_time,
+                old_annotation,
+                annotation,
+            } => Change::WorkSliceUncompleted {
+                project_id,
+                work_slice_id,
+                end_time,
+                old_annotation: annotation,
+                annotation: old_annotation,
+            },
+            Change::WorkSliceUncompleted {
+                project_id,
+                work_slice_id,
+                end_time,
+                old_annotation,
+                annotation,
+            } => Change::WorkSliceCompleted {
+                project_id,
+                work_slice_id,
+                end_time,
+                old_annotation: annotation,
+                annotation: old_annotation,
+            },
+            Change::WorkSliceStarted {
+                project_id,
+                work_slice_id,
+                start_time,
+                payment,
+                note,
+                tags,
+            } => Change::WorkSliceDeleted {
+                project_id,
+                work_slice: WorkSliceData::Incomplete(IncompleteWorkSliceData {
+                    start: start_time,
+                    payment,
+                    breaks: Vec::new(),
+                    paused_at: None,
+                    note,
+                    tags,
+                    id: work_slice_id,
+                }),
+                deleted_at: None,
+            },
+            Change::WorkSlicePaused {
+                project_id,
+                work_slice_id,
+                time,
+            } => Change::WorkSlicePauseReverted {
+                project_id,
+                work_slice_id,
+                time,
+            },
+            Change::WorkSlicePauseReverted {
+                project_id,
+                work_slice_id,
+                time,
+            } => Change::WorkSlicePaused {
+                project_id,
+                work_slice_id,
+                time,
+            },
+            Change::WorkSliceResumed {
+                project_id,
+                work_slice_id,
+                paused_at,
+                time,
+            } => Change::WorkSliceResumeReverted {
+                project_id,
+                work_slice_id,
+                paused_at,
+                time,
+            },
+            Change::WorkSliceResumeReverted {
+                project_id,
+                work_slice_id,
+                paused_at,
+                time,
+            } => Change::WorkSliceResumed {
+                project_id,
+                work_slice_id,
+                paused_at,
+                time,
+            },
+            Change::WorkSliceLogged {
+                project_id,
+                work_slice,
+            } => Change::WorkSliceDeleted {
+                project_id,
+                work_slice: WorkSliceData::Complete(work_slice),
+                deleted_at: None,
+            },
+            Change::WorkSliceEdited {
+                project_id,
+                work_slice_id,
+                old_start,
+                old_end,
+                old_payment,
+                start,
+                end,
+                payment,
+            } => Change::WorkSliceEdited {
+                project_id,
+                work_slice_id,
+                old_start: start,
+                old_end: end,
+                old_payment: payment,
+                start: old_start,
+                end: old_end,
+                payment: old_payment,
+            },
+            Change::WorkSliceAnnotated {
+                project_id,
+                work_slice_id,
+                old_note,
+                old_tags,
+                note,
+                tags,
+            } => Change::WorkSliceAnnotated {
+                project_id,
+                work_slice_id,
+                old_note: note,
+                old_tags: tags,
+                note: old_note,
+                tags: old_tags,
+            },
+            Change::WorkSliceDeleted {
+                project_id,
+                work_slice,
+                deleted_at,
+            } => Change::WorkSliceRestored {
+                project_id,
+                work_slice,
+                deleted_at,
+            },
+            Change::WorkSliceRestored {
+                project_id,
+                work_slice,
+                deleted_at,
+            } => Change::WorkSliceDeleted {
+                project_id,
+                work_slice,
+                deleted_at,
+            },
+            Change::TrashPurged { entries } => Change::TrashPurgeReverted { entries },
+            Change::TrashPurgeReverted { entries } => Change::TrashPurged { entries },
+        }
+    }
 }
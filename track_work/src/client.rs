@@ -0,0 +1,247 @@
+use chrono::TimeDelta;
+
+use crate::{
+    Change, ClientData, InvalidClientId, MoneyTotals, Payment, Project, ProjectId,
+    SetProjectClientError, State,
+};
+
+/// Represents the id of a client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ClientId(u64);
+impl ClientId {
+    /// # Safety
+    /// The ID must refer to a client which exists (or existed) in a `State`,
+    /// otherwise it won't be found by any lookups.
+    pub unsafe fn new(id: u64) -> Self {
+        Self(id)
+    }
+
+    /// # Safety
+    /// The raw ID is only meaningful to the `State` that created it.
+    pub unsafe fn inner(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Represents a "client" - someone who pays for projects,
+/// with the details needed to bill them.
+#[derive(Debug)]
+pub struct Client {
+    name: String,
+    contact: String,
+    billing_address: String,
+    default_payment: Option<Payment>,
+    id: ClientId,
+}
+impl PartialEq for Client {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+impl Eq for Client {}
+impl Client {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    /// Returns how to get in touch with the client, like an email address.
+    pub fn contact(&self) -> &str {
+        &self.contact
+    }
+    pub fn billing_address(&self) -> &str {
+        &self.billing_address
+    }
+    /// Returns the payment used for new work on the client's projects
+    /// when no payment is given and the project has no default payment, if there is one.
+    pub fn default_payment(&self) -> Option<Payment> {
+        self.default_payment
+    }
+    pub fn id(&self) -> ClientId {
+        self.id
+    }
+}
+impl From<ClientData> for Client {
+    fn from(value: ClientData) -> Self {
+        Self {
+            name: value.name,
+            contact: value.contact,
+            billing_address: value.billing_address,
+            default_payment: value.default_payment,
+            id: unsafe { ClientId::new(value.id) },
+        }
+    }
+}
+
+impl State {
+    /// Creates a new client, and returns its ID.
+    /// The default payment is used for work on the client's projects
+    /// when no payment is given and the project has no default payment.
+    pub fn new_client(
+        &mut self,
+        name: String,
+        contact: String,
+        billing_address: String,
+        default_payment: Option<Payment>,
+    ) -> ClientId {
+        self.previous_client_id += 1;
+        let data = ClientData {
+            name,
+            contact,
+            billing_address,
+            default_payment,
+            id: self.previous_client_id,
+        };
+        self.clients.push(data.clone().into());
+        self.record_change(Change::ClientCreated { client: data });
+        unsafe { ClientId::new(self.previous_client_id) }
+    }
+
+    /// Returns all clients.
+    pub fn all_clients(&self) -> impl Iterator<Item = &Client> + '_ {
+        self.clients.iter()
+    }
+
+    /// Returns the client with the given ID, if it exists.
+    pub fn client_from_id(&self, id: ClientId) -> Option<&Client> {
+        self.clients.iter().find(|x| x.id() == id)
+    }
+
+    /// Returns the client which a project is for, if it has one.
+    pub fn client_of_project(&self, project: &Project) -> Option<&Client> {
+        project.client().and_then(|x| self.client_from_id(x))
+    }
+
+    /// Returns the projects which are for a client, including archived ones.
+    pub fn client_projects(&self, id: ClientId) -> impl Iterator<Item = &Project> + '_ {
+        self.all_projects().filter(move |x| x.client() == Some(id))
+    }
+
+    /// Makes a project be for a client, or for no client if `client` is `None`.
+    /// Fails if the project or the client doesn't exist.
+    pub fn set_project_client(
+        &mut self,
+        id: ProjectId,
+        client: Option<ClientId>,
+    ) -> Result<(), SetProjectClientError> {
+        if client.is_some_and(|x| self.client_from_id(x).is_none()) {
+            return Err(SetProjectClientError::InvalidClientId);
+        }
+        let project = self
+            .project_from_id_mut(id)
+            .ok_or(SetProjectClientError::InvalidProjectId)?;
+        let old_client = project.client();
+        project.set_client(client);
+        if old_client != client {
+            self.record_change(Change::ProjectClientChanged {
+                id: unsafe { id.inner() },
+                old_client: old_client.map(|x| unsafe { x.inner() }),
+                client: client.map(|x| unsafe { x.inner() }),
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the time worked on the complete work slices of all of a client's projects,
+    /// not including breaks. Fails if the client doesn't exist.
+    pub fn client_duration(&self, id: ClientId) -> Result<TimeDelta, InvalidClientId> {
+        self.client_from_id(id).ok_or(InvalidClientId)?;
+        Ok(self.client_projects(id).map(Project::total_duration).sum())
+    }
+
+    /// Returns the amount of money earned by the complete work slices of all of a client's projects,
+    /// in each currency. Fails if the client doesn't exist.
+    pub fn client_total_payment(&self, id: ClientId) -> Result<MoneyTotals, InvalidClientId> {
+        self.client_from_id(id).ok_or(InvalidClientId)?;
+        Ok(self.client_projects(id).map(Project::total_payment).sum())
+    }
+
+    /// Returns the payment used for new work on a project when no payment is given:
+    /// the project's default payment, or its client's if the project doesn't have one.
+    pub(crate) fn default_payment_of(&self, project: &Project) -> Option<Payment> {
+        project
+            .default_payment()
+            .or_else(|| self.client_of_project(project)?.default_payment())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use crate::test_util::assert_replays;
+    use crate::{
+        Annotation, ClientId, Currency, MemoryStorage, Money, Payment, ProjectId,
+        SetProjectClientError, State,
+    };
+
+    /// Returns a state with a client, two projects for it without default payments,
+    /// and another project which isn't for it.
+    fn client_with_projects() -> (State, ClientId, [ProjectId; 3]) {
+        let payment = Payment::Hourly(Money::new(1000, Currency::Gbp));
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let client = state.new_client(
+            "client".to_string(),
+            "client@example.com".to_string(),
+            "1 Street, Town".to_string(),
+            Some(payment),
+        );
+        let first = state.new_project("first".to_string(), "".to_string(), None);
+        let second = state.new_project("second".to_string(), "".to_string(), None);
+        let other = state.new_project("other".to_string(), "".to_string(), Some(payment));
+        state.set_project_client(first, Some(client)).unwrap();
+        state.set_project_client(second, Some(client)).unwrap();
+        (state, client, [first, second, other])
+    }
+
+    #[test]
+    fn projects_can_only_be_for_clients_which_exist() {
+        let (mut state, _, [first, ..]) = client_with_projects();
+        assert_eq!(
+            state.set_project_client(first, Some(unsafe { ClientId::new(100) })),
+            Err(SetProjectClientError::InvalidClientId)
+        );
+        assert_eq!(
+            state.set_project_client(unsafe { ProjectId::new(100) }, None),
+            Err(SetProjectClientError::InvalidProjectId)
+        );
+    }
+
+    #[test]
+    fn clients_group_projects() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let (mut state, client, [first, second, other]) = client_with_projects();
+        // the client's default payment is used, as the projects don't have one
+        for (project, start, end) in [(first, 5, 4), (second, 3, 1), (other, 9, 8)] {
+            state
+                .add_complete_work_slice(
+                    project,
+                    now - hours(start),
+                    now - hours(end),
+                    None,
+                    Annotation::default(),
+                )
+                .unwrap();
+        }
+        assert_eq!(state.client_projects(client).count(), 2);
+        assert_eq!(state.client_duration(client), Ok(hours(3)));
+        assert_eq!(
+            state
+                .client_total_payment(client)
+                .unwrap()
+                .get(Currency::Gbp),
+            Money::new(3000, Currency::Gbp).into()
+        );
+    }
+
+    #[test]
+    fn clients_are_replayed_and_undone() {
+        let (mut state, client, _) = client_with_projects();
+        let replayed = assert_replays(&mut state);
+        assert_eq!(replayed.client_from_id(client).unwrap().name(), "client");
+        assert_eq!(replayed.client_projects(client).count(), 2);
+
+        while state.undo().is_ok() {}
+        assert_eq!(state.all_clients().count(), 0);
+        assert_eq!(state.all_projects().count(), 0);
+    }
+}
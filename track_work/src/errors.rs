@@ -37,14 +37,74 @@ pub enum NotFoundError {
     WorkSliceNotFound,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct WorkSliceNotFoundError;
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum WorkStartError {
     AlreadyStarted,
     InvalidProjectId,
     InvalidStartTime,
+    /// No payment was given, and the project doesn't have a default payment.
+    NoPayment,
+    ProjectArchived,
+    /// The overlap policy is `Forbid`, and the work would overlap with the work slices with these IDs.
+    Overlap(Vec<crate::WorkSliceId>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkPauseError {
+    InvalidProjectId,
+    NoWorkToPause,
+    AlreadyPaused,
+    /// The time was in the future, or before the work started or its last break ended.
+    InvalidTime,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkResumeError {
+    InvalidProjectId,
+    /// There is no ongoing work, or it isn't paused.
+    NotPaused,
+    /// The time was in the future, or before the work was paused.
+    InvalidTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AddWorkSliceError {
+    InvalidProjectId,
+    /// No payment was given, and the project doesn't have a default payment.
+    NoPayment,
+    ProjectArchived,
+    StartTimeAfterNow,
+    EndTimeBeforeStart,
+    /// The overlap policy is `Forbid`, and the work would overlap with the work slices with these IDs.
+    Overlap(Vec<crate::WorkSliceId>),
+}
+impl From<DataToCompleteWorkSliceError> for AddWorkSliceError {
+    fn from(value: DataToCompleteWorkSliceError) -> Self {
+        match value {
+            DataToCompleteWorkSliceError::StartTimeAfterNow => Self::StartTimeAfterNow,
+            DataToCompleteWorkSliceError::EndTimeBeforeStart => Self::EndTimeBeforeStart,
+            DataToCompleteWorkSliceError::InvalidBreaks => {
+                unreachable!("Logged work slices don't have any breaks")
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveProjectError {
+    InvalidProjectId,
+    AlreadyArchived,
+    /// Projects with ongoing work can't be archived.
+    WorkOngoing,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReopenProjectError {
+    InvalidProjectId,
+    NotArchived,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -65,17 +125,77 @@ impl From<CompleteWorkError> for WorkEndError {
 #[derive(Debug, Clone, Copy)]
 pub struct InvalidProjectId;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidClientId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetProjectClientError {
+    InvalidProjectId,
+    InvalidClientId,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnnotateWorkSliceError {
+    WorkSliceNotFound,
+    /// The work slice is on an invoice, so it can't be changed.
+    Invoiced,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CreateInvoiceError {
+    /// An invoice must have at least one work slice.
+    NoWorkSlices,
+    DueBeforeIssued,
+    InvalidClientId,
+    WorkSliceNotFound,
+    /// Only complete work slices can be invoiced.
+    WorkSliceIncomplete,
+    /// A work slice is already on another invoice.
+    AlreadyInvoiced,
+    /// A work slice is for a project which isn't for the invoice's client.
+    WrongClient,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordPaymentError {
+    InvalidProjectId,
+    /// A payment must be for more than zero.
+    ZeroAmount,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportError {
+    InvalidProjectId,
+    InvalidClientId,
+}
+
+/// A template couldn't be parsed. Each error has the line of the template it was found on, starting at 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateError {
+    /// A `{{` wasn't closed by `}}` on the same line.
+    UnclosedTag { line: usize },
+    /// The field isn't one which can be used here,
+    /// e.g. a work slice's field outside of a `lines` section.
+    UnknownField { line: usize, name: String },
+    /// A section was opened with `{{#name}}` or `{{^name}}` but never closed with `{{/name}}`.
+    UnclosedSection { line: usize, name: String },
+    /// A section was closed which isn't the one most recently opened.
+    UnexpectedClose { line: usize, name: String },
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DataToCompleteWorkSliceError {
     StartTimeAfterNow,
     EndTimeBeforeStart,
+    /// The breaks weren't in order between the start and the end.
+    InvalidBreaks,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DataToProjectError {
     CompleteWorkSlice(DataToCompleteWorkSliceError),
     /// There was an issue with the incomplete work slice:
-    /// it's start time was after `Utc::now()`
+    /// it's start time was after `Utc::now()`, or its breaks weren't in order after its start
     IncompleteWorkSlice,
 }
 
@@ -84,8 +204,159 @@ pub enum StateInitError {
     ProjectLoadError(DataToProjectError),
     DuplicateProjectId,
     DuplicateWorkSliceId,
+    DuplicateClientId,
+    /// A project is for a client which doesn't exist.
+    ClientNotFound,
+    DuplicateInvoiceNumber,
+    InvalidInvoice(CreateInvoiceError),
+    DuplicateReceivedPaymentId,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditWorkSliceError {
+    WorkSliceNotFound,
+    /// Only complete work slices can be edited.
+    WorkSliceIncomplete,
+    StartTimeAfterNow,
+    EndTimeBeforeStart,
+    /// The new start or end would leave some of the work slice's breaks outside of it.
+    BreakOutsideWorkSlice,
+    /// The overlap policy is `Forbid`, and the work would overlap with the work slices with these IDs.
+    Overlap(Vec<crate::WorkSliceId>),
+    /// The work slice is on an invoice, so it can't be changed.
+    Invoiced,
+}
+impl From<DataToCompleteWorkSliceError> for EditWorkSliceError {
+    fn from(value: DataToCompleteWorkSliceError) -> Self {
+        match value {
+            DataToCompleteWorkSliceError::StartTimeAfterNow => Self::StartTimeAfterNow,
+            DataToCompleteWorkSliceError::EndTimeBeforeStart => Self::EndTimeBeforeStart,
+            DataToCompleteWorkSliceError::InvalidBreaks => Self::BreakOutsideWorkSlice,
+        }
+    }
+}
+
+/// Describes why a change couldn't be replayed onto a `State`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidChangeError {
+    DuplicateProjectId,
+    DuplicateWorkSliceId,
+    ProjectNotFound,
+    WorkSliceNotFound,
+    AlreadyStarted,
+    NoWorkToComplete,
+    InvalidStartTime,
+    EndTimeTooEarly,
+    AlreadyArchived,
+    NotArchived,
+    ProjectArchived,
+    WorkOngoing,
+    /// Something was expected to be in the trash, but it wasn't.
+    NotInTrash,
+    NoWorkToPause,
+    AlreadyPaused,
+    NotPaused,
+    /// A break was at an invalid time, e.g. before the work started.
+    InvalidBreak,
+    DuplicateClientId,
+    ClientNotFound,
+    /// A client couldn't be deleted, as some projects or invoices are for it.
+    ClientInUse,
+    DuplicateInvoiceNumber,
+    InvalidInvoice(CreateInvoiceError),
+    InvoiceNotFound,
+    /// A work slice couldn't be changed or deleted, as it's on an invoice.
+    WorkSliceInvoiced,
+    DuplicateReceivedPaymentId,
+    ReceivedPaymentNotFound,
+}
+
+impl From<DataToCompleteWorkSliceError> for InvalidChangeError {
+    fn from(value: DataToCompleteWorkSliceError) -> Self {
+        match value {
+            DataToCompleteWorkSliceError::StartTimeAfterNow => Self::InvalidStartTime,
+            DataToCompleteWorkSliceError::EndTimeBeforeStart => Self::EndTimeTooEarly,
+            DataToCompleteWorkSliceError::InvalidBreaks => Self::InvalidBreak,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateReplayError {
+    /// The snapshot the changes were replayed onto was invalid.
+    Init(StateInitError),
+    /// The change at position `index` in the log couldn't be applied.
+    InvalidChange {
+        index: usize,
+        error: InvalidChangeError,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HistoryError {
+    NothingToUndo,
+    NothingToRedo,
+    /// The change being undone or redone didn't match the current state.
+    InvalidChange(InvalidChangeError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RestoreError {
+    NotInTrash,
+    /// The item couldn't be put back, e.g. because its project was deleted too.
+    Conflict(InvalidChangeError),
+}
+
+#[derive(Debug)]
+pub enum StateLoadError {
+    /// The storage failed to load the saved data.
+    Storage(Box<dyn std::error::Error>),
+    /// The saved data was invalid.
+    Replay(StateReplayError),
+}
+
+/// A file was changed by something else after it was loaded,
+/// so saving over it would lose those changes.
+#[derive(Debug, Clone)]
+pub struct FileModifiedError {
+    pub path: std::path::PathBuf,
+}
+
+/// Two amounts of money couldn't be combined because they're in different currencies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CurrencyMismatchError {
+    pub expected: crate::Currency,
+    pub found: crate::Currency,
+}
+
+/// Two amounts of money couldn't be added together.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AddMoneyError {
+    CurrencyMismatch(CurrencyMismatchError),
+    /// The total is too large to be stored.
+    Overflow,
+}
+impl From<CurrencyMismatchError> for AddMoneyError {
+    fn from(value: CurrencyMismatchError) -> Self {
+        Self::CurrencyMismatch(value)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownCurrencyError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownDurationRoundingError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownOverlapPolicyError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownReportPeriodError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnknownRenderFormatError;
+
 derive_debug_error! {
     CompleteWorkError
     WorkStartNowError
@@ -93,7 +364,34 @@ derive_debug_error! {
     NotFoundError
     WorkSliceNotFoundError
     WorkStartError
+    WorkPauseError
+    WorkResumeError
+    AddWorkSliceError
+    ArchiveProjectError
+    ReopenProjectError
     InvalidProjectId
+    InvalidClientId
+    SetProjectClientError
+    ReportError
+    TemplateError
+    AnnotateWorkSliceError
+    CreateInvoiceError
+    RecordPaymentError
     DataToCompleteWorkSliceError
     DataToProjectError
+    StateInitError
+    EditWorkSliceError
+    InvalidChangeError
+    StateReplayError
+    StateLoadError
+    HistoryError
+    RestoreError
+    FileModifiedError
+    CurrencyMismatchError
+    AddMoneyError
+    UnknownCurrencyError
+    UnknownDurationRoundingError
+    UnknownOverlapPolicyError
+    UnknownReportPeriodError
+    UnknownRenderFormatError
 }
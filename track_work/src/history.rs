@@ -0,0 +1,210 @@
+use crate::{Change, HistoryError, State};
+
+impl State {
+    /// Records a change made to this state, so that it is returned by `handle_changes`
+    /// and can be undone. This clears the redo history.
+    pub(crate) fn record_change(&mut self, change: Change) {
+        self.undo_history.push(change.clone());
+        self.redo_history.clear();
+        self.changes.push(change);
+    }
+
+    /// Reverses the most recent change which hasn't been undone,
+    /// and returns it. The reversal is itself recorded as a change (see `Change::inverse`),
+    /// so that it is returned by `handle_changes`.
+    /// If the change can't be reversed, it is removed from the history.
+    pub fn undo(&mut self) -> Result<Change, HistoryError> {
+        let change = self.undo_history.pop().ok_or(HistoryError::NothingToUndo)?;
+        let inverse = change.inverse();
+        self.apply_change(inverse.clone())
+            .map_err(HistoryError::InvalidChange)?;
+        self.changes.push(inverse);
+        self.redo_history.push(change.clone());
+        Ok(change)
+    }
+
+    /// Reapplies the most recently undone change, and returns it.
+    /// If the change can't be reapplied, it is removed from the history.
+    pub fn redo(&mut self) -> Result<Change, HistoryError> {
+        let change = self.redo_history.pop().ok_or(HistoryError::NothingToRedo)?;
+        self.apply_change(change.clone())
+            .map_err(HistoryError::InvalidChange)?;
+        self.changes.push(change.clone());
+        self.undo_history.push(change.clone());
+        Ok(change)
+    }
+
+    /// Returns the changes which can be undone, with the most recent one last.
+    pub fn undo_history(&self) -> &[Change] {
+        &self.undo_history
+    }
+
+    /// Returns the changes which have been undone and can be redone,
+    /// with the most recently undone one last.
+    pub fn redo_history(&self) -> &[Change] {
+        &self.redo_history
+    }
+
+    /// Replaces the undo and redo history, e.g. with a history saved by a previous `State`.
+    /// The history must describe changes leading up to this state's current data,
+    /// otherwise undoing and redoing will fail.
+    pub fn set_history(&mut self, undo_history: Vec<Change>, redo_history: Vec<Change>) {
+        self.undo_history = undo_history;
+        self.redo_history = redo_history;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use crate::test_util::{assert_replays, snapshot};
+    use crate::{
+        Annotation, Currency, HistoryError, MemoryStorage, Money, Payment, ProjectData, State,
+    };
+
+    /// Undoes every change, checking that each goes back to the snapshot taken before it,
+    /// then redoes them all in the same way, and checks that the changes replay to the same state.
+    /// `snapshots` starts with the state before any changes, and ends with the current state.
+    fn assert_undo_and_redo(state: &mut State, snapshots: &[Vec<ProjectData>]) {
+        for expected in snapshots.iter().rev().skip(1) {
+            state.undo().unwrap();
+            assert_eq!(&snapshot(state), expected);
+        }
+        assert_eq!(state.undo(), Err(HistoryError::NothingToUndo));
+        for expected in &snapshots[1..] {
+            state.redo().unwrap();
+            assert_eq!(&snapshot(state), expected);
+        }
+        assert_eq!(state.redo(), Err(HistoryError::NothingToRedo));
+        assert_replays(state);
+    }
+
+    #[test]
+    fn undo_and_redo_project_changes() {
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let mut snapshots = vec![snapshot(&state)];
+        let project = state.new_project("project".to_string(), "".to_string(), None);
+        snapshots.push(snapshot(&state));
+        let other = state.new_project("other".to_string(), "".to_string(), None);
+        snapshots.push(snapshot(&state));
+        state
+            .set_project_default_payment(
+                project,
+                Some(Payment::Hourly(Money::new(2000, Currency::Gbp))),
+            )
+            .unwrap();
+        snapshots.push(snapshot(&state));
+        state
+            .update_project(
+                project,
+                Some("renamed".to_string()),
+                Some("about".to_string()),
+            )
+            .unwrap();
+        snapshots.push(snapshot(&state));
+        state.archive_project(other).unwrap();
+        snapshots.push(snapshot(&state));
+        state.reopen_project(other).unwrap();
+        snapshots.push(snapshot(&state));
+        state.archive_project(other).unwrap();
+        snapshots.push(snapshot(&state));
+        state.delete_project(project);
+        snapshots.push(snapshot(&state));
+        state.delete_project(other);
+        snapshots.push(snapshot(&state));
+
+        assert_undo_and_redo(&mut state, &snapshots);
+    }
+
+    #[test]
+    fn undo_and_redo_work_changes() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let payment = Payment::Hourly(Money::new(2000, Currency::Gbp));
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let mut snapshots = vec![snapshot(&state)];
+        let project = state.new_project("project".to_string(), "".to_string(), Some(payment));
+        snapshots.push(snapshot(&state));
+        state
+            .start_work(project, None, now - hours(2), Annotation::default())
+            .unwrap();
+        snapshots.push(snapshot(&state));
+        state.end_work(project, now - hours(1), None).unwrap();
+        snapshots.push(snapshot(&state));
+        let work_slice = state
+            .project_from_id(project)
+            .unwrap()
+            .complete_work_slices()
+            .next()
+            .unwrap()
+            .id();
+        state
+            .edit_work_slice(
+                work_slice,
+                now - hours(3),
+                now - hours(1),
+                Payment::Fixed(Money::new(5000, Currency::Gbp)),
+            )
+            .unwrap();
+        snapshots.push(snapshot(&state));
+        state
+            .start_work(
+                project,
+                None,
+                now - TimeDelta::minutes(30),
+                Annotation::default(),
+            )
+            .unwrap();
+        snapshots.push(snapshot(&state));
+        state
+            .add_complete_work_slice(
+                project,
+                now - hours(5),
+                now - hours(4),
+                None,
+                Annotation::default(),
+            )
+            .unwrap();
+        snapshots.push(snapshot(&state));
+        state.delete_work_slice(work_slice);
+        snapshots.push(snapshot(&state));
+
+        assert_undo_and_redo(&mut state, &snapshots);
+    }
+
+    #[test]
+    fn undo_and_redo_breaks() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let payment = Payment::Hourly(Money::new(1000, Currency::Gbp));
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let mut snapshots = vec![snapshot(&state)];
+        let project = state.new_project("project".to_string(), "".to_string(), Some(payment));
+        snapshots.push(snapshot(&state));
+        state
+            .start_work(project, None, now - hours(6), Annotation::default())
+            .unwrap();
+        snapshots.push(snapshot(&state));
+        state.pause_work(project, now - hours(5)).unwrap();
+        snapshots.push(snapshot(&state));
+        state.resume_work(project, now - hours(3)).unwrap();
+        snapshots.push(snapshot(&state));
+        state.pause_work(project, now - hours(2)).unwrap();
+        snapshots.push(snapshot(&state));
+        state.end_work(project, now - hours(1), None).unwrap();
+        snapshots.push(snapshot(&state));
+
+        assert_undo_and_redo(&mut state, &snapshots);
+    }
+
+    #[test]
+    fn new_changes_clear_redo_history() {
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        state.new_project("first".to_string(), "".to_string(), None);
+        state.undo().unwrap();
+        assert_eq!(state.redo_history().len(), 1);
+        state.new_project("second".to_string(), "".to_string(), None);
+        assert_eq!(state.redo(), Err(HistoryError::NothingToRedo));
+    }
+}
@@ -1,42 +1,99 @@
+use std::collections::BTreeSet;
+
 use chrono::{DateTime, Utc};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
 use super::{
-    CompleteWorkSlice, DataToCompleteWorkSliceError, DataToProjectError, IncompleteWorkSlice,
-    Payment, Project, ProjectId, WorkSliceId,
+    Break, Client, ClientId, CompleteWorkSlice, DataToCompleteWorkSliceError, DataToProjectError,
+    IncompleteWorkSlice, Invoice, Money, Payment, Project, ProjectId, ReceivedPayment, TrashEntry,
+    WorkSlice, WorkSliceId,
 };
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct IncompleteWorkSliceData {
     pub start: DateTime<Utc>,
     pub payment: Payment,
+    /// Work slices saved before breaks were supported don't have any.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub breaks: Vec<Break>,
+    /// When the current break started, if the work slice is paused.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub paused_at: Option<DateTime<Utc>>,
+    /// What the work is for. Work slices saved before notes and tags were supported don't have any.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub note: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tags: BTreeSet<String>,
     pub id: u64,
 }
+impl From<&IncompleteWorkSlice> for IncompleteWorkSliceData {
+    fn from(value: &IncompleteWorkSlice) -> Self {
+        Self {
+            start: value.start(),
+            payment: value.payment(),
+            breaks: value.breaks().to_vec(),
+            paused_at: value.paused_at(),
+            note: value.note().map(str::to_string),
+            tags: value.tags().clone(),
+            id: unsafe { value.id().inner() },
+        }
+    }
+}
 impl IncompleteWorkSliceData {
     pub(super) fn into_work_slice(self) -> Option<IncompleteWorkSlice> {
-        IncompleteWorkSlice::new(self.start, self.payment, unsafe {
+        let mut work_slice = IncompleteWorkSlice::new(self.start, self.payment, unsafe {
             WorkSliceId::new(self.id)
-        })
+        })?
+        .with_breaks(self.breaks, self.paused_at)?;
+        work_slice.annotate(self.note, self.tags);
+        Some(work_slice)
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct CompleteWorkSliceData {
     pub start: DateTime<Utc>,
     pub end: DateTime<Utc>,
     pub payment: Payment,
+    /// Work slices saved before breaks were supported don't have any.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub breaks: Vec<Break>,
+    /// What the work was for. Work slices saved before notes and tags were supported don't have any.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub note: Option<String>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub tags: BTreeSet<String>,
     pub id: u64,
 }
+impl From<&CompleteWorkSlice> for CompleteWorkSliceData {
+    fn from(value: &CompleteWorkSlice) -> Self {
+        Self {
+            start: value.start(),
+            end: value.completion(),
+            payment: value.payment(),
+            breaks: value.breaks().to_vec(),
+            note: value.note().map(str::to_string),
+            tags: value.tags().clone(),
+            id: unsafe { value.id().inner() },
+        }
+    }
+}
 impl CompleteWorkSliceData {
     pub(super) fn into_work_slice(self) -> Result<CompleteWorkSlice, DataToCompleteWorkSliceError> {
         match IncompleteWorkSlice::new(self.start, self.payment, unsafe {
             WorkSliceId::new(self.id)
         }) {
             Some(incomplete) => match CompleteWorkSlice::new(incomplete, self.end) {
-                Ok(complete) => Ok(complete),
+                Ok(complete) => {
+                    let mut complete = complete
+                        .with_breaks(self.breaks)
+                        .ok_or(DataToCompleteWorkSliceError::InvalidBreaks)?;
+                    complete.annotate(self.note, self.tags);
+                    Ok(complete)
+                }
                 Err(_) => Err(DataToCompleteWorkSliceError::EndTimeBeforeStart),
             },
             None => Err(DataToCompleteWorkSliceError::StartTimeAfterNow),
@@ -44,15 +101,69 @@ impl CompleteWorkSliceData {
     }
 }
 
-#[derive(Debug, Clone)]
+/// The data for a work slice which may or may not be complete.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum WorkSliceData {
+    Complete(CompleteWorkSliceData),
+    Incomplete(IncompleteWorkSliceData),
+}
+impl WorkSliceData {
+    /// Returns the ID of this work slice.
+    pub fn id(&self) -> u64 {
+        match self {
+            Self::Complete(x) => x.id,
+            Self::Incomplete(x) => x.id,
+        }
+    }
+}
+impl From<WorkSlice<'_>> for WorkSliceData {
+    fn from(value: WorkSlice<'_>) -> Self {
+        match value {
+            WorkSlice::Complete(x) => Self::Complete(x.into()),
+            WorkSlice::Incomplete(x) => Self::Incomplete(x.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct ProjectData {
     pub name: String,
     pub description: String,
     pub work_slices: Vec<CompleteWorkSliceData>,
     pub current_slice: Option<IncompleteWorkSliceData>,
+    /// The payment used for new work when no payment is given.
+    /// Projects saved before default payments were supported don't have one.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub default_payment: Option<Payment>,
+    /// Whether the project has been archived.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub archived: bool,
+    /// The ID of the client which the project is for, if it has one.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub client: Option<u64>,
+    /// The payments received for the project's work.
+    /// Projects saved before received payments were supported don't have any.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub received_payments: Vec<ReceivedPaymentData>,
     pub id: u64,
 }
+impl From<&Project> for ProjectData {
+    fn from(value: &Project) -> Self {
+        Self {
+            name: value.name().to_string(),
+            description: value.description().to_string(),
+            work_slices: value.complete_work_slices().map(Into::into).collect(),
+            current_slice: value.current_work_slice().map(Into::into),
+            default_payment: value.default_payment(),
+            archived: value.is_archived(),
+            client: value.client().map(|x| unsafe { x.inner() }),
+            received_payments: value.received_payments().map(Into::into).collect(),
+            id: unsafe { value.id().inner() },
+        }
+    }
+}
 impl ProjectData {
     pub(super) fn into_project(self) -> Result<Project, DataToProjectError> {
         let complete: Vec<_> = self
@@ -75,12 +186,176 @@ impl ProjectData {
             },
         };
 
-        Ok(Project::new_with_slices(
+        let mut project = Project::new_with_slices(
             self.name,
             self.description,
             unsafe { ProjectId::new(self.id) },
             complete,
             current,
-        ))
+        );
+        project.set_default_payment(self.default_payment);
+        project.set_archived(self.archived);
+        project.set_client(self.client.map(|x| unsafe { ClientId::new(x) }));
+        for payment in self.received_payments {
+            project.insert_received_payment(payment.into());
+        }
+        Ok(project)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ReceivedPaymentData {
+    pub amount: Money,
+    pub date: DateTime<Utc>,
+    pub reference: String,
+    pub id: u64,
+}
+impl From<&ReceivedPayment> for ReceivedPaymentData {
+    fn from(value: &ReceivedPayment) -> Self {
+        Self {
+            amount: value.amount(),
+            date: value.date(),
+            reference: value.reference().to_string(),
+            id: unsafe { value.id().inner() },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ClientData {
+    pub name: String,
+    pub contact: String,
+    pub billing_address: String,
+    /// The payment used for work on the client's projects
+    /// when no payment is given and the project has no default payment.
+    pub default_payment: Option<Payment>,
+    pub id: u64,
+}
+impl From<&Client> for ClientData {
+    fn from(value: &Client) -> Self {
+        Self {
+            name: value.name().to_string(),
+            contact: value.contact().to_string(),
+            billing_address: value.billing_address().to_string(),
+            default_payment: value.default_payment(),
+            id: unsafe { value.id().inner() },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct InvoiceData {
+    pub number: u64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub client: Option<u64>,
+    pub issued: DateTime<Utc>,
+    pub due: DateTime<Utc>,
+    pub work_slices: Vec<u64>,
+}
+impl From<&Invoice> for InvoiceData {
+    fn from(value: &Invoice) -> Self {
+        Self {
+            number: value.number(),
+            client: value.client().map(|x| unsafe { x.inner() }),
+            issued: value.issued(),
+            due: value.due(),
+            work_slices: value
+                .work_slices()
+                .iter()
+                .map(|x| unsafe { x.inner() })
+                .collect(),
+        }
+    }
+}
+
+/// A snapshot of everything in a `State`: its projects, clients and invoices, and what is in its trash.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct StateData {
+    pub projects: Vec<ProjectData>,
+    pub clients: Vec<ClientData>,
+    pub invoices: Vec<InvoiceData>,
+    pub trash: Vec<TrashEntry>,
+    /// The highest number any invoice has had, including deleted ones,
+    /// so that their numbers aren't used again.
+    pub previous_invoice_number: u64,
+}
+
+impl StateData {
+    /// Returns whether there's nothing at all in this snapshot, including invoice numbers which have been used.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// The current format of a `StateData`.
+/// Fields which were added later default to being empty.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct StateDataFormat {
+    projects: Vec<ProjectData>,
+    #[serde(default)]
+    clients: Vec<ClientData>,
+    #[serde(default)]
+    invoices: Vec<InvoiceData>,
+    #[serde(default)]
+    trash: Vec<TrashEntry>,
+    /// Data saved before this was kept only has the numbers of the invoices which still exist.
+    #[serde(default)]
+    previous_invoice_number: u64,
+}
+#[cfg(feature = "serde")]
+impl From<StateDataFormat> for StateData {
+    fn from(value: StateDataFormat) -> Self {
+        Self {
+            projects: value.projects,
+            clients: value.clients,
+            invoices: value.invoices,
+            trash: value.trash,
+            previous_invoice_number: value.previous_invoice_number,
+        }
+    }
+}
+
+/// Loads the current format from a map, and data saved before the trash was added
+/// (which is just a list of projects) from a list.
+/// Choosing the format by the kind of value, rather than trying each format in turn,
+/// means that errors in a corrupt file are reported as they are.
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for StateData {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct StateDataVisitor;
+        impl<'de> serde::de::Visitor<'de> for StateDataVisitor {
+            type Value = StateData;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str(
+                    "a map of projects, clients, invoices and trash, or a list of projects",
+                )
+            }
+
+            fn visit_map<A: serde::de::MapAccess<'de>>(
+                self,
+                map: A,
+            ) -> Result<StateData, A::Error> {
+                StateDataFormat::deserialize(serde::de::value::MapAccessDeserializer::new(map))
+                    .map(StateData::from)
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                seq: A,
+            ) -> Result<StateData, A::Error> {
+                let projects = Vec::deserialize(serde::de::value::SeqAccessDeserializer::new(seq))?;
+                Ok(StateData {
+                    projects,
+                    ..Default::default()
+                })
+            }
+        }
+        deserializer.deserialize_any(StateDataVisitor)
     }
 }
@@ -0,0 +1,377 @@
+use chrono::{DateTime, Utc};
+
+use crate::{
+    Change, ClientId, CompleteWorkSlice, CreateInvoiceError, InvoiceData, Money, MoneyTotals,
+    Project, State, WorkSliceId,
+};
+
+/// A bill for some complete work slices, which can be for a client.
+/// Once a work slice is on an invoice, it can't be edited or deleted,
+/// and it can't be put on another invoice, until the invoice is deleted.
+#[derive(Debug)]
+pub struct Invoice {
+    number: u64,
+    client: Option<ClientId>,
+    issued: DateTime<Utc>,
+    due: DateTime<Utc>,
+    work_slices: Vec<WorkSliceId>,
+    amounts: Vec<Money>,
+    total: MoneyTotals,
+}
+impl PartialEq for Invoice {
+    fn eq(&self, other: &Self) -> bool {
+        self.number == other.number
+    }
+}
+impl Eq for Invoice {}
+impl Invoice {
+    /// Returns the number of this invoice. Invoices are numbered in the order they were created, starting at 1.
+    pub fn number(&self) -> u64 {
+        self.number
+    }
+    pub fn client(&self) -> Option<ClientId> {
+        self.client
+    }
+    pub fn issued(&self) -> DateTime<Utc> {
+        self.issued
+    }
+    /// Returns when this invoice has to be paid by.
+    pub fn due(&self) -> DateTime<Utc> {
+        self.due
+    }
+    /// Returns the IDs of the work slices on this invoice, in order.
+    pub fn work_slices(&self) -> &[WorkSliceId] {
+        &self.work_slices
+    }
+    /// Returns the amount billed for each work slice on this invoice, in the same order as `work_slices`.
+    /// Each is the work slice's payment rounded with `MoneyExact::billed`.
+    pub fn amounts(&self) -> &[Money] {
+        &self.amounts
+    }
+    /// Returns the amount of money billed on this invoice in each currency, which is the sum of its `amounts`.
+    pub fn total(&self) -> &MoneyTotals {
+        &self.total
+    }
+}
+
+impl State {
+    /// Creates an invoice for complete work slices, and returns its number,
+    /// which is one more than the highest number any invoice has had,
+    /// so the numbers of deleted invoices aren't used again.
+    /// If a client is given, all of the work slices must be for that client's projects.
+    /// Fails if any of the work slices don't exist, are ongoing or are already on an invoice,
+    /// or if there aren't any, or if the invoice is due before it's issued.
+    pub fn create_invoice(
+        &mut self,
+        client: Option<ClientId>,
+        work_slices: Vec<WorkSliceId>,
+        issued: DateTime<Utc>,
+        due: DateTime<Utc>,
+    ) -> Result<u64, CreateInvoiceError> {
+        let number = self.previous_invoice_number + 1;
+        let data = InvoiceData {
+            number,
+            client: client.map(|x| unsafe { x.inner() }),
+            issued,
+            due,
+            work_slices: work_slices
+                .into_iter()
+                .map(|x| unsafe { x.inner() })
+                .collect(),
+        };
+        let invoice = self.invoice_from_data(data.clone())?;
+        self.invoices.push(invoice);
+        self.previous_invoice_number = number;
+        self.record_change(Change::InvoiceCreated { invoice: data });
+        Ok(number)
+    }
+
+    /// Returns the highest number any invoice has had, including ones which were deleted.
+    pub fn previous_invoice_number(&self) -> u64 {
+        self.previous_invoice_number
+    }
+
+    /// Deletes an invoice, so its work slices can be changed and billed again.
+    /// Returns false if there isn't an invoice with that number.
+    pub fn delete_invoice(&mut self, number: u64) -> bool {
+        match self.invoices.iter().position(|x| x.number() == number) {
+            Some(i) => {
+                let invoice = InvoiceData::from(&self.invoices.remove(i));
+                self.record_change(Change::InvoiceDeleted { invoice });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns all invoices, in the order they were created.
+    pub fn invoices(&self) -> impl Iterator<Item = &Invoice> + '_ {
+        self.invoices.iter()
+    }
+
+    /// Returns the invoice with the given number, if it exists.
+    pub fn invoice_from_number(&self, number: u64) -> Option<&Invoice> {
+        self.invoices.iter().find(|x| x.number() == number)
+    }
+
+    /// Returns the invoice which a work slice is on, if it's on one.
+    pub fn invoice_of_work_slice(&self, id: WorkSliceId) -> Option<&Invoice> {
+        self.invoices.iter().find(|x| x.work_slices.contains(&id))
+    }
+
+    /// Returns true if a work slice is on an invoice, so it can't be changed.
+    pub fn is_invoiced(&self, id: WorkSliceId) -> bool {
+        self.invoice_of_work_slice(id).is_some()
+    }
+
+    /// Returns the complete work slices in all projects which aren't on an invoice yet.
+    pub fn unbilled_work_slices(&self) -> impl Iterator<Item = &CompleteWorkSlice> + '_ {
+        self.all_projects()
+            .flat_map(|x| x.complete_work_slices())
+            .filter(|x| !self.is_invoiced(x.id()))
+    }
+
+    /// Checks that an invoice can be added to this state, and works out its total.
+    pub(crate) fn invoice_from_data(
+        &self,
+        data: InvoiceData,
+    ) -> Result<Invoice, CreateInvoiceError> {
+        let client = data.client.map(|x| unsafe { ClientId::new(x) });
+        if client.is_some_and(|x| self.client_from_id(x).is_none()) {
+            return Err(CreateInvoiceError::InvalidClientId);
+        }
+        if data.work_slices.is_empty() {
+            return Err(CreateInvoiceError::NoWorkSlices);
+        }
+        if data.due < data.issued {
+            return Err(CreateInvoiceError::DueBeforeIssued);
+        }
+        let mut work_slices: Vec<_> = data
+            .work_slices
+            .into_iter()
+            .map(|x| unsafe { WorkSliceId::new(x) })
+            .collect();
+        work_slices.sort();
+        work_slices.dedup();
+        let mut amounts = Vec::with_capacity(work_slices.len());
+        for &id in &work_slices {
+            let work_slice = self
+                .work_slice_from_id(id)
+                .ok_or(CreateInvoiceError::WorkSliceNotFound)?
+                .complete()
+                .ok_or(CreateInvoiceError::WorkSliceIncomplete)?;
+            if self.is_invoiced(id) {
+                return Err(CreateInvoiceError::AlreadyInvoiced);
+            }
+            if let Some(client) = client {
+                let project = self
+                    .project_id_from_work_slice(id)
+                    .and_then(|x| self.project_from_id(x));
+                if project.and_then(|x| x.client()) != Some(client) {
+                    return Err(CreateInvoiceError::WrongClient);
+                }
+            }
+            amounts.push(work_slice.calculate_payment().billed());
+        }
+        Ok(Invoice {
+            number: data.number,
+            client,
+            issued: data.issued,
+            due: data.due,
+            work_slices,
+            total: amounts.iter().copied().sum(),
+            amounts,
+        })
+    }
+
+    /// Returns true if any work slice in a project is on an invoice.
+    pub(crate) fn project_is_invoiced(&self, project: &Project) -> bool {
+        project
+            .complete_work_slices()
+            .any(|x| self.is_invoiced(x.id()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use chrono::{TimeDelta, Utc};
+
+    use crate::{
+        AnnotateWorkSliceError, Annotation, CreateInvoiceError, Currency, EditWorkSliceError,
+        MemoryStorage, Money, Payment, State,
+    };
+
+    #[test]
+    fn invoiced_work_is_locked() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let payment = Payment::Hourly(Money::new(1000, Currency::Gbp));
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let client = state.new_client("client".to_string(), "".to_string(), "".to_string(), None);
+        let project = state.new_project("project".to_string(), "".to_string(), Some(payment));
+        let other = state.new_project("other".to_string(), "".to_string(), Some(payment));
+        state.set_project_client(project, Some(client)).unwrap();
+        let first = state
+            .add_complete_work_slice(
+                project,
+                now - hours(5),
+                now - hours(4),
+                None,
+                Annotation::default(),
+            )
+            .unwrap();
+        let second = state
+            .add_complete_work_slice(
+                project,
+                now - hours(3),
+                now - hours(1),
+                None,
+                Annotation::default(),
+            )
+            .unwrap();
+        let elsewhere = state
+            .add_complete_work_slice(
+                other,
+                now - hours(7),
+                now - hours(6),
+                None,
+                Annotation::default(),
+            )
+            .unwrap();
+        state
+            .start_work(project, None, now, Annotation::default())
+            .unwrap();
+        let ongoing = state
+            .project_from_id(project)
+            .unwrap()
+            .current_work_slice()
+            .unwrap()
+            .id();
+
+        let due = now + TimeDelta::days(30);
+        assert_eq!(
+            state.create_invoice(Some(client), vec![first, elsewhere], now, due),
+            Err(CreateInvoiceError::WrongClient)
+        );
+        assert_eq!(
+            state.create_invoice(None, vec![ongoing], now, due),
+            Err(CreateInvoiceError::WorkSliceIncomplete)
+        );
+        assert_eq!(
+            state.create_invoice(None, vec![first], due, now),
+            Err(CreateInvoiceError::DueBeforeIssued)
+        );
+        assert_eq!(
+            state.create_invoice(Some(client), vec![first, second], now, due),
+            Ok(1)
+        );
+        assert_eq!(
+            state.create_invoice(None, vec![second, elsewhere], now, due),
+            Err(CreateInvoiceError::AlreadyInvoiced)
+        );
+        assert_eq!(state.create_invoice(None, vec![elsewhere], now, due), Ok(2));
+        assert_eq!(
+            state
+                .invoice_from_number(1)
+                .unwrap()
+                .total()
+                .get(Currency::Gbp),
+            Money::new(3000, Currency::Gbp).into()
+        );
+        assert_eq!(state.invoice_of_work_slice(second).unwrap().number(), 1);
+        assert_eq!(state.unbilled_work_slices().count(), 0);
+
+        assert_eq!(
+            state.edit_work_slice(first, now - hours(6), now - hours(4), payment),
+            Err(EditWorkSliceError::Invoiced)
+        );
+        assert_eq!(
+            state.annotate_work_slice(first, Some("note".to_string()), BTreeSet::new()),
+            Err(AnnotateWorkSliceError::Invoiced)
+        );
+        assert!(!state.delete_work_slice(first));
+        assert!(!state.delete_project(project));
+
+        let changes = state.handle_changes();
+        let mut replayed = State::from_changes(changes, MemoryStorage::default()).unwrap();
+        assert_eq!(replayed.invoices().count(), 2);
+        assert!(replayed.delete_invoice(2));
+        assert!(replayed.delete_work_slice(elsewhere));
+        assert_eq!(
+            replayed.create_invoice(None, vec![ongoing], now, due),
+            Err(CreateInvoiceError::WorkSliceIncomplete)
+        );
+        replayed.end_work(project, Utc::now(), None).unwrap();
+        // the number of the deleted invoice isn't used again
+        assert_eq!(
+            replayed.create_invoice(None, vec![ongoing], now, due),
+            Ok(3)
+        );
+
+        state.undo().unwrap();
+        assert_eq!(state.invoices().count(), 1);
+        assert!(state.delete_work_slice(elsewhere));
+    }
+
+    #[test]
+    fn invoice_numbers_are_not_reused_after_loading() {
+        let now = Utc::now();
+        let payment = Payment::Hourly(Money::new(1000, Currency::Gbp));
+        let storage = MemoryStorage::default();
+        let mut state = State::new(storage.clone()).unwrap();
+        let project = state.new_project("project".to_string(), "".to_string(), Some(payment));
+        let work_slice = state
+            .add_complete_work_slice(
+                project,
+                now - TimeDelta::hours(2),
+                now - TimeDelta::hours(1),
+                None,
+                Annotation::default(),
+            )
+            .unwrap();
+        let number = state
+            .create_invoice(None, vec![work_slice], now, now)
+            .unwrap();
+        assert!(state.delete_invoice(number));
+        state.save_snapshot().unwrap();
+        drop(state);
+
+        assert_eq!(storage.data().data.previous_invoice_number, number);
+        let mut state = State::new(storage).unwrap();
+        assert_eq!(
+            state.create_invoice(None, vec![work_slice], now, now),
+            Ok(number + 1)
+        );
+    }
+
+    #[test]
+    fn invoice_total_adds_up_rounded_lines() {
+        let now = Utc::now();
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        // half an hour at 1p an hour earns half a penny
+        let payment = Payment::Hourly(Money::new(1, Currency::Gbp));
+        let project = state.new_project("project".to_string(), "".to_string(), Some(payment));
+        let work_slices = (1..=3)
+            .map(|i| {
+                let start = now - TimeDelta::hours(i);
+                state
+                    .add_complete_work_slice(
+                        project,
+                        start,
+                        start + TimeDelta::minutes(30),
+                        None,
+                        Annotation::default(),
+                    )
+                    .unwrap()
+            })
+            .collect();
+        let number = state
+            .create_invoice(None, work_slices, now, now + TimeDelta::days(30))
+            .unwrap();
+        let invoice = state.invoice_from_number(number).unwrap();
+        assert_eq!(invoice.amounts(), [Money::new(1, Currency::Gbp); 3]);
+        assert_eq!(invoice.total().to_string(), "£0.03");
+    }
+}
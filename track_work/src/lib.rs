@@ -1,39 +1,96 @@
 #![allow(dead_code)]
 
+mod balance;
 mod changes;
+mod client;
 mod errors;
+mod history;
 mod initial_data;
+mod invoice;
+mod overlap;
 mod payment;
 mod project;
+mod range;
+mod render;
+mod replay;
+mod report;
+#[cfg(feature = "sqlite")]
+mod sqlite_storage;
+mod storage;
+mod tags;
+#[cfg(test)]
+mod test_util;
+mod trash;
 mod work_slice;
 
-use std::collections::HashSet;
+use std::{collections::HashSet, error::Error};
 
+pub use balance::*;
 pub use changes::*;
 use chrono::{DateTime, Utc};
+pub use client::*;
 pub use errors::*;
 pub use initial_data::*;
+pub use invoice::*;
+pub use overlap::*;
 pub use payment::*;
 pub use project::*;
+pub use range::*;
+pub use render::*;
+pub use report::*;
+#[cfg(feature = "sqlite")]
+pub use sqlite_storage::*;
+pub use storage::*;
+pub use tags::*;
+pub use trash::*;
 pub use work_slice::*;
 
-type CommitOnDropBox = Box<dyn Fn(Vec<Change>, Vec<&Project>)>;
+/// Called with the error when saving fails while a `State` is being dropped.
+type SaveErrorHandler = Box<dyn FnMut(Box<dyn Error>)>;
 
 /// Used to create, modify and delete projects.
 pub struct State {
     previous_project_id: u64,
     previous_work_slice_id: u64,
+    previous_client_id: u64,
+    previous_received_payment_id: u64,
+    previous_invoice_number: u64,
     projects: Vec<Project>,
+    clients: Vec<Client>,
+    invoices: Vec<Invoice>,
+    trash: Vec<TrashEntry>,
     changes: Vec<Change>,
-    commit_on_drop: CommitOnDropBox,
+    undo_history: Vec<Change>,
+    redo_history: Vec<Change>,
+    overlap_policy: OverlapPolicy,
+    overlap_warnings: Vec<Overlap>,
+    storage: Box<dyn Storage>,
+    drop_save_error_handler: Option<SaveErrorHandler>,
 }
 impl State {
-    /// Returns an empty State, which has no projects.
-    pub fn new(
-        initial_data: Vec<ProjectData>,
-        commit_on_drop: impl Fn(Vec<Change>, Vec<&Project>) + 'static,
+    /// Loads a State from the given storage, which its changes will be saved to.
+    /// To start with no projects, use an empty storage, like `MemoryStorage::default()`.
+    pub fn new(mut storage: impl Storage + 'static) -> Result<Self, StateLoadError> {
+        let data = storage.load().map_err(StateLoadError::Storage)?;
+        Self::from_snapshot_and_changes(data.data, data.changes, storage)
+            .map_err(StateLoadError::Replay)
+    }
+
+    /// Constructs a State from a snapshot of its projects, clients, invoices and trash,
+    /// without loading anything from its storage.
+    fn from_data(
+        initial_data: StateData,
+        storage: Box<dyn Storage>,
     ) -> Result<Self, StateInitError> {
-        let projects: Vec<_> = initial_data
+        let StateData {
+            projects,
+            clients,
+            mut invoices,
+            trash,
+            previous_invoice_number,
+        } = initial_data;
+        invoices.sort_by_key(|x| x.number);
+        let projects: Vec<_> = projects
             .into_iter()
             .map(ProjectData::into_project)
             .collect();
@@ -41,13 +98,22 @@ impl State {
         if let Some(e) = projects.iter().find_map(|x| x.as_ref().err()) {
             return Err(StateInitError::ProjectLoadError(*e));
         }
-        let projects: Vec<_> = projects.into_iter().map(Result::unwrap).collect();
+        // everything is kept in order of its ID (see `insert_project_data`),
+        // so that it's listed in the same order whichever storage it was loaded from
+        let mut projects: Vec<_> = projects.into_iter().map(Result::unwrap).collect();
+        projects.sort_by_key(Project::id);
 
         let previous_project_id = projects
             .iter()
             .map(Project::id)
             .max()
             .map(|x| unsafe { x.inner() })
+            .into_iter()
+            .chain(trash.iter().filter_map(|x| match &x.item {
+                TrashedItem::Project(project) => Some(project.id),
+                TrashedItem::WorkSlice { .. } => None,
+            }))
+            .max()
             .unwrap_or(0);
 
         let previous_work_slice_id = projects
@@ -60,11 +126,55 @@ impl State {
                 )
             })
             .flat_map(|x| unsafe { x.map(|x| x.inner()) })
+            .chain(trash.iter().flat_map(|x| {
+                match &x.item {
+                    TrashedItem::Project(project) => project
+                        .work_slices
+                        .iter()
+                        .map(|x| x.id)
+                        .chain(project.current_slice.iter().map(|x| x.id))
+                        .collect(),
+                    TrashedItem::WorkSlice { work_slice, .. } => vec![work_slice.id()],
+                }
+            }))
+            .max()
+            .unwrap_or(0);
+
+        let previous_received_payment_id = projects
+            .iter()
+            .flat_map(|x| x.received_payments().map(|x| unsafe { x.id().inner() }))
+            .chain(trash.iter().flat_map(|x| match &x.item {
+                TrashedItem::Project(project) => {
+                    project.received_payments.iter().map(|x| x.id).collect()
+                }
+                TrashedItem::WorkSlice { .. } => Vec::new(),
+            }))
+            .max()
+            .unwrap_or(0);
+
+        let mut clients: Vec<Client> = clients.into_iter().map(Client::from).collect();
+        clients.sort_by_key(Client::id);
+        let previous_client_id = clients
+            .iter()
+            .map(|x| unsafe { x.id().inner() })
             .max()
             .unwrap_or(0);
 
         let mut hashmap = HashSet::new();
         unsafe {
+            for id in clients.iter().map(|x| x.id().inner()) {
+                if !hashmap.insert(id) {
+                    return Err(StateInitError::DuplicateClientId);
+                }
+            }
+            if projects
+                .iter()
+                .filter_map(Project::client)
+                .any(|x| !hashmap.contains(&x.inner()))
+            {
+                return Err(StateInitError::ClientNotFound);
+            }
+            hashmap.clear();
             for id in projects.iter().map(Project::id).map(|x| x.inner()) {
                 if !hashmap.insert(id) {
                     return Err(StateInitError::DuplicateProjectId);
@@ -90,33 +200,178 @@ impl State {
                     return Err(StateInitError::DuplicateWorkSliceId);
                 }
             }
+            hashmap.clear();
+            for id in projects
+                .iter()
+                .flat_map(|x| x.received_payments())
+                .map(|x| x.id().inner())
+            {
+                if !hashmap.insert(id) {
+                    return Err(StateInitError::DuplicateReceivedPaymentId);
+                }
+            }
         }
 
-        Ok(Self {
+        let mut state = Self {
             previous_project_id,
             previous_work_slice_id,
+            previous_client_id,
+            previous_received_payment_id,
+            previous_invoice_number: invoices
+                .iter()
+                .map(|x| x.number)
+                .fold(previous_invoice_number, u64::max),
             projects,
+            clients,
+            invoices: Vec::new(),
+            trash,
             changes: Vec::new(),
-            commit_on_drop: Box::new(commit_on_drop),
-        })
+            undo_history: Vec::new(),
+            redo_history: Vec::new(),
+            overlap_policy: OverlapPolicy::default(),
+            overlap_warnings: Vec::new(),
+            storage,
+            drop_save_error_handler: None,
+        };
+        for invoice in invoices {
+            if state.invoice_from_number(invoice.number).is_some() {
+                return Err(StateInitError::DuplicateInvoiceNumber);
+            }
+            let invoice = state
+                .invoice_from_data(invoice)
+                .map_err(StateInitError::InvalidInvoice)?;
+            state.invoices.push(invoice);
+        }
+        Ok(state)
     }
 
     /// Creates a new project, and returns its ID.
-    pub fn new_project(&mut self, name: String, description: String) -> ProjectId {
-        self.changes.push(Change::ProjectCreated {
+    /// The default payment is used when work is started without a payment.
+    pub fn new_project(
+        &mut self,
+        name: String,
+        description: String,
+        default_payment: Option<Payment>,
+    ) -> ProjectId {
+        self.record_change(Change::ProjectCreated {
             name: name.clone(),
             description: description.clone(),
+            default_payment,
             id: self.previous_project_id + 1,
         });
         let id = unsafe { ProjectId::new(self.previous_project_id + 1) };
 
-        self.projects.push(Project::new(name, description, id));
+        let mut project = Project::new(name, description, id);
+        project.set_default_payment(default_payment);
+        self.projects.push(project);
 
         self.previous_project_id += 1;
 
         id
     }
 
+    /// Changes the name and/or description of a project,
+    /// keeping the current value of anything which is `None`.
+    /// Nothing is recorded if neither of them changes.
+    /// Fails if the project doesn't exist.
+    pub fn update_project(
+        &mut self,
+        id: ProjectId,
+        name: Option<String>,
+        description: Option<String>,
+    ) -> Result<(), InvalidProjectId> {
+        let project = self.project_from_id_mut(id).ok_or(InvalidProjectId)?;
+        let old_name = project.name().to_string();
+        let old_description = project.description().clone();
+        let name = name.unwrap_or_else(|| old_name.clone());
+        let description = description.unwrap_or_else(|| old_description.clone());
+        if name == old_name && description == old_description {
+            return Ok(());
+        }
+        project.set_name(name.clone());
+        project.set_description(description.clone());
+        self.record_change(Change::ProjectUpdated {
+            id: unsafe { id.inner() },
+            old_name,
+            old_description,
+            name,
+            description,
+        });
+        Ok(())
+    }
+
+    /// Changes the name of a project, but fails if the project doesn't exist.
+    pub fn rename_project(&mut self, id: ProjectId, name: String) -> Result<(), InvalidProjectId> {
+        self.update_project(id, Some(name), None)
+    }
+
+    /// Changes the description of a project, but fails if the project doesn't exist.
+    pub fn set_project_description(
+        &mut self,
+        id: ProjectId,
+        description: String,
+    ) -> Result<(), InvalidProjectId> {
+        self.update_project(id, None, Some(description))
+    }
+
+    /// Changes the payment used when work is started for a project without a payment,
+    /// or removes it if `default_payment` is `None`.
+    /// Nothing is recorded if it doesn't change.
+    /// Fails if the project doesn't exist.
+    pub fn set_project_default_payment(
+        &mut self,
+        id: ProjectId,
+        default_payment: Option<Payment>,
+    ) -> Result<(), InvalidProjectId> {
+        let project = self.project_from_id_mut(id).ok_or(InvalidProjectId)?;
+        let old_payment = project.default_payment();
+        if old_payment == default_payment {
+            return Ok(());
+        }
+        project.set_default_payment(default_payment);
+        self.record_change(Change::ProjectDefaultPaymentChanged {
+            id: unsafe { id.inner() },
+            old_payment,
+            payment: default_payment,
+        });
+        Ok(())
+    }
+
+    /// Archives a project, so no new work can be started for it until it's reopened.
+    /// Fails if the project doesn't exist, is already archived, or has ongoing work.
+    pub fn archive_project(&mut self, id: ProjectId) -> Result<(), ArchiveProjectError> {
+        let project = self
+            .project_from_id_mut(id)
+            .ok_or(ArchiveProjectError::InvalidProjectId)?;
+        if project.is_archived() {
+            return Err(ArchiveProjectError::AlreadyArchived);
+        }
+        if project.current_work_slice().is_some() {
+            return Err(ArchiveProjectError::WorkOngoing);
+        }
+        project.set_archived(true);
+        self.record_change(Change::ProjectArchived {
+            id: unsafe { id.inner() },
+        });
+        Ok(())
+    }
+
+    /// Makes an archived project active again.
+    /// Fails if the project doesn't exist or isn't archived.
+    pub fn reopen_project(&mut self, id: ProjectId) -> Result<(), ReopenProjectError> {
+        let project = self
+            .project_from_id_mut(id)
+            .ok_or(ReopenProjectError::InvalidProjectId)?;
+        if !project.is_archived() {
+            return Err(ReopenProjectError::NotArchived);
+        }
+        project.set_archived(false);
+        self.record_change(Change::ProjectReopened {
+            id: unsafe { id.inner() },
+        });
+        Ok(())
+    }
+
     fn new_project_id(&mut self) -> ProjectId {
         self.previous_project_id += 1;
         unsafe { ProjectId::new(self.previous_project_id) }
@@ -148,6 +403,11 @@ impl State {
         self.projects.iter()
     }
 
+    /// Returns the projects which haven't been archived.
+    pub fn active_projects(&self) -> impl Iterator<Item = &Project> + '_ {
+        self.projects.iter().filter(|x| !x.is_archived())
+    }
+
     fn all_projects_mut(&mut self) -> impl Iterator<Item = &mut Project> + '_ {
         self.projects.iter_mut()
     }
@@ -175,25 +435,51 @@ impl State {
     }
 }
 impl State {
-    /// Tries to start a new incomplete work slice for a project,
+    /// Tries to start a new incomplete work slice for a project, with the given note and tags,
+    /// using the project's default payment if `payment` is `None`,
     /// but can fail. See `WorkStartError` for information on how.
     pub fn start_work(
         &mut self,
         id: ProjectId,
-        payment: Payment,
+        payment: Option<Payment>,
         time: DateTime<Utc>,
+        annotation: Annotation,
     ) -> Result<(), WorkStartError> {
+        let project = self
+            .project_from_id(id)
+            .ok_or(WorkStartError::InvalidProjectId)?;
+        if project.is_archived() {
+            return Err(WorkStartError::ProjectArchived);
+        }
+        let payment = match payment {
+            Some(payment) => payment,
+            None => self
+                .default_payment_of(project)
+                .ok_or(WorkStartError::NoPayment)?,
+        };
+        if time > Utc::now() {
+            return Err(WorkStartError::InvalidStartTime);
+        }
+        if project.current_work_slice().is_some() {
+            return Err(WorkStartError::AlreadyStarted);
+        }
+        self.check_overlaps(time, None, None)
+            .map_err(WorkStartError::Overlap)?;
         match IncompleteWorkSlice::new(time, payment, self.new_work_slice_id()) {
-            Some(work_slice) => match self.project_from_id_mut(id) {
+            Some(mut work_slice) => match self.project_from_id_mut(id) {
                 Some(project) => {
                     let work_id = work_slice.id();
+                    let Annotation { note, tags } = annotation;
+                    work_slice.annotate(note.clone(), tags.clone());
                     match project.start_work(work_slice) {
                         Ok(()) => {
-                            self.changes.push(Change::WorkSliceStarted {
+                            self.record_change(Change::WorkSliceStarted {
                                 project_id: unsafe { id.inner() },
                                 work_slice_id: unsafe { work_id.inner() },
                                 start_time: time,
                                 payment,
+                                note,
+                                tags,
                             });
                             Ok(())
                         }
@@ -207,17 +493,40 @@ impl State {
     }
 
     /// Tries to end the current incomplete work slice for a project,
-    // but can fail. See `WorkEndError` for more information on how.
-    pub fn end_work(&mut self, id: ProjectId, time: DateTime<Utc>) -> Result<(), WorkEndError> {
+    /// replacing its note and tags if `annotation` isn't `None`,
+    /// but can fail. See `WorkEndError` for more information on how.
+    pub fn end_work(
+        &mut self,
+        id: ProjectId,
+        time: DateTime<Utc>,
+        annotation: Option<Annotation>,
+    ) -> Result<(), WorkEndError> {
         match self.project_from_id_mut(id) {
             Some(project) => {
                 let work_id = project.current_work_slice().map(|x| x.id());
                 match project.complete_work(time) {
                     Ok(()) => {
-                        self.changes.push(Change::WorkSliceCompleted {
+                        let work_id = work_id.unwrap();
+                        let old_annotation = annotation.as_ref().and_then(|x| {
+                            let (note, tags) = project.annotate_work_slice(
+                                work_id,
+                                x.note.clone(),
+                                x.tags.clone(),
+                            )?;
+                            Some(Annotation { note, tags })
+                        });
+                        // the annotation is only recorded if it changed
+                        let (old_annotation, annotation) = if old_annotation == annotation {
+                            (None, None)
+                        } else {
+                            (old_annotation, annotation)
+                        };
+                        self.record_change(Change::WorkSliceCompleted {
                             project_id: unsafe { id.inner() },
-                            work_slice_id: unsafe { work_id.unwrap().inner() },
+                            work_slice_id: unsafe { work_id.inner() },
                             end_time: time,
+                            old_annotation,
+                            annotation,
                         });
                         Ok(())
                     }
@@ -228,20 +537,167 @@ impl State {
         }
     }
 
-    /// Tries to delete a project, but can fail if the project has already been deleted.
+    /// Pauses the current work slice of a project, starting a break which isn't counted as time worked.
+    /// Fails if there is no ongoing work, if it's already paused,
+    /// or if `time` is in the future or before the work started or its last break ended.
+    pub fn pause_work(&mut self, id: ProjectId, time: DateTime<Utc>) -> Result<(), WorkPauseError> {
+        let work_slice = self
+            .project_from_id_mut(id)
+            .ok_or(WorkPauseError::InvalidProjectId)?
+            .current_work_slice_mut()
+            .ok_or(WorkPauseError::NoWorkToPause)?;
+        work_slice.pause(time)?;
+        let work_slice_id = unsafe { work_slice.id().inner() };
+        self.record_change(Change::WorkSlicePaused {
+            project_id: unsafe { id.inner() },
+            work_slice_id,
+            time,
+        });
+        Ok(())
+    }
+
+    /// Resumes the paused work slice of a project, ending its break.
+    /// Fails if there is no paused work, or if `time` is in the future or before the work was paused.
+    pub fn resume_work(
+        &mut self,
+        id: ProjectId,
+        time: DateTime<Utc>,
+    ) -> Result<(), WorkResumeError> {
+        let work_slice = self
+            .project_from_id_mut(id)
+            .ok_or(WorkResumeError::InvalidProjectId)?
+            .current_work_slice_mut()
+            .ok_or(WorkResumeError::NotPaused)?;
+        let paused_at = work_slice.resume(time)?;
+        let work_slice_id = unsafe { work_slice.id().inner() };
+        self.record_change(Change::WorkSliceResumed {
+            project_id: unsafe { id.inner() },
+            work_slice_id,
+            paused_at,
+            time,
+        });
+        Ok(())
+    }
+
+    /// Adds a complete work slice to a project, e.g. for work which was done earlier
+    /// but wasn't tracked at the time, and returns its ID.
+    /// This can be used while the project has ongoing work, and uses the project's
+    /// default payment if `payment` is `None`. The work slice is given the note and tags in `annotation`.
+    /// Fails if the project doesn't exist or is archived, if the start is in the future,
+    /// or if the end isn't after the start.
+    pub fn add_complete_work_slice(
+        &mut self,
+        project_id: ProjectId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        payment: Option<Payment>,
+        annotation: Annotation,
+    ) -> Result<WorkSliceId, AddWorkSliceError> {
+        let project = self
+            .project_from_id(project_id)
+            .ok_or(AddWorkSliceError::InvalidProjectId)?;
+        if project.is_archived() {
+            return Err(AddWorkSliceError::ProjectArchived);
+        }
+        let payment = match payment {
+            Some(payment) => payment,
+            None => self
+                .default_payment_of(project)
+                .ok_or(AddWorkSliceError::NoPayment)?,
+        };
+        let data = CompleteWorkSliceData {
+            start,
+            end,
+            payment,
+            breaks: Vec::new(),
+            note: annotation.note,
+            tags: annotation.tags,
+            id: self.previous_work_slice_id + 1,
+        };
+        let work_slice = data.clone().into_work_slice()?;
+        self.check_overlaps(start, Some(end), None)
+            .map_err(AddWorkSliceError::Overlap)?;
+        let id = self.new_work_slice_id();
+        self.project_from_id_mut(project_id)
+            .ok_or(AddWorkSliceError::InvalidProjectId)?
+            .insert_complete_work_slice(work_slice);
+        self.record_change(Change::WorkSliceLogged {
+            project_id: unsafe { project_id.inner() },
+            work_slice: data,
+        });
+        Ok(id)
+    }
+
+    /// Tries to change the start, end and payment of a complete work slice,
+    /// enforcing the same rules as when it was completed: the start can't be in the future,
+    /// and the end must be after the start.
+    /// To keep any of these the same, pass the work slice's current value.
+    /// This also fails if the new times would overlap with other work when the overlap policy is `Forbid`.
+    pub fn edit_work_slice(
+        &mut self,
+        id: WorkSliceId,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        payment: Payment,
+    ) -> Result<(), EditWorkSliceError> {
+        let project_id = self
+            .project_id_from_work_slice(id)
+            .ok_or(EditWorkSliceError::WorkSliceNotFound)?;
+        if self.is_invoiced(id) {
+            return Err(EditWorkSliceError::Invoiced);
+        }
+        // the times are checked before overlaps, so invalid times are reported as such
+        self.work_slice_from_id(id)
+            .and_then(WorkSlice::complete)
+            .ok_or(EditWorkSliceError::WorkSliceIncomplete)?
+            .check_edit(start, end)?;
+        self.check_overlaps(start, Some(end), Some(id))
+            .map_err(EditWorkSliceError::Overlap)?;
+        let work_slice = self
+            .project_from_id_mut(project_id)
+            .and_then(|x| x.complete_work_slice_mut(id))
+            .ok_or(EditWorkSliceError::WorkSliceIncomplete)?;
+        let (old_start, old_end, old_payment) = (
+            work_slice.start(),
+            work_slice.completion(),
+            work_slice.payment(),
+        );
+        work_slice.edit(start, end, payment)?;
+        self.record_change(Change::WorkSliceEdited {
+            project_id: unsafe { project_id.inner() },
+            work_slice_id: unsafe { id.inner() },
+            old_start,
+            old_end,
+            old_payment,
+            start,
+            end,
+            payment,
+        });
+        Ok(())
+    }
+
+    /// Tries to delete a project, but can fail if the project has already been deleted,
+    /// or if any of its work slices are on an invoice.
+    /// The project is moved to the trash, so it can be restored with `restore_project`.
     /// Returns true if the project deletion is successful, or false otherwise.
     pub fn delete_project(&mut self, id: ProjectId) -> bool {
         let index = self
-            .all_project_ids()
+            .all_projects()
             .enumerate()
-            .find(|(_, project_id)| *project_id == id)
+            .find(|(_, project)| project.id() == id && !self.project_is_invoiced(project))
             .map(|(i, _)| i);
         match index {
             Some(i) => {
-                self.changes.push(Change::ProjectDeleted {
-                    id: unsafe { id.inner() },
+                let project = ProjectData::from(&self.projects.remove(i));
+                let deleted_at = Utc::now();
+                self.add_to_trash(TrashEntry {
+                    deleted_at,
+                    item: TrashedItem::Project(project.clone()),
+                });
+                self.record_change(Change::ProjectDeleted {
+                    project,
+                    deleted_at: Some(deleted_at),
                 });
-                self.projects.swap_remove(i);
                 true
             }
             None => false,
@@ -249,7 +705,7 @@ impl State {
     }
 
     /// Tries to delete a work slice from its project, but fails if the work slice has been deleted,
-    /// or if its project has been deleted. Returns true if it succeeds.
+    /// or if its project has been deleted, or if it's on an invoice. Returns true if it succeeds.
     pub fn delete_work_slice(&mut self, id: WorkSliceId) -> bool {
         match self.project_id_from_work_slice(id) {
             Some(project_id) => self.delete_work_slice_from_project(project_id, id),
@@ -258,20 +714,42 @@ impl State {
     }
 
     /// Tries to delete a work slice from the specified project, but fails if the work slice has been deleted,
-    /// or if it is not part of the project, or if the project has already been deleted.
+    /// or if it is not part of the project, or if the project has already been deleted,
+    /// or if it's on an invoice.
+    /// The work slice is moved to the trash, so it can be restored with `restore_work_slice`.
     pub fn delete_work_slice_from_project(
         &mut self,
         project_id: ProjectId,
         work_slice_id: WorkSliceId,
     ) -> bool {
+        if self.is_invoiced(work_slice_id) {
+            return false;
+        }
+        let work_slice = match self
+            .project_from_id(project_id)
+            .and_then(|project| project.work_slice_from_id(work_slice_id))
+        {
+            Some(work_slice) => WorkSliceData::from(work_slice),
+            None => return false,
+        };
         match self
             .project_from_id_mut(project_id)
             .map(|project| project.delete_work_slice(work_slice_id))
         {
             Some(true) => {
-                self.changes.push(Change::WorkSliceDeleted {
-                    project_id: unsafe { project_id.inner() },
-                    work_slice_id: unsafe { work_slice_id.inner() },
+                let project_id = unsafe { project_id.inner() };
+                let deleted_at = Utc::now();
+                self.add_to_trash(TrashEntry {
+                    deleted_at,
+                    item: TrashedItem::WorkSlice {
+                        project_id,
+                        work_slice: work_slice.clone(),
+                    },
+                });
+                self.record_change(Change::WorkSliceDeleted {
+                    project_id,
+                    work_slice,
+                    deleted_at: Some(deleted_at),
                 });
                 true
             }
@@ -287,15 +765,66 @@ impl State {
     }
 }
 impl State {
+    /// Returns the changes made since the last call to `handle_changes` or `save`,
+    /// or since this State was constructed. These changes won't be saved to storage.
     pub fn handle_changes(&mut self) -> Vec<Change> {
         std::mem::take(&mut self.changes)
     }
+
+    /// Sets what happens when saving fails while this State is being dropped.
+    /// By default, the error is printed to stderr.
+    pub fn on_drop_save_error(&mut self, handler: impl FnMut(Box<dyn Error>) + 'static) {
+        self.drop_save_error_handler = Some(Box::new(handler));
+    }
+
+    /// Saves the changes made since the last call to `handle_changes` or `save`
+    /// to storage, or does nothing if there aren't any.
+    /// If saving fails, the changes are kept so that saving can be tried again.
+    pub fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        if self.changes.is_empty() {
+            return Ok(());
+        }
+        let changes = std::mem::take(&mut self.changes);
+        match self.storage.apply_changes(
+            changes.clone(),
+            self.projects.iter().collect(),
+            &self.clients,
+            &self.invoices,
+            &self.trash,
+            self.previous_invoice_number,
+        ) {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                self.changes = changes;
+                Err(err)
+            }
+        }
+    }
+
+    /// Overwrites everything in storage with a snapshot of all of the projects, clients, invoices and the trash,
+    /// which includes any changes which haven't been saved yet.
+    pub fn save_snapshot(&mut self) -> Result<(), Box<dyn Error>> {
+        self.storage.snapshot(StateData {
+            projects: self.projects.iter().map(ProjectData::from).collect(),
+            clients: self.clients.iter().map(ClientData::from).collect(),
+            invoices: self.invoices.iter().map(InvoiceData::from).collect(),
+            trash: self.trash.clone(),
+            previous_invoice_number: self.previous_invoice_number,
+        })?;
+        self.changes.clear();
+        Ok(())
+    }
 }
 impl Drop for State {
+    /// Saves any changes which haven't been saved yet.
+    /// If saving fails, the error is passed to the handler set with `on_drop_save_error`,
+    /// or printed to stderr if there isn't one. Call `save` before this State is dropped to handle errors yourself.
     fn drop(&mut self) {
-        (self.commit_on_drop)(
-            std::mem::take(&mut self.changes),
-            self.projects.iter().collect(),
-        );
+        if let Err(err) = self.save() {
+            match &mut self.drop_save_error_handler {
+                Some(handler) => handler(err),
+                None => eprintln!("Failed to save changes: {err}"),
+            }
+        }
     }
 }
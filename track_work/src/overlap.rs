@@ -0,0 +1,281 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{DateTime, Utc};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{State, UnknownOverlapPolicyError, WorkSliceId};
+
+/// What a `State` does when new or edited work would overlap in time with other work,
+/// in the same project or in another one.
+/// Ongoing work counts as lasting forever, so it overlaps with all work which starts after it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum OverlapPolicy {
+    /// Overlapping work is allowed.
+    #[default]
+    Allow,
+    /// Overlapping work is allowed, but the overlaps are kept until `take_overlap_warnings` is called.
+    Warn,
+    /// Work can't overlap. This means that only one work slice can be ongoing at a time.
+    Forbid,
+}
+impl Display for OverlapPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                OverlapPolicy::Allow => "allow",
+                OverlapPolicy::Warn => "warn",
+                OverlapPolicy::Forbid => "forbid",
+            }
+        )
+    }
+}
+impl FromStr for OverlapPolicy {
+    type Err = UnknownOverlapPolicyError;
+
+    /// Parses "allow", "warn" or "forbid".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "allow" => Ok(OverlapPolicy::Allow),
+            "warn" => Ok(OverlapPolicy::Warn),
+            "forbid" => Ok(OverlapPolicy::Forbid),
+            _ => Err(UnknownOverlapPolicyError),
+        }
+    }
+}
+
+/// Two work slices which were worked on at the same time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overlap {
+    /// One of the work slices. For overlaps found by `State::overlaps`, this is the one which started first.
+    pub first: WorkSliceId,
+    /// The other work slice. For overlap warnings, this is the work which was started, logged or edited.
+    pub second: WorkSliceId,
+    /// When the overlap starts.
+    pub start: DateTime<Utc>,
+    /// When the overlap ends, or `None` if both work slices are ongoing.
+    pub end: Option<DateTime<Utc>>,
+}
+
+impl State {
+    /// Returns what happens when new or edited work overlaps with other work.
+    pub fn overlap_policy(&self) -> OverlapPolicy {
+        self.overlap_policy
+    }
+
+    /// Changes what happens when new or edited work overlaps with other work.
+    /// This doesn't affect work which already overlaps, or changes which are replayed or undone.
+    pub fn set_overlap_policy(&mut self, policy: OverlapPolicy) {
+        self.overlap_policy = policy;
+    }
+
+    /// Returns the overlaps found since the last call to this,
+    /// when work was started, logged or edited with the `Warn` policy.
+    pub fn take_overlap_warnings(&mut self) -> Vec<Overlap> {
+        std::mem::take(&mut self.overlap_warnings)
+    }
+
+    /// Finds every pair of work slices which overlap in time, in any projects,
+    /// ordered by when the overlaps start.
+    pub fn overlaps(&self) -> Vec<Overlap> {
+        let mut spans = self.work_slice_spans();
+        spans.sort();
+        let mut overlaps = Vec::new();
+        for (i, &(_, end, id)) in spans.iter().enumerate() {
+            // the spans are sorted by start, so only later spans which start before this one ends can overlap
+            for &(other_start, other_end, other_id) in &spans[i + 1..] {
+                if end.is_some_and(|end| other_start >= end) {
+                    break;
+                }
+                overlaps.push(Overlap {
+                    first: unsafe { WorkSliceId::new(id) },
+                    second: unsafe { WorkSliceId::new(other_id) },
+                    start: other_start,
+                    end: match (end, other_end) {
+                        (Some(a), Some(b)) => Some(a.min(b)),
+                        (a, b) => a.or(b),
+                    },
+                });
+            }
+        }
+        overlaps.sort_by_key(|x| x.start);
+        overlaps
+    }
+
+    /// Finds the overlaps between every work slice and a span of time, which is ongoing if `end` is `None`.
+    /// The work slice with the ID `ignore` is skipped, so a work slice's new times can be checked.
+    /// Each overlap's `second` is `ignore`, or the first unused work slice ID if `ignore` is `None`.
+    pub(crate) fn find_overlaps(
+        &self,
+        start: DateTime<Utc>,
+        end: Option<DateTime<Utc>>,
+        ignore: Option<WorkSliceId>,
+    ) -> Vec<Overlap> {
+        let second = match ignore {
+            Some(id) => id,
+            None => unsafe { WorkSliceId::new(self.previous_work_slice_id + 1) },
+        };
+        let mut overlaps: Vec<_> = self
+            .work_slice_spans()
+            .into_iter()
+            .filter(|&(_, _, id)| ignore.is_none_or(|x| unsafe { x.inner() } != id))
+            .filter(|&(other_start, other_end, _)| {
+                end.is_none_or(|end| other_start < end)
+                    && other_end.is_none_or(|other_end| start < other_end)
+            })
+            .map(|(other_start, other_end, id)| Overlap {
+                first: unsafe { WorkSliceId::new(id) },
+                second,
+                start: start.max(other_start),
+                end: match (end, other_end) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                },
+            })
+            .collect();
+        overlaps.sort_by_key(|x| x.start);
+        overlaps
+    }
+
+    /// Applies the overlap policy to new or edited work over a span of time.
+    /// Fails with the IDs of the overlapping work slices, in the order the overlaps start,
+    /// if the policy is `Forbid` and there are overlaps, and keeps the overlaps as warnings if the policy is `Warn`.
+    pub(crate) fn check_overlaps(
+        &mut self,
+        start: DateTime<Utc>,
+        end: Option<DateTime<Utc>>,
+        ignore: Option<WorkSliceId>,
+    ) -> Result<(), Vec<WorkSliceId>> {
+        if self.overlap_policy == OverlapPolicy::Allow {
+            return Ok(());
+        }
+        let overlaps = self.find_overlaps(start, end, ignore);
+        match self.overlap_policy {
+            OverlapPolicy::Forbid if !overlaps.is_empty() => {
+                Err(overlaps.into_iter().map(|x| x.first).collect())
+            }
+            OverlapPolicy::Warn => {
+                self.overlap_warnings.extend(overlaps);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the start, end (or `None` if ongoing) and raw ID of every work slice.
+    fn work_slice_spans(&self) -> Vec<(DateTime<Utc>, Option<DateTime<Utc>>, u64)> {
+        self.all_projects()
+            .flat_map(|project| {
+                project
+                    .complete_work_slices()
+                    .map(|x| (x.start(), Some(x.completion()), unsafe { x.id().inner() }))
+                    .chain(
+                        project
+                            .current_work_slice()
+                            .map(|x| (x.start(), None, unsafe { x.id().inner() })),
+                    )
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use crate::{
+        AddWorkSliceError, Annotation, Currency, MemoryStorage, Money, OverlapPolicy, Payment,
+        State, WorkStartError,
+    };
+
+    #[test]
+    fn overlap_policies() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let payment = Some(Payment::Hourly(Money::new(1000, Currency::Gbp)));
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let first = state.new_project("first".to_string(), "".to_string(), None);
+        let second = state.new_project("second".to_string(), "".to_string(), None);
+        let a = state
+            .add_complete_work_slice(
+                first,
+                now - hours(5),
+                now - hours(3),
+                payment,
+                Annotation::default(),
+            )
+            .unwrap();
+        let b = state
+            .add_complete_work_slice(
+                second,
+                now - hours(4),
+                now - hours(2),
+                payment,
+                Annotation::default(),
+            )
+            .unwrap();
+        state
+            .start_work(first, payment, now - hours(1), Annotation::default())
+            .unwrap();
+        let ongoing = state
+            .project_from_id(first)
+            .unwrap()
+            .current_work_slice()
+            .unwrap()
+            .id();
+
+        let overlaps = state.overlaps();
+        assert_eq!(overlaps.len(), 1);
+        assert_eq!((overlaps[0].first, overlaps[0].second), (a, b));
+        assert_eq!(overlaps[0].start, now - hours(4));
+        assert_eq!(overlaps[0].end, Some(now - hours(3)));
+
+        state.set_overlap_policy(OverlapPolicy::Forbid);
+        assert_eq!(
+            state.start_work(
+                second,
+                payment,
+                now - TimeDelta::minutes(30),
+                Annotation::default()
+            ),
+            Err(WorkStartError::Overlap(vec![ongoing]))
+        );
+        assert_eq!(
+            state.add_complete_work_slice(
+                second,
+                now - hours(6),
+                now - hours(4),
+                payment,
+                Annotation::default()
+            ),
+            Err(AddWorkSliceError::Overlap(vec![a]))
+        );
+        state
+            .add_complete_work_slice(
+                second,
+                now - hours(7),
+                now - hours(6),
+                payment,
+                Annotation::default(),
+            )
+            .unwrap();
+
+        state.set_overlap_policy(OverlapPolicy::Warn);
+        state
+            .start_work(
+                second,
+                payment,
+                now - TimeDelta::minutes(30),
+                Annotation::default(),
+            )
+            .unwrap();
+        let warnings = state.take_overlap_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].end, None);
+        assert!(state.take_overlap_warnings().is_empty());
+        assert_eq!(state.overlaps().len(), 2);
+    }
+}
@@ -1,130 +1,514 @@
-use std::{fmt::Display, iter::Sum, ops::Add};
+use std::{collections::BTreeMap, fmt::Display, iter::Sum, ops::Add, str::FromStr};
 
 use chrono::TimeDelta;
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-/// Represents a whole number of pence.
+use crate::{
+    AddMoneyError, CurrencyMismatchError, UnknownCurrencyError, UnknownDurationRoundingError,
+};
+
+/// A currency which money can be in.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "UPPERCASE"))]
+pub enum Currency {
+    #[default]
+    Gbp,
+    Eur,
+    Usd,
+    Jpy,
+}
+impl Currency {
+    /// Returns the ISO 4217 code for this currency, like "GBP".
+    pub fn code(&self) -> &'static str {
+        match self {
+            Currency::Gbp => "GBP",
+            Currency::Eur => "EUR",
+            Currency::Usd => "USD",
+            Currency::Jpy => "JPY",
+        }
+    }
+
+    /// Returns the symbol which is written before amounts of this currency, like "£".
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Currency::Gbp => "£",
+            Currency::Eur => "€",
+            Currency::Usd => "$",
+            Currency::Jpy => "¥",
+        }
+    }
+
+    /// Returns the number of decimal places used when writing amounts of this currency,
+    /// which is the number of digits in its minor unit (e.g. 2 for pence).
+    pub fn decimal_places(&self) -> u32 {
+        match self {
+            Currency::Gbp | Currency::Eur | Currency::Usd => 2,
+            Currency::Jpy => 0,
+        }
+    }
+
+    /// Returns the number of minor units (e.g. pence) in one major unit (e.g. pounds).
+    pub fn minor_units_per_major(&self) -> u32 {
+        10u32.pow(self.decimal_places())
+    }
+}
+impl Display for Currency {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+impl FromStr for Currency {
+    type Err = UnknownCurrencyError;
+
+    /// Parses an ISO 4217 code, like "GBP" or "eur".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "GBP" => Ok(Currency::Gbp),
+            "EUR" => Ok(Currency::Eur),
+            "USD" => Ok(Currency::Usd),
+            "JPY" => Ok(Currency::Jpy),
+            _ => Err(UnknownCurrencyError),
+        }
+    }
+}
+
+/// Represents a whole number of the minor unit of a currency (e.g. pence).
 /// If you want to store fractional components, see `MoneyExact`
 #[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub struct Money(u32);
+#[cfg_attr(feature = "serde", serde(from = "MoneyData"))]
+pub struct Money {
+    amount: u32,
+    currency: Currency,
+}
 impl Money {
     /// Returns a new instance of `Money`
-    /// with `money` pence.
-    pub fn new(money: u32) -> Self {
-        Self(money)
+    /// with `amount` of the currency's minor unit (e.g. pence).
+    pub fn new(amount: u32, currency: Currency) -> Self {
+        Self { amount, currency }
     }
 
-    /// Returns the number of pence
+    /// Returns the number of the currency's minor unit (e.g. pence)
     /// that `self` represents.
-    pub fn as_pence(&self) -> u32 {
-        self.0
+    pub fn amount(&self) -> u32 {
+        self.amount
     }
-}
-impl Sum<Money> for Money {
-    fn sum<I: Iterator<Item = Money>>(iter: I) -> Self {
-        Money(iter.map(|x| x.0).sum())
+
+    /// Returns the currency that this money is in.
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    /// Adds two amounts of money, but fails if they're in different currencies
+    /// or the total is too large to be stored.
+    pub fn checked_add(self, rhs: Money) -> Result<Money, AddMoneyError> {
+        if self.currency != rhs.currency {
+            return Err(CurrencyMismatchError {
+                expected: self.currency,
+                found: rhs.currency,
+            }
+            .into());
+        }
+        let amount = self
+            .amount
+            .checked_add(rhs.amount)
+            .ok_or(AddMoneyError::Overflow)?;
+        Ok(Money::new(amount, self.currency))
     }
 }
 impl Display for Money {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let pounds = self.0 / 100;
-        let pence = self.0 % 100;
-        let pence = if pence >= 10 {
-            pence.to_string()
-        } else {
-            format!("0{pence}")
-        };
-        write!(f, "£{pounds}.{pence}")
+        let symbol = self.currency.symbol();
+        let per_major = self.currency.minor_units_per_major();
+        let major = self.amount / per_major;
+        match self.currency.decimal_places() {
+            0 => write!(f, "{symbol}{major}"),
+            places => write!(
+                f,
+                "{symbol}{major}.{minor:0places$}",
+                minor = self.amount % per_major,
+                places = places as usize
+            ),
+        }
     }
 }
 
-/// Represents some amount of money.
-/// This can be used to store fractional pence.
-/// If you want perfect precision (with no fractional components), see `Money`
-#[derive(Clone, Copy, PartialEq, PartialOrd, Debug)]
+/// How `Money` is saved. Money saved before currencies were supported
+/// is just a number of pence.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MoneyData {
+    Pence(u32),
+    Money {
+        amount: u32,
+        #[serde(default)]
+        currency: Currency,
+    },
+}
+#[cfg(feature = "serde")]
+impl From<MoneyData> for Money {
+    fn from(value: MoneyData) -> Self {
+        match value {
+            MoneyData::Pence(amount) => Money::new(amount, Currency::Gbp),
+            MoneyData::Money { amount, currency } => Money::new(amount, currency),
+        }
+    }
+}
+
+/// How many parts each of the currency's minor unit (e.g. pence) is split into by `MoneyExact`.
+/// This is the number of milliseconds in a day, so hourly and daily rates
+/// can be calculated exactly for any whole number of milliseconds.
+const PARTS_PER_MINOR_UNIT: u128 = 24 * 60 * 60 * 1000;
+
+/// How a fractional amount of money is rounded to a whole number of the currency's minor unit.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Rounding {
+    /// Round to the nearest minor unit, with halves rounded up.
+    #[default]
+    HalfUp,
+    /// Round to the nearest minor unit, with halves rounded to the even one (banker's rounding).
+    HalfEven,
+    /// Round down, ignoring any fraction of a minor unit.
+    Truncate,
+}
+
+/// How an amount is rounded to a whole number of the currency's minor unit (e.g. pence) when it's billed,
+/// on invoices and statements. Each line is rounded on its own and the rounded lines are added up,
+/// so a bill's total always matches its lines.
+pub const BILLING_ROUNDING: Rounding = Rounding::HalfUp;
+
+/// Represents some amount of money exactly.
+/// This can be used to store fractional amounts of the currency's minor unit (e.g. pence),
+/// down to one part in 86,400,000 of the minor unit.
+/// If you want a whole amount of money (with no fractional components), see `Money`,
+/// and use `round` to convert to it.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
-pub struct MoneyExact(f64);
+pub struct MoneyExact {
+    parts: u128,
+    currency: Currency,
+}
 impl MoneyExact {
-    /// Returns a new instance of `MoneyExact` if `money` is non-negative,
-    /// or `None` otherwise.
-    pub fn new(money: f64) -> Option<Self> {
-        if money >= 0.0 {
-            Some(Self(money))
+    /// Returns `numerator / denominator` of the currency's minor unit (e.g. pence),
+    /// or `None` if `denominator` is zero or the amount can't be stored exactly.
+    pub fn from_ratio(numerator: u128, denominator: u128, currency: Currency) -> Option<Self> {
+        let scaled = numerator.checked_mul(PARTS_PER_MINOR_UNIT)?;
+        if denominator == 0 || !scaled.is_multiple_of(denominator) {
+            return None;
+        }
+        Some(Self {
+            parts: scaled / denominator,
+            currency,
+        })
+    }
+
+    /// Returns no money in the given currency.
+    pub fn zero(currency: Currency) -> Self {
+        Self { parts: 0, currency }
+    }
+
+    /// Returns the currency that this money is in.
+    pub fn currency(&self) -> Currency {
+        self.currency
+    }
+
+    /// Returns true if there's a fraction of a minor unit (e.g. pence) in this amount.
+    pub fn has_fraction(&self) -> bool {
+        !self.parts.is_multiple_of(PARTS_PER_MINOR_UNIT)
+    }
+
+    /// Adds two amounts of money, but fails if they're in different currencies
+    /// or the total is too large to be stored.
+    pub fn checked_add(self, rhs: MoneyExact) -> Result<MoneyExact, AddMoneyError> {
+        if self.currency != rhs.currency {
+            return Err(CurrencyMismatchError {
+                expected: self.currency,
+                found: rhs.currency,
+            }
+            .into());
+        }
+        let parts = self
+            .parts
+            .checked_add(rhs.parts)
+            .ok_or(AddMoneyError::Overflow)?;
+        Ok(MoneyExact {
+            parts,
+            currency: self.currency,
+        })
+    }
+
+    /// Subtracts `rhs` from this amount, giving zero if `rhs` is larger,
+    /// but fails if they're in different currencies.
+    pub fn saturating_sub(self, rhs: MoneyExact) -> Result<MoneyExact, CurrencyMismatchError> {
+        if self.currency == rhs.currency {
+            Ok(MoneyExact {
+                parts: self.parts.saturating_sub(rhs.parts),
+                currency: self.currency,
+            })
         } else {
-            None
+            Err(CurrencyMismatchError {
+                expected: self.currency,
+                found: rhs.currency,
+            })
         }
     }
 
-    /// Returns the number of pence that this value stores,
-    /// which is guaranteed to be non-negative.
-    pub fn as_pence(&self) -> f64 {
-        self.0
+    /// Returns the number of the currency's minor unit (e.g. pence) in this amount,
+    /// rounded to a whole number with the given rounding.
+    pub fn minor_units(&self, rounding: Rounding) -> u128 {
+        round_ratio(self.parts, PARTS_PER_MINOR_UNIT, rounding)
+    }
+
+    /// Rounds this amount to a whole number of the currency's minor unit (e.g. pence),
+    /// or returns `None` if the result is too large to be stored in `Money`.
+    pub fn round(&self, rounding: Rounding) -> Option<Money> {
+        let amount = self.minor_units(rounding).try_into().ok()?;
+        Some(Money::new(amount, self.currency))
+    }
+
+    /// Rounds this amount with `BILLING_ROUNDING`, as it's billed on a line of an invoice or statement.
+    /// Amounts too large to be stored in `Money` are billed as the largest amount which can be.
+    pub fn billed(&self) -> Money {
+        self.round(BILLING_ROUNDING)
+            .unwrap_or(Money::new(u32::MAX, self.currency))
     }
 }
 impl Display for MoneyExact {
+    /// Writes the amount in the currency's major unit (e.g. pounds), rounded half up.
+    /// By default, this uses the currency's decimal places, but a precision
+    /// can be given to show fractions of the minor unit (e.g. `{:.4}`).
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let pounds = (self.0 / 100.0).floor();
-        let pence = self.0 % 100.0;
-        let pence = if pence >= 10.0 {
-            (pence / 100.0).to_string().chars().skip(2).collect()
-        } else if pence > 0.0 {
-            format!(
-                "0{pence}",
-                pence = (pence / 100.0)
-                    .to_string()
-                    .chars()
-                    .skip(2)
-                    .collect::<String>()
-            )
-        } else {
-            "00".to_string()
-        };
-        write!(f, "£{pounds}.{pence:.2}")
-    }
-}
-impl Sum<MoneyExact> for MoneyExact {
-    fn sum<I: Iterator<Item = MoneyExact>>(iter: I) -> Self {
-        MoneyExact(iter.map(|x| x.0).sum())
+        let places = f
+            .precision()
+            .unwrap_or(self.currency.decimal_places() as usize);
+        let scale = 10u128.pow(places as u32);
+        let per_major = PARTS_PER_MINOR_UNIT * u128::from(self.currency.minor_units_per_major());
+        let scaled = round_ratio(self.parts * scale, per_major, Rounding::HalfUp);
+        let symbol = self.currency.symbol();
+        match places {
+            0 => write!(f, "{symbol}{scaled}"),
+            places => write!(
+                f,
+                "{symbol}{major}.{minor:0places$}",
+                major = scaled / scale,
+                minor = scaled % scale,
+            ),
+        }
     }
 }
 
 impl From<Money> for MoneyExact {
     fn from(value: Money) -> Self {
-        MoneyExact(value.0.into())
+        MoneyExact {
+            parts: u128::from(value.amount) * PARTS_PER_MINOR_UNIT,
+            currency: value.currency,
+        }
     }
 }
 
-impl Add<Money> for Money {
-    type Output = Money;
+/// Divides `numerator` by `denominator`, rounding to a whole number with the given rounding.
+fn round_ratio(numerator: u128, denominator: u128, rounding: Rounding) -> u128 {
+    let quotient = numerator / denominator;
+    let remainder = numerator % denominator;
+    let round_up = match rounding {
+        Rounding::Truncate => false,
+        Rounding::HalfUp => remainder * 2 >= denominator,
+        Rounding::HalfEven => {
+            remainder * 2 > denominator || (remainder * 2 == denominator && quotient % 2 == 1)
+        }
+    };
+    quotient + u128::from(round_up)
+}
 
-    fn add(self, rhs: Money) -> Self::Output {
-        Money(self.0 + rhs.0)
+/// Totals of money which may be in several currencies,
+/// with a separate total for each currency.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct MoneyTotals(BTreeMap<Currency, MoneyExact>);
+impl MoneyTotals {
+    /// Returns the total for a single currency, which is zero if nothing was added in it.
+    pub fn get(&self, currency: Currency) -> MoneyExact {
+        self.0
+            .get(&currency)
+            .copied()
+            .unwrap_or(MoneyExact::zero(currency))
     }
-}
 
-impl Add<MoneyExact> for MoneyExact {
-    type Output = MoneyExact;
+    /// Returns the total for each currency which has had money added in it,
+    /// ordered by currency.
+    pub fn iter(&self) -> impl Iterator<Item = MoneyExact> + '_ {
+        self.0.values().copied()
+    }
 
-    fn add(self, rhs: MoneyExact) -> Self::Output {
-        MoneyExact(self.0 + rhs.0)
+    /// Returns true if no money has been added.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
     }
 }
+impl Add<MoneyExact> for MoneyTotals {
+    type Output = MoneyTotals;
 
-impl Add<Money> for MoneyExact {
-    type Output = MoneyExact;
+    fn add(mut self, rhs: MoneyExact) -> Self::Output {
+        self.0
+            .entry(rhs.currency)
+            .or_insert(MoneyExact::zero(rhs.currency))
+            .parts += rhs.parts;
+        self
+    }
+}
+impl Add<Money> for MoneyTotals {
+    type Output = MoneyTotals;
 
     fn add(self, rhs: Money) -> Self::Output {
-        MoneyExact(self.0 + f64::from(rhs.0))
+        self + MoneyExact::from(rhs)
+    }
+}
+impl Add<MoneyTotals> for MoneyTotals {
+    type Output = MoneyTotals;
+
+    fn add(self, rhs: MoneyTotals) -> Self::Output {
+        rhs.iter().fold(self, |acc, e| acc + e)
+    }
+}
+impl Sum<MoneyExact> for MoneyTotals {
+    fn sum<I: Iterator<Item = MoneyExact>>(iter: I) -> Self {
+        iter.fold(MoneyTotals::default(), |acc, e| acc + e)
+    }
+}
+impl Sum<Money> for MoneyTotals {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Self {
+        iter.fold(MoneyTotals::default(), |acc, e| acc + e)
+    }
+}
+impl Sum<MoneyTotals> for MoneyTotals {
+    fn sum<I: Iterator<Item = MoneyTotals>>(iter: I) -> Self {
+        iter.fold(MoneyTotals::default(), |acc, e| acc + e)
+    }
+}
+impl Display for MoneyTotals {
+    /// Writes each currency's total, separated by " + ", or "nothing" if there is no money,
+    /// as there's no currency to write a zero amount in.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "nothing");
+        }
+        let totals: Vec<_> = self.iter().map(|x| x.to_string()).collect();
+        write!(f, "{}", totals.join(" + "))
+    }
+}
+
+const MILLISECONDS_PER_HOUR: u128 = 60 * 60 * 1000;
+const MILLISECONDS_PER_DAY: u128 = 24 * MILLISECONDS_PER_HOUR;
+
+/// Which way a duration is rounded to a whole number of billing increments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum DurationRounding {
+    /// Any part of an increment is billed as a whole increment.
+    #[default]
+    Up,
+    /// Only whole increments are billed.
+    Down,
+    /// Round to the nearest increment, with halves rounded up.
+    Nearest,
+}
+impl Display for DurationRounding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                DurationRounding::Up => "up",
+                DurationRounding::Down => "down",
+                DurationRounding::Nearest => "nearest",
+            }
+        )
     }
 }
+impl FromStr for DurationRounding {
+    type Err = UnknownDurationRoundingError;
 
-impl Add<MoneyExact> for Money {
-    type Output = MoneyExact;
+    /// Parses "up", "down" or "nearest".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "up" => Ok(DurationRounding::Up),
+            "down" => Ok(DurationRounding::Down),
+            "nearest" => Ok(DurationRounding::Nearest),
+            _ => Err(UnknownDurationRoundingError),
+        }
+    }
+}
 
-    fn add(self, rhs: MoneyExact) -> Self::Output {
-        MoneyExact(f64::from(self.0) + rhs.0)
+/// Stores how the duration of a work slice is turned into the duration which is billed for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BillingPolicy {
+    /// The length of each billing increment, in seconds.
+    /// Durations are rounded to a whole number of increments, unless this is 0.
+    pub increment_seconds: u32,
+    /// Which way durations are rounded to a whole number of increments.
+    pub rounding: DurationRounding,
+    /// The minimum billable duration of each work slice, in seconds.
+    /// This is applied after rounding.
+    pub minimum_seconds: u32,
+}
+impl BillingPolicy {
+    /// Returns the duration which is billed for when work lasts for `time`.
+    pub fn billable_duration(&self, time: TimeDelta) -> TimeDelta {
+        let milliseconds = time.num_milliseconds().max(0);
+        let increment = i64::from(self.increment_seconds) * 1000;
+        let rounded = if increment == 0 {
+            milliseconds
+        } else {
+            let whole = milliseconds / increment;
+            let remainder = milliseconds % increment;
+            let round_up = match self.rounding {
+                DurationRounding::Up => remainder > 0,
+                DurationRounding::Down => false,
+                DurationRounding::Nearest => remainder * 2 >= increment,
+            };
+            (whole + i64::from(round_up)) * increment
+        };
+        TimeDelta::milliseconds(rounded.max(i64::from(self.minimum_seconds) * 1000))
+    }
+}
+impl Display for BillingPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut parts = Vec::new();
+        if self.increment_seconds != 0 {
+            let increment = format_seconds(self.increment_seconds);
+            parts.push(match self.rounding {
+                DurationRounding::Nearest => {
+                    format!("rounded to the nearest multiple of {increment}")
+                }
+                rounding => format!("rounded {rounding} to a multiple of {increment}"),
+            });
+        }
+        if self.minimum_seconds != 0 {
+            parts.push(format!("minimum {}", format_seconds(self.minimum_seconds)));
+        }
+        if parts.is_empty() {
+            write!(f, "billed exactly")
+        } else {
+            write!(f, "{}", parts.join(", "))
+        }
+    }
+}
+
+/// Formats a number of seconds as minutes if it's a whole number of minutes.
+fn format_seconds(seconds: u32) -> String {
+    let (amount, unit) = if seconds.is_multiple_of(60) {
+        (seconds / 60, "minute")
+    } else {
+        (seconds, "second")
+    };
+    if amount == 1 {
+        format!("{amount} {unit}")
+    } else {
+        format!("{amount} {unit}s")
     }
 }
 
@@ -135,19 +519,109 @@ pub enum Payment {
     /// Payment is a fixed amount of money per hour,
     /// with no rounding (so every second is paid for, even if it's less than one hour)
     Hourly(Money),
+    /// Payment is a fixed amount of money per hour,
+    /// for a duration which is rounded and given a minimum by a billing policy.
+    HourlyBilled(Money, BillingPolicy),
     /// Payment is a fixed amount of money,
     /// regardless of how long the work slice takes.
     Fixed(Money),
+    /// Payment is a fixed amount of money for every day (24 hours) of work which is started,
+    /// so any part of a day is paid for as a whole day.
+    Daily(Money),
+    /// Payment is a fixed amount of money per hour for the first `hours` hours,
+    /// then `overtime_amount` of the same currency's minor unit (e.g. pence) per hour after that.
+    Tiered {
+        rate: Money,
+        hours: u32,
+        overtime_amount: u32,
+    },
+    /// Payment is a fixed amount of money per hour, with no rounding,
+    /// but never more than `cap_amount` of the same currency's minor unit (e.g. pence) in total.
+    Capped { rate: Money, cap_amount: u32 },
 }
 impl Payment {
     /// Calculate how much this payment method would pay
     /// for a work slice with a given duration.
     pub fn calculate(&self, time: TimeDelta) -> MoneyExact {
         match *self {
-            Payment::Hourly(hourly) => MoneyExact(
-                hourly.as_pence() as f64 * time.num_milliseconds() as f64 / 1000.0 / (60.0 * 60.0),
-            ),
+            Payment::Hourly(hourly) => MoneyExact {
+                parts: u128::from(hourly.amount())
+                    * time.num_milliseconds().max(0) as u128
+                    * (PARTS_PER_MINOR_UNIT / MILLISECONDS_PER_HOUR),
+                currency: hourly.currency(),
+            },
+            Payment::HourlyBilled(hourly, policy) => {
+                Payment::Hourly(hourly).calculate(policy.billable_duration(time))
+            }
             Payment::Fixed(money) => money.into(),
+            Payment::Daily(daily) => {
+                let days = (time.num_milliseconds().max(0) as u128).div_ceil(MILLISECONDS_PER_DAY);
+                MoneyExact {
+                    parts: u128::from(daily.amount()) * days * PARTS_PER_MINOR_UNIT,
+                    currency: daily.currency(),
+                }
+            }
+            Payment::Tiered {
+                rate,
+                hours,
+                overtime_amount,
+            } => {
+                let milliseconds = time.num_milliseconds().max(0) as u128;
+                let standard = milliseconds.min(u128::from(hours) * MILLISECONDS_PER_HOUR);
+                let overtime = milliseconds - standard;
+                MoneyExact {
+                    parts: (u128::from(rate.amount()) * standard
+                        + u128::from(overtime_amount) * overtime)
+                        * (PARTS_PER_MINOR_UNIT / MILLISECONDS_PER_HOUR),
+                    currency: rate.currency(),
+                }
+            }
+            Payment::Capped { rate, cap_amount } => Payment::Hourly(rate)
+                .calculate(time)
+                .min(Money::new(cap_amount, rate.currency()).into()),
+        }
+    }
+
+    /// Calculates how much of the payment for a work slice lasting `total`
+    /// is earned by `part` of that time, where `includes_start` is true if the part
+    /// includes the start of the work slice.
+    /// `Hourly` payments are calculated for the part on its own.
+    /// `Fixed` payments aren't split: they're earned in full by the part which includes
+    /// the start of the work slice, and the rest of it earns nothing.
+    /// Every other payment is split in proportion to how much of `total` the part is,
+    /// so billing minimums, caps, overtime and whole days are only applied once per work slice.
+    pub fn calculate_part(
+        &self,
+        total: TimeDelta,
+        part: TimeDelta,
+        includes_start: bool,
+    ) -> MoneyExact {
+        let total_milliseconds = total.num_milliseconds().max(0) as u128;
+        let part_milliseconds = (part.num_milliseconds().max(0) as u128).min(total_milliseconds);
+        match *self {
+            Payment::Hourly(_) => self.calculate(part),
+            Payment::Fixed(money) if includes_start => money.into(),
+            Payment::Fixed(money) => MoneyExact::zero(money.currency()),
+            _ if part_milliseconds == total_milliseconds => self.calculate(total),
+            _ => {
+                let whole = self.calculate(total);
+                MoneyExact {
+                    parts: whole.parts * part_milliseconds / total_milliseconds,
+                    currency: whole.currency,
+                }
+            }
+        }
+    }
+
+    /// Returns the currency that this payment method pays in.
+    pub fn currency(&self) -> Currency {
+        match self {
+            Payment::Hourly(money)
+            | Payment::HourlyBilled(money, _)
+            | Payment::Fixed(money)
+            | Payment::Daily(money)
+            | Payment::Tiered { rate: money, .. }
+            | Payment::Capped { rate: money, .. } => money.currency(),
         }
     }
 }
@@ -158,7 +632,21 @@ impl Display for Payment {
             "{}",
             match self {
                 Self::Hourly(money) => format!("{money} / hour"),
+                Self::HourlyBilled(money, policy) => format!("{money} / hour ({policy})"),
                 Self::Fixed(money) => format!("fixed at {money}"),
+                Self::Daily(money) => format!("{money} / started day"),
+                Self::Tiered {
+                    rate,
+                    hours,
+                    overtime_amount,
+                } => format!(
+                    "{rate} / hour for {hours} hours, then {overtime} / hour",
+                    overtime = Money::new(*overtime_amount, rate.currency())
+                ),
+                Self::Capped { rate, cap_amount } => format!(
+                    "{rate} / hour, capped at {cap}",
+                    cap = Money::new(*cap_amount, rate.currency())
+                ),
             }
         )
     }
@@ -168,17 +656,23 @@ impl Display for Payment {
 mod tests {
     use chrono::TimeDelta;
 
-    use super::{Money, Payment};
+    use super::{
+        AddMoneyError, BillingPolicy, Currency, DurationRounding, Money, MoneyExact, MoneyTotals,
+        Payment, Rounding,
+    };
 
     #[test]
     fn money_format() {
         let tests = [
-            (Money::new(23), "£0.23"),
-            (Money::new(0), "£0.00"),
-            (Money::new(1), "£0.01"),
-            (Money::new(100), "£1.00"),
-            (Money::new(145), "£1.45"),
-            (Money::new(123456), "£1234.56"),
+            (Money::new(23, Currency::Gbp), "£0.23"),
+            (Money::new(0, Currency::Gbp), "£0.00"),
+            (Money::new(1, Currency::Gbp), "£0.01"),
+            (Money::new(100, Currency::Gbp), "£1.00"),
+            (Money::new(145, Currency::Gbp), "£1.45"),
+            (Money::new(123456, Currency::Gbp), "£1234.56"),
+            (Money::new(1005, Currency::Eur), "€10.05"),
+            (Money::new(99, Currency::Usd), "$0.99"),
+            (Money::new(1500, Currency::Jpy), "¥1500"),
         ];
         for (test, output) in tests {
             assert_eq!(test.to_string(), output);
@@ -189,72 +683,292 @@ mod tests {
     fn fixed_payment() {
         let tests = [
             (
-                Payment::Fixed(Money::new(8000)),
+                Payment::Fixed(Money::new(8000, Currency::Gbp)),
                 TimeDelta::new(10, 23).unwrap(),
-                8000.0,
+                8000,
             ),
             (
-                Payment::Fixed(Money::new(4500)),
+                Payment::Fixed(Money::new(4500, Currency::Gbp)),
                 TimeDelta::new(15, 28).unwrap(),
-                4500.0,
+                4500,
             ),
             (
-                Payment::Fixed(Money::new(23)),
+                Payment::Fixed(Money::new(23, Currency::Gbp)),
                 TimeDelta::new(13, 23).unwrap(),
-                23.0,
+                23,
             ),
             (
-                Payment::Fixed(Money::new(45)),
+                Payment::Fixed(Money::new(45, Currency::Gbp)),
                 TimeDelta::new(118, 23).unwrap(),
-                45.0,
+                45,
             ),
             (
-                Payment::Fixed(Money::new(0)),
+                Payment::Fixed(Money::new(0, Currency::Gbp)),
                 TimeDelta::new(12, 23).unwrap(),
-                0.0,
+                0,
             ),
             (
-                Payment::Fixed(Money::new(1)),
+                Payment::Fixed(Money::new(1, Currency::Gbp)),
                 TimeDelta::new(1121, 23).unwrap(),
-                1.0,
+                1,
             ),
             (
-                Payment::Fixed(Money::new(100)),
+                Payment::Fixed(Money::new(100, Currency::Gbp)),
                 TimeDelta::new(15, 23).unwrap(),
-                100.0,
+                100,
             ),
             (
-                Payment::Fixed(Money::new(245)),
+                Payment::Fixed(Money::new(245, Currency::Gbp)),
                 TimeDelta::new(16, 23).unwrap(),
-                245.0,
+                245,
             ),
             (
-                Payment::Fixed(Money::new(4563)),
+                Payment::Fixed(Money::new(4563, Currency::Gbp)),
                 TimeDelta::new(3273, 393).unwrap(),
-                4563.0,
+                4563,
             ),
         ];
         for (test, duration, output) in tests {
-            assert_eq!(test.calculate(duration).as_pence(), output);
+            assert_eq!(
+                test.calculate(duration),
+                MoneyExact::from(Money::new(output, Currency::Gbp))
+            );
         }
     }
 
     #[test]
     fn hourly_payment() {
         let tests = [
-            (3600, 2.0, 7200.0),
-            (1250, 5.5, 6875.0),
-            (1250, 5.25, 6562.50),
+            (3600, 2.0, 7200, 1),
+            (1250, 5.5, 6875, 1),
+            (1250, 5.25, 13125, 2),
+            (1, 0.1, 1, 10),
         ];
-        for (hourly, duration, total) in tests {
+        for (hourly, duration, numerator, denominator) in tests {
             assert_eq!(
-                Payment::Hourly(Money::new(hourly))
-                    .calculate(TimeDelta::seconds(
-                        (duration * 60.0f64 * 60.0).floor() as i64
-                    ))
-                    .as_pence(),
-                total
+                Payment::Hourly(Money::new(hourly, Currency::Gbp)).calculate(TimeDelta::seconds(
+                    (duration * 60.0f64 * 60.0).round() as i64
+                )),
+                MoneyExact::from_ratio(numerator, denominator, Currency::Gbp).unwrap()
             );
         }
     }
+
+    #[test]
+    fn daily_payment() {
+        let tests = [
+            (40000, TimeDelta::hours(24), 40000),
+            (40000, TimeDelta::hours(25), 80000),
+            (40000, TimeDelta::minutes(1), 40000),
+            (40000, TimeDelta::hours(72), 120000),
+            (40000, TimeDelta::zero(), 0),
+        ];
+        for (daily, duration, total) in tests {
+            assert_eq!(
+                Payment::Daily(Money::new(daily, Currency::Eur)).calculate(duration),
+                MoneyExact::from(Money::new(total, Currency::Eur))
+            );
+        }
+    }
+
+    #[test]
+    fn tiered_payment() {
+        let tests = [
+            (TimeDelta::hours(6), 12000),
+            (TimeDelta::hours(8), 16000),
+            (TimeDelta::hours(10), 22000),
+            (TimeDelta::minutes(8 * 60 + 30), 17500),
+        ];
+        for (duration, total) in tests {
+            let payment = Payment::Tiered {
+                rate: Money::new(2000, Currency::Gbp),
+                hours: 8,
+                overtime_amount: 3000,
+            };
+            assert_eq!(
+                payment.calculate(duration),
+                MoneyExact::from(Money::new(total, Currency::Gbp))
+            );
+        }
+    }
+
+    #[test]
+    fn capped_payment() {
+        let tests = [
+            (TimeDelta::hours(2), 5000),
+            (TimeDelta::hours(4), 10000),
+            (TimeDelta::hours(5), 10000),
+        ];
+        for (duration, total) in tests {
+            let payment = Payment::Capped {
+                rate: Money::new(2500, Currency::Usd),
+                cap_amount: 10000,
+            };
+            assert_eq!(
+                payment.calculate(duration),
+                MoneyExact::from(Money::new(total, Currency::Usd))
+            );
+        }
+    }
+
+    #[test]
+    fn payment_format() {
+        let tests = [
+            (
+                Payment::Daily(Money::new(40000, Currency::Gbp)),
+                "£400.00 / started day",
+            ),
+            (
+                Payment::Tiered {
+                    rate: Money::new(2000, Currency::Gbp),
+                    hours: 8,
+                    overtime_amount: 3000,
+                },
+                "£20.00 / hour for 8 hours, then £30.00 / hour",
+            ),
+            (
+                Payment::Capped {
+                    rate: Money::new(2500, Currency::Usd),
+                    cap_amount: 10000,
+                },
+                "$25.00 / hour, capped at $100.00",
+            ),
+        ];
+        for (payment, output) in tests {
+            assert_eq!(payment.to_string(), output);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn payment_serde_round_trip() {
+        let payments = [
+            Payment::Daily(Money::new(40000, Currency::Gbp)),
+            Payment::Tiered {
+                rate: Money::new(2000, Currency::Eur),
+                hours: 8,
+                overtime_amount: 3000,
+            },
+            Payment::Capped {
+                rate: Money::new(2500, Currency::Usd),
+                cap_amount: 10000,
+            },
+        ];
+        for payment in payments {
+            let json = serde_json::to_string(&payment).unwrap();
+            assert_eq!(serde_json::from_str::<Payment>(&json).unwrap(), payment);
+        }
+    }
+
+    #[test]
+    fn billed_hourly_payment() {
+        let policy = |increment_minutes: u32, rounding, minimum_minutes: u32| BillingPolicy {
+            increment_seconds: increment_minutes * 60,
+            rounding,
+            minimum_seconds: minimum_minutes * 60,
+        };
+        let tests = [
+            (policy(15, DurationRounding::Up, 0), 61, 75),
+            (policy(15, DurationRounding::Up, 0), 60, 60),
+            (policy(15, DurationRounding::Down, 0), 74, 60),
+            (policy(6, DurationRounding::Nearest, 0), 62, 60),
+            (policy(6, DurationRounding::Nearest, 0), 63, 66),
+            (policy(15, DurationRounding::Up, 30), 5, 30),
+            (policy(0, DurationRounding::Up, 30), 45, 45),
+        ];
+        for (policy, minutes, billed_minutes) in tests {
+            let duration = TimeDelta::minutes(minutes);
+            assert_eq!(
+                policy.billable_duration(duration),
+                TimeDelta::minutes(billed_minutes)
+            );
+            assert_eq!(
+                Payment::HourlyBilled(Money::new(6000, Currency::Gbp), policy).calculate(duration),
+                MoneyExact::from(Money::new(billed_minutes as u32 * 100, Currency::Gbp))
+            );
+        }
+    }
+
+    #[test]
+    fn exact_money_rounding() {
+        let tests = [
+            ((5, 2), Rounding::HalfUp, 3),
+            ((5, 2), Rounding::HalfEven, 2),
+            ((7, 2), Rounding::HalfEven, 4),
+            ((5, 2), Rounding::Truncate, 2),
+            ((26, 10), Rounding::HalfEven, 3),
+            ((29, 10), Rounding::Truncate, 2),
+            ((1, 10), Rounding::HalfUp, 0),
+        ];
+        for ((numerator, denominator), rounding, output) in tests {
+            let money = MoneyExact::from_ratio(numerator, denominator, Currency::Gbp).unwrap();
+            assert_eq!(
+                money.round(rounding),
+                Some(Money::new(output, Currency::Gbp))
+            );
+        }
+
+        let tenth = MoneyExact::from_ratio(1, 10, Currency::Gbp).unwrap();
+        let total = (0..10).fold(MoneyExact::zero(Currency::Gbp), |acc, _| {
+            acc.checked_add(tenth).unwrap()
+        });
+        assert_eq!(total, MoneyExact::from(Money::new(1, Currency::Gbp)));
+        assert_eq!(tenth.to_string(), "£0.00");
+        assert_eq!(format!("{tenth:.3}"), "£0.001");
+        assert_eq!(
+            MoneyExact::from_ratio(2469, 2, Currency::Gbp)
+                .unwrap()
+                .to_string(),
+            "£12.35"
+        );
+        assert_eq!(MoneyExact::from_ratio(1, 7, Currency::Gbp), None);
+    }
+
+    #[test]
+    fn billed_lines_are_rounded_before_adding_up() {
+        let half_penny = MoneyExact::from_ratio(1, 2, Currency::Gbp).unwrap();
+        assert_eq!(half_penny.billed(), Money::new(1, Currency::Gbp));
+        let totals: MoneyTotals = [half_penny; 3].iter().map(MoneyExact::billed).sum();
+        assert_eq!(totals.to_string(), "£0.03");
+
+        let most = MoneyExact::from(Money::new(u32::MAX, Currency::Gbp))
+            .checked_add(half_penny)
+            .unwrap();
+        assert_eq!(most.billed(), Money::new(u32::MAX, Currency::Gbp));
+    }
+
+    #[test]
+    fn currency_totals() {
+        let pounds = MoneyExact::from(Money::new(150, Currency::Gbp));
+        let euros = MoneyExact::from(Money::new(275, Currency::Eur));
+        assert!(matches!(
+            pounds.checked_add(euros),
+            Err(AddMoneyError::CurrencyMismatch(_))
+        ));
+        assert!(Money::new(1, Currency::Usd)
+            .checked_add(Money::new(1, Currency::Jpy))
+            .is_err());
+        assert_eq!(
+            Money::new(u32::MAX, Currency::Gbp).checked_add(Money::new(1, Currency::Gbp)),
+            Err(AddMoneyError::Overflow)
+        );
+        let most = MoneyExact {
+            parts: u128::MAX,
+            currency: Currency::Gbp,
+        };
+        assert_eq!(
+            most.checked_add(MoneyExact::from_ratio(1, 10, Currency::Gbp).unwrap()),
+            Err(AddMoneyError::Overflow)
+        );
+
+        let totals: MoneyTotals = [pounds, euros, pounds].into_iter().sum();
+        assert_eq!(
+            totals.get(Currency::Gbp),
+            pounds.checked_add(pounds).unwrap()
+        );
+        assert_eq!(totals.get(Currency::Eur), euros);
+        assert_eq!(totals.get(Currency::Usd), MoneyExact::zero(Currency::Usd));
+        assert_eq!(totals.to_string(), "£3.00 + €2.75");
+        assert_eq!(MoneyTotals::default().to_string(), "nothing");
+    }
 }
@@ -1,7 +1,11 @@
-use chrono::{DateTime, Utc};
+use std::collections::BTreeSet;
+
+use chrono::{DateTime, TimeDelta, Utc};
 
 use crate::{
-    payment::MoneyExact,
+    balance::{ReceivedPayment, ReceivedPaymentId},
+    client::ClientId,
+    payment::{MoneyTotals, Payment},
     work_slice::{CompleteWorkSlice, IncompleteWorkSlice, WorkSlice, WorkSliceId},
 };
 
@@ -11,10 +15,15 @@ use super::CompleteWorkError;
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct ProjectId(u64);
 impl ProjectId {
+    /// # Safety
+    /// The ID must refer to a project which exists (or existed) in a `State`,
+    /// otherwise it won't be found by any lookups.
     pub unsafe fn new(id: u64) -> Self {
         Self(id)
     }
 
+    /// # Safety
+    /// The raw ID is only meaningful to the `State` that created it.
     pub unsafe fn inner(&self) -> u64 {
         self.0
     }
@@ -28,6 +37,10 @@ pub struct Project {
     description: String,
     work_slices: Vec<CompleteWorkSlice>,
     current_slice: Option<IncompleteWorkSlice>,
+    default_payment: Option<Payment>,
+    archived: bool,
+    client: Option<ClientId>,
+    received_payments: Vec<ReceivedPayment>,
     id: ProjectId,
 }
 impl PartialEq for Project {
@@ -46,6 +59,33 @@ impl Project {
     pub fn id(&self) -> ProjectId {
         self.id
     }
+    /// Returns the payment used for new work when no payment is given, if there is one.
+    pub fn default_payment(&self) -> Option<Payment> {
+        self.default_payment
+    }
+    /// Returns true if the project has been archived, so no new work can be started for it.
+    pub fn is_archived(&self) -> bool {
+        self.archived
+    }
+    /// Returns the ID of the client which this project is for, if it has one.
+    pub fn client(&self) -> Option<ClientId> {
+        self.client
+    }
+    pub(super) fn set_name(&mut self, name: String) {
+        self.name = name;
+    }
+    pub(super) fn set_description(&mut self, description: String) {
+        self.description = description;
+    }
+    pub(super) fn set_default_payment(&mut self, default_payment: Option<Payment>) {
+        self.default_payment = default_payment;
+    }
+    pub(super) fn set_archived(&mut self, archived: bool) {
+        self.archived = archived;
+    }
+    pub(super) fn set_client(&mut self, client: Option<ClientId>) {
+        self.client = client;
+    }
 }
 impl Project {
     pub fn new(name: String, description: String, id: ProjectId) -> Self {
@@ -55,6 +95,10 @@ impl Project {
             id,
             work_slices: Vec::new(),
             current_slice: None,
+            default_payment: None,
+            archived: false,
+            client: None,
+            received_payments: Vec::new(),
         }
     }
 
@@ -71,6 +115,10 @@ impl Project {
             id,
             work_slices,
             current_slice,
+            default_payment: None,
+            archived: false,
+            client: None,
+            received_payments: Vec::new(),
         }
     }
 
@@ -85,16 +133,27 @@ impl Project {
         self.current_slice.as_ref()
     }
 
-    /// Returns the amount of money earned by the complete work slices in this project.
-    /// ignoring the current work slice if there is one.
-    pub fn total_payment(&self) -> MoneyExact {
+    pub(super) fn current_work_slice_mut(&mut self) -> Option<&mut IncompleteWorkSlice> {
+        self.current_slice.as_mut()
+    }
+
+    /// Returns the amount of money earned by the complete work slices in this project,
+    /// in each currency, ignoring the current work slice if there is one.
+    pub fn total_payment(&self) -> MoneyTotals {
         self.complete_work_slices()
             .map(|x| x.calculate_payment())
             .sum()
     }
 
+    /// Returns the time worked on the complete work slices in this project, not including breaks,
+    /// ignoring the current work slice if there is one.
+    pub fn total_duration(&self) -> TimeDelta {
+        self.complete_work_slices().map(|x| x.duration()).sum()
+    }
+
     /// Tries to set the given work slice to the current work slice of this project,
     /// but fails if there is already a current work slice.
+    #[allow(clippy::result_unit_err)]
     pub fn start_work(&mut self, current_work: IncompleteWorkSlice) -> Result<(), ()> {
         if self.current_slice.is_none() {
             self.current_slice = Some(current_work);
@@ -164,7 +223,180 @@ impl Project {
             .find(|x| x.id() == id)
         {
             Some(x) => Some(x),
-            None => self.current_slice.as_ref().map(WorkSlice::Incomplete),
+            None => self
+                .current_slice
+                .as_ref()
+                .filter(|x| x.id() == id)
+                .map(WorkSlice::Incomplete),
         }
     }
 }
+impl Project {
+    /// Removes a complete work slice from this project and returns it,
+    /// if it is in this project.
+    pub(super) fn take_complete_work_slice(
+        &mut self,
+        id: WorkSliceId,
+    ) -> Option<CompleteWorkSlice> {
+        let index = self.work_slices.iter().position(|x| x.id() == id)?;
+        Some(self.work_slices.remove(index))
+    }
+
+    /// Returns a mutable reference to a complete work slice,
+    /// if it is in this project.
+    pub(super) fn complete_work_slice_mut(
+        &mut self,
+        id: WorkSliceId,
+    ) -> Option<&mut CompleteWorkSlice> {
+        self.work_slices.iter_mut().find(|x| x.id() == id)
+    }
+
+    /// Replaces the note and tags of a work slice in this project, complete or not,
+    /// and returns the old ones, or `None` if the work slice isn't in this project.
+    pub(super) fn annotate_work_slice(
+        &mut self,
+        id: WorkSliceId,
+        note: Option<String>,
+        tags: BTreeSet<String>,
+    ) -> Option<(Option<String>, BTreeSet<String>)> {
+        if let Some(work_slice) = self.complete_work_slice_mut(id) {
+            return Some(work_slice.annotate(note, tags));
+        }
+        self.current_work_slice_mut()
+            .filter(|x| x.id() == id)
+            .map(|x| x.annotate(note, tags))
+    }
+
+    /// Adds a complete work slice to this project,
+    /// keeping the complete work slices ordered by their IDs.
+    pub(super) fn insert_complete_work_slice(&mut self, work_slice: CompleteWorkSlice) {
+        let index = self
+            .work_slices
+            .iter()
+            .position(|x| x.id() > work_slice.id())
+            .unwrap_or(self.work_slices.len());
+        self.work_slices.insert(index, work_slice);
+    }
+
+    /// Returns the payments received for this project, ordered by their IDs.
+    pub fn received_payments(&self) -> impl Iterator<Item = &ReceivedPayment> {
+        self.received_payments.iter()
+    }
+
+    /// Returns the payment with the given ID, if it was received for this project.
+    pub fn received_payment_from_id(&self, id: ReceivedPaymentId) -> Option<&ReceivedPayment> {
+        self.received_payments.iter().find(|x| x.id() == id)
+    }
+
+    /// Adds a received payment to this project, keeping the payments ordered by their IDs.
+    pub(super) fn insert_received_payment(&mut self, payment: ReceivedPayment) {
+        let index = self
+            .received_payments
+            .iter()
+            .position(|x| x.id() > payment.id())
+            .unwrap_or(self.received_payments.len());
+        self.received_payments.insert(index, payment);
+    }
+
+    /// Removes a received payment from this project and returns it,
+    /// if it was received for this project.
+    pub(super) fn take_received_payment(
+        &mut self,
+        id: ReceivedPaymentId,
+    ) -> Option<ReceivedPayment> {
+        let index = self.received_payments.iter().position(|x| x.id() == id)?;
+        Some(self.received_payments.remove(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use crate::{
+        AddWorkSliceError, Annotation, Currency, MemoryStorage, Money, Payment, ProjectId, State,
+        WorkStartError,
+    };
+
+    /// Returns a state with an active project and an archived project, in that order.
+    fn archived_project() -> (State, ProjectId, ProjectId) {
+        let payment = Payment::Hourly(Money::new(1000, Currency::Gbp));
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let active = state.new_project("active".to_string(), "".to_string(), Some(payment));
+        let archived = state.new_project("archived".to_string(), "".to_string(), Some(payment));
+        state.archive_project(archived).unwrap();
+        (state, active, archived)
+    }
+
+    #[test]
+    fn work_cannot_be_added_to_archived_projects() {
+        let now = Utc::now();
+        let (mut state, _, archived) = archived_project();
+        assert_eq!(
+            state.start_work(archived, None, now, Annotation::default()),
+            Err(WorkStartError::ProjectArchived)
+        );
+        assert_eq!(
+            state.add_complete_work_slice(
+                archived,
+                now - TimeDelta::hours(2),
+                now - TimeDelta::hours(1),
+                None,
+                Annotation::default(),
+            ),
+            Err(AddWorkSliceError::ProjectArchived)
+        );
+        let project = state.project_from_id(archived).unwrap();
+        assert!(project.current_work_slice().is_none());
+        assert_eq!(project.complete_work_slices().count(), 0);
+    }
+
+    #[test]
+    fn archived_projects_are_not_active() {
+        let (state, active, archived) = archived_project();
+        let ids: Vec<_> = state.active_projects().map(|x| x.id()).collect();
+        assert_eq!(ids, [active]);
+        let ids: Vec<_> = state.all_projects().map(|x| x.id()).collect();
+        assert_eq!(ids, [active, archived]);
+    }
+
+    #[test]
+    fn reopened_projects_can_be_worked_on() {
+        let (mut state, active, archived) = archived_project();
+        state.reopen_project(archived).unwrap();
+        assert!(!state.project_from_id(archived).unwrap().is_archived());
+        let ids: Vec<_> = state.active_projects().map(|x| x.id()).collect();
+        assert_eq!(ids, [active, archived]);
+        state
+            .start_work(archived, None, Utc::now(), Annotation::default())
+            .unwrap();
+        assert!(state
+            .project_from_id(archived)
+            .unwrap()
+            .current_work_slice()
+            .is_some());
+    }
+
+    #[test]
+    fn unchanged_project_updates_are_not_recorded() {
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let project = state.new_project("project".to_string(), "about".to_string(), None);
+        state.handle_changes();
+        state.update_project(project, None, None).unwrap();
+        state
+            .update_project(
+                project,
+                Some("project".to_string()),
+                Some("about".to_string()),
+            )
+            .unwrap();
+        assert_eq!(state.undo_history().len(), 1);
+        assert!(state.handle_changes().is_empty());
+        state
+            .rename_project(project, "renamed".to_string())
+            .unwrap();
+        assert_eq!(state.undo_history().len(), 2);
+        state.set_project_default_payment(project, None).unwrap();
+        assert_eq!(state.undo_history().len(), 2);
+    }
+}
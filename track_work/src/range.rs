@@ -0,0 +1,199 @@
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::{Break, MoneyExact, Project, ProjectId, State, WorkSlice, WorkSliceId};
+
+/// The part of a work slice which was worked between two times,
+/// found by `State::work_in_range` or `Project::work_in_range`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WorkInRange {
+    pub project_id: ProjectId,
+    pub work_slice_id: WorkSliceId,
+    /// When the work slice started, or the start of the range if it started before it.
+    pub start: DateTime<Utc>,
+    /// When the work slice ended, or the end of the range if it ended after it.
+    /// For ongoing work, "now" is used as the end of the work slice.
+    pub end: DateTime<Utc>,
+    /// The time worked between `start` and `end`, not including breaks.
+    pub duration: TimeDelta,
+    /// The payment earned between `start` and `end`. See `Payment::calculate_part`
+    /// for how the payment of a work slice which goes over the range is split.
+    pub payment: MoneyExact,
+    /// True if the work slice started before the range or ended after it,
+    /// so only part of it is included. Ongoing work counts as ending now,
+    /// so it's clipped if the range ends before now.
+    pub clipped: bool,
+    /// True if the work slice is still ongoing.
+    pub ongoing: bool,
+}
+
+impl Project {
+    /// Returns the work done in this project from `start` until `end`, ordered by when it started.
+    /// Work slices which go over either end of the range are clipped to it,
+    /// and ongoing work is included up to `Utc::now()`.
+    pub fn work_in_range(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<WorkInRange> {
+        let now = Utc::now();
+        self.complete_work_slices()
+            .map(WorkSlice::Complete)
+            .chain(self.current_work_slice().map(WorkSlice::Incomplete))
+            .filter_map(|work_slice| {
+                let ongoing = matches!(work_slice, WorkSlice::Incomplete(_));
+                let (work_end, current_break) = match work_slice {
+                    WorkSlice::Complete(x) => (x.completion(), None),
+                    WorkSlice::Incomplete(x) => (
+                        now,
+                        x.paused_at().map(|paused_at| Break {
+                            start: paused_at,
+                            end: now,
+                        }),
+                    ),
+                };
+                let clipped_start = work_slice.start().max(start);
+                let clipped_end = work_end.min(end);
+                if clipped_start >= clipped_end {
+                    return None;
+                }
+                let overlap = |from: DateTime<Utc>, to: DateTime<Utc>| {
+                    (to.min(clipped_end) - from.max(clipped_start)).max(TimeDelta::zero())
+                };
+                let duration = overlap(work_slice.start(), work_end)
+                    - work_slice
+                        .breaks()
+                        .iter()
+                        .chain(current_break.as_ref())
+                        .map(|x| overlap(x.start, x.end))
+                        .sum::<TimeDelta>();
+                let includes_start = work_slice.start() >= start;
+                Some(WorkInRange {
+                    project_id: self.id(),
+                    work_slice_id: work_slice.id(),
+                    start: clipped_start,
+                    end: clipped_end,
+                    duration,
+                    payment: work_slice.payment_rate().calculate_part(
+                        work_slice.duration(),
+                        duration,
+                        includes_start,
+                    ),
+                    clipped: !includes_start || work_end > end,
+                    ongoing,
+                })
+            })
+            .collect()
+    }
+}
+
+impl State {
+    /// Returns the work done in all projects from `start` until `end`, ordered by when it started.
+    /// Work slices which go over either end of the range are clipped to it,
+    /// and ongoing work is included up to `Utc::now()`.
+    pub fn work_in_range(&self, start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<WorkInRange> {
+        let mut work: Vec<_> = self
+            .all_projects()
+            .flat_map(|x| x.work_in_range(start, end))
+            .collect();
+        work.sort_by_key(|x| x.start);
+        work
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use crate::{Annotation, Currency, MemoryStorage, Money, MoneyExact, Payment, State};
+
+    #[test]
+    fn work_is_clipped_to_range() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let gbp = |amount| MoneyExact::from(Money::new(amount, Currency::Gbp));
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let hourly = state.new_project("hourly".to_string(), "".to_string(), None);
+        let fixed = state.new_project("fixed".to_string(), "".to_string(), None);
+        let capped = state.new_project("capped".to_string(), "".to_string(), None);
+        state
+            .add_complete_work_slice(
+                hourly,
+                now - hours(10),
+                now - hours(6),
+                Some(Payment::Hourly(Money::new(1000, Currency::Gbp))),
+                Annotation::default(),
+            )
+            .unwrap();
+        state
+            .add_complete_work_slice(
+                fixed,
+                now - hours(9),
+                now - hours(7),
+                Some(Payment::Fixed(Money::new(5000, Currency::Gbp))),
+                Annotation::default(),
+            )
+            .unwrap();
+        state
+            .add_complete_work_slice(
+                fixed,
+                now - hours(5),
+                now - hours(3),
+                Some(Payment::Fixed(Money::new(5000, Currency::Gbp))),
+                Annotation::default(),
+            )
+            .unwrap();
+        state
+            .add_complete_work_slice(
+                capped,
+                now - hours(12),
+                now - hours(8),
+                Some(Payment::Capped {
+                    rate: Money::new(1000, Currency::Gbp),
+                    cap_amount: 2000,
+                }),
+                Annotation::default(),
+            )
+            .unwrap();
+        state
+            .start_work(
+                hourly,
+                Some(Payment::Hourly(Money::new(1000, Currency::Gbp))),
+                now - hours(2),
+                Annotation::default(),
+            )
+            .unwrap();
+        state.pause_work(hourly, now - hours(1)).unwrap();
+
+        // the capped work slice ends at the start of the range, so it isn't included
+        assert_eq!(state.work_in_range(now - hours(8), now - hours(4)).len(), 3);
+        let work = state.work_in_range(now - hours(9), now - hours(4));
+        assert_eq!(work.len(), 4);
+        assert_eq!(work[0].start, now - hours(9));
+        assert_eq!(work[0].project_id, hourly);
+        assert_eq!(work[0].duration, hours(3));
+        assert_eq!(work[0].payment, gbp(3000));
+        assert!(work[0].clipped);
+        // fixed work slices are paid in full by the range they start in
+        assert_eq!(work[1].payment, gbp(5000));
+        assert!(!work[1].clipped);
+        // the capped work slice is paid a quarter of its capped total, as a quarter of it is in the range
+        assert_eq!(work[2].start, now - hours(9));
+        assert_eq!(work[2].payment, gbp(500));
+        assert_eq!(work[3].start, now - hours(5));
+        assert_eq!(work[3].end, now - hours(4));
+        assert_eq!(work[3].payment, gbp(5000));
+
+        let work = state.work_in_range(now - hours(4), now + hours(1));
+        assert_eq!(work.len(), 2);
+        // so the rest of this fixed work slice isn't paid
+        assert_eq!(work[0].payment, MoneyExact::zero(Currency::Gbp));
+        assert!(work[1].ongoing);
+        assert!(!work[1].clipped);
+        // the ongoing work slice has been paused for the last hour
+        assert_eq!(work[1].duration, hours(1));
+        assert_eq!(work[1].payment, gbp(1000));
+
+        // ongoing work is clipped by a range which ends before now
+        let work = state.work_in_range(now - hours(3), now - TimeDelta::minutes(90));
+        assert_eq!(work.len(), 1);
+        assert!(work[0].ongoing);
+        assert!(work[0].clipped);
+        assert_eq!(work[0].duration, TimeDelta::minutes(30));
+    }
+}
@@ -0,0 +1,703 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{DateTime, TimeDelta, Utc};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    report::first_work_start, Client, Money, MoneyTotals, Payment, ProjectId, ReportError,
+    ReportFilter, State, TemplateError, UnknownRenderFormatError, WorkSliceId,
+};
+
+/// The kind of document which a `Statement` is rendered to.
+/// Values are escaped for the format, so they can't change the document's layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RenderFormat {
+    #[default]
+    Markdown,
+    /// A self-contained HTML page, with its styles inline.
+    Html,
+}
+impl RenderFormat {
+    /// Returns the text of the template which is used when no other template is given.
+    pub fn default_template(&self) -> &'static str {
+        match self {
+            RenderFormat::Markdown => include_str!("templates/statement.md"),
+            RenderFormat::Html => include_str!("templates/statement.html"),
+        }
+    }
+
+    /// Escapes a value so that it's shown as it is in this format.
+    /// Line breaks in Markdown become `<br>`, so values can be used in tables.
+    fn escape(&self, value: &str) -> String {
+        let mut escaped = String::with_capacity(value.len());
+        for c in value.chars() {
+            match (self, c) {
+                (RenderFormat::Markdown, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|') => {
+                    escaped.push('\\');
+                    escaped.push(c);
+                }
+                (RenderFormat::Markdown, '\n') => escaped.push_str("<br>"),
+                (RenderFormat::Markdown, '\r') => {}
+                (RenderFormat::Html, '&') => escaped.push_str("&amp;"),
+                (RenderFormat::Html, '<') => escaped.push_str("&lt;"),
+                (RenderFormat::Html, '>') => escaped.push_str("&gt;"),
+                (RenderFormat::Html, '"') => escaped.push_str("&quot;"),
+                (RenderFormat::Html, '\'') => escaped.push_str("&#39;"),
+                _ => escaped.push(c),
+            }
+        }
+        escaped
+    }
+}
+impl Display for RenderFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                RenderFormat::Markdown => "markdown",
+                RenderFormat::Html => "html",
+            }
+        )
+    }
+}
+impl FromStr for RenderFormat {
+    type Err = UnknownRenderFormatError;
+
+    /// Parses "markdown" or "md", or "html" or "htm".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(RenderFormat::Markdown),
+            "html" | "htm" => Ok(RenderFormat::Html),
+            _ => Err(UnknownRenderFormatError),
+        }
+    }
+}
+
+/// A single line item of a `Statement`: the work done in one work slice.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatementLine {
+    pub project_id: ProjectId,
+    pub project_name: String,
+    pub work_slice_id: WorkSliceId,
+    /// When the work started, or the start of the statement if it started before it.
+    pub date: DateTime<Utc>,
+    /// The time worked within the statement, not including breaks.
+    pub duration: TimeDelta,
+    pub rate: Payment,
+    /// The payment earned within the statement, rounded with `MoneyExact::billed`. See `Payment::calculate_part`
+    /// for how the payment of a work slice which goes over the statement's period is split.
+    pub amount: Money,
+    pub note: Option<String>,
+}
+
+/// The complete work for a project, a client or an invoice, itemised so it can be sent to be paid.
+/// Made by `State::statement` or `State::invoice_statement`, and turned into a document by `render`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Statement {
+    /// "Invoice" and its number for invoices, or the name of the project or client.
+    pub title: String,
+    pub invoice_number: Option<u64>,
+    /// The name of the client who is billed, if there is one.
+    pub client_name: Option<String>,
+    pub billing_address: Option<String>,
+    pub issued: DateTime<Utc>,
+    /// When the statement has to be paid by. Only invoices have a due date.
+    pub due: Option<DateTime<Utc>>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// The line items, ordered by date.
+    pub lines: Vec<StatementLine>,
+    pub total_duration: TimeDelta,
+    /// The sum of the lines' amounts.
+    pub total: MoneyTotals,
+}
+impl Statement {
+    /// Writes this statement into a template, escaping every value for the format.
+    pub fn render(&self, template: &Template, format: RenderFormat) -> String {
+        let mut output = String::new();
+        self.render_nodes(&template.nodes, None, format, &mut output);
+        output
+    }
+
+    fn render_nodes(
+        &self,
+        nodes: &[Node],
+        line: Option<&StatementLine>,
+        format: RenderFormat,
+        output: &mut String,
+    ) {
+        for node in nodes {
+            match node {
+                Node::Text(text) => output.push_str(text),
+                Node::Field(name) => output.push_str(&format.escape(&self.field(name, line))),
+                Node::Section {
+                    name,
+                    inverted,
+                    nodes,
+                } if name == "lines" => {
+                    if *inverted {
+                        if self.lines.is_empty() {
+                            self.render_nodes(nodes, line, format, output);
+                        }
+                    } else {
+                        for line in &self.lines {
+                            self.render_nodes(nodes, Some(line), format, output);
+                        }
+                    }
+                }
+                Node::Section {
+                    name,
+                    inverted,
+                    nodes,
+                } => {
+                    if self.field(name, line).is_empty() == *inverted {
+                        self.render_nodes(nodes, line, format, output);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the value of a field, as it's written in a rendered statement,
+    /// or an empty string if the field doesn't have a value.
+    /// The line's fields are used inside a `lines` section.
+    fn field(&self, name: &str, line: Option<&StatementLine>) -> String {
+        let date = |x: DateTime<Utc>| x.format("%Y-%m-%d").to_string();
+        if let Some(line) = line {
+            match name {
+                "date" => return date(line.date),
+                "project" => return line.project_name.clone(),
+                "work_slice" => return unsafe { line.work_slice_id.inner() }.to_string(),
+                "note" => return line.note.clone().unwrap_or_default(),
+                "duration" => return format_hours(line.duration),
+                "rate" => return line.rate.to_string(),
+                "amount" => return line.amount.to_string(),
+                _ => {}
+            }
+        }
+        match name {
+            "title" => self.title.clone(),
+            "invoice_number" => self
+                .invoice_number
+                .map(|x| x.to_string())
+                .unwrap_or_default(),
+            "client" => self.client_name.clone().unwrap_or_default(),
+            "billing_address" => self.billing_address.clone().unwrap_or_default(),
+            "issued" => date(self.issued),
+            "due" => self.due.map(date).unwrap_or_default(),
+            "start" => date(self.start),
+            "end" => date(self.end),
+            "total_duration" => format_hours(self.total_duration),
+            "total" => self.total.to_string(),
+            // templates are checked for unknown fields when they're parsed
+            _ => String::new(),
+        }
+    }
+
+    fn with_totals(mut self) -> Self {
+        self.total_duration = self.lines.iter().map(|x| x.duration).sum();
+        self.total = self.lines.iter().map(|x| x.amount).sum();
+        self
+    }
+}
+
+/// Writes a duration as hours and minutes, like "3h 05m".
+fn format_hours(duration: TimeDelta) -> String {
+    let minutes = duration.num_minutes();
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+/// The fields which can be used anywhere in a template.
+const STATEMENT_FIELDS: [&str; 10] = [
+    "title",
+    "invoice_number",
+    "client",
+    "billing_address",
+    "issued",
+    "due",
+    "start",
+    "end",
+    "total_duration",
+    "total",
+];
+/// The fields of a line item, which can only be used inside a `lines` section.
+const LINE_FIELDS: [&str; 7] = [
+    "date",
+    "project",
+    "work_slice",
+    "note",
+    "duration",
+    "rate",
+    "amount",
+];
+
+/// The layout of a rendered `Statement`, parsed from text with `{{...}}` tags:
+/// - `{{name}}` is replaced with the value of a field, like `{{title}}` or `{{total}}`.
+/// - `{{#lines}}...{{/lines}}` is repeated for each line item,
+///   and the line's fields (like `{{date}}` and `{{amount}}`) can be used inside it.
+/// - `{{#name}}...{{/name}}` is only included if the field has a value, like `{{#due}}`,
+///   and `{{^name}}...{{/name}}` is only included if it doesn't (or if there are no line items).
+/// - `{{! ...}}` is a comment, which isn't included.
+///
+/// Lines which only have a section tag or a comment on them are left out completely.
+/// See `RenderFormat::default_template` for examples.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+impl Template {
+    /// Returns the template which is used when no other template is given.
+    pub fn default_for(format: RenderFormat) -> Self {
+        format
+            .default_template()
+            .parse()
+            .expect("The default templates are valid")
+    }
+}
+impl FromStr for Template {
+    type Err = TemplateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        struct OpenSection {
+            name: String,
+            inverted: bool,
+            line: usize,
+            nodes: Vec<Node>,
+        }
+        let mut nodes = Vec::new();
+        let mut open: Vec<OpenSection> = Vec::new();
+        fn current<'a>(nodes: &'a mut Vec<Node>, open: &'a mut [OpenSection]) -> &'a mut Vec<Node> {
+            match open.last_mut() {
+                Some(section) => &mut section.nodes,
+                None => nodes,
+            }
+        }
+
+        for (i, text) in s.split_inclusive('\n').enumerate() {
+            let line = i + 1;
+            let trimmed = text.trim();
+            let standalone = trimmed.starts_with("{{")
+                && trimmed.ends_with("}}")
+                && trimmed.matches("{{").count() == 1
+                && trimmed[2..].trim_start().starts_with(['#', '^', '/', '!']);
+            let mut rest = if standalone { trimmed } else { text };
+            while let Some(start) = rest.find("{{") {
+                push_text(current(&mut nodes, &mut open), &rest[..start]);
+                let after = &rest[start + 2..];
+                let end = after
+                    .find("}}")
+                    .ok_or(TemplateError::UnclosedTag { line })?;
+                let tag = after[..end].trim();
+                rest = &after[end + 2..];
+                if tag.starts_with('!') {
+                    continue;
+                }
+                let name = tag.trim_start_matches(['#', '^', '/']).trim().to_string();
+                let in_lines = open.iter().any(|x| x.name == "lines" && !x.inverted);
+                match tag.chars().next() {
+                    Some('/') => {
+                        let section = open
+                            .pop()
+                            .filter(|x| x.name == name)
+                            .ok_or(TemplateError::UnexpectedClose { line, name })?;
+                        current(&mut nodes, &mut open).push(Node::Section {
+                            name: section.name,
+                            inverted: section.inverted,
+                            nodes: section.nodes,
+                        });
+                    }
+                    Some(kind @ ('#' | '^')) => {
+                        let known = STATEMENT_FIELDS.contains(&name.as_str())
+                            || (name == "lines" && !in_lines)
+                            || (in_lines && LINE_FIELDS.contains(&name.as_str()));
+                        if !known {
+                            return Err(TemplateError::UnknownField { line, name });
+                        }
+                        open.push(OpenSection {
+                            name,
+                            inverted: kind == '^',
+                            line,
+                            nodes: Vec::new(),
+                        });
+                    }
+                    _ => {
+                        let known = STATEMENT_FIELDS.contains(&name.as_str())
+                            || (in_lines && LINE_FIELDS.contains(&name.as_str()));
+                        if !known {
+                            return Err(TemplateError::UnknownField { line, name });
+                        }
+                        current(&mut nodes, &mut open).push(Node::Field(name));
+                    }
+                }
+            }
+            push_text(current(&mut nodes, &mut open), rest);
+        }
+        match open.pop() {
+            Some(section) => Err(TemplateError::UnclosedSection {
+                line: section.line,
+                name: section.name,
+            }),
+            None => Ok(Self { nodes }),
+        }
+    }
+}
+
+/// A part of a parsed `Template`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Text(String),
+    Field(String),
+    Section {
+        name: String,
+        inverted: bool,
+        nodes: Vec<Node>,
+    },
+}
+
+/// Adds text to the end of some nodes, joining it onto the last node if that's text too.
+fn push_text(nodes: &mut Vec<Node>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    match nodes.last_mut() {
+        Some(Node::Text(last)) => last.push_str(text),
+        _ => nodes.push(Node::Text(text.to_string())),
+    }
+}
+
+impl State {
+    /// Itemises the complete work from `start` until `end`, for the projects chosen by `filter`.
+    /// Work slices which go over either end are clipped in the same way as `State::work_in_range`,
+    /// and ongoing work is left out. If `start` is `None`, the statement starts when the earliest
+    /// of those projects' work started, and if `end` is `None`, it ends now.
+    /// Fails if the project or client in the filter doesn't exist.
+    pub fn statement(
+        &self,
+        filter: ReportFilter,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<Statement, ReportError> {
+        let projects = self.filtered_projects(filter)?;
+        let end = end.unwrap_or_else(Utc::now);
+        let start = start.unwrap_or_else(|| first_work_start(&projects).unwrap_or(end).min(end));
+        let mut lines: Vec<_> = projects
+            .iter()
+            .flat_map(|project| {
+                project
+                    .work_in_range(start, end)
+                    .into_iter()
+                    .filter(|x| !x.ongoing)
+                    .map(|work| {
+                        let work_slice = project
+                            .work_slice_from_id(work.work_slice_id)
+                            .expect("The work is in this project");
+                        StatementLine {
+                            project_id: project.id(),
+                            project_name: project.name().to_string(),
+                            work_slice_id: work.work_slice_id,
+                            date: work.start,
+                            duration: work.duration,
+                            rate: work_slice.payment_rate(),
+                            amount: work.payment.billed(),
+                            note: work_slice.note().map(str::to_string),
+                        }
+                    })
+            })
+            .collect();
+        lines.sort_by_key(|x| x.date);
+
+        let (title, client) = match filter {
+            ReportFilter::All => ("All projects".to_string(), None),
+            ReportFilter::Project(_) => (
+                projects[0].name().to_string(),
+                self.client_of_project(projects[0]),
+            ),
+            ReportFilter::Client(id) => {
+                let client = self.client_from_id(id);
+                (
+                    client.map(Client::name).unwrap_or_default().to_string(),
+                    client,
+                )
+            }
+        };
+        Ok(Statement {
+            title,
+            invoice_number: None,
+            client_name: client.map(|x| x.name().to_string()),
+            billing_address: client.map(|x| x.billing_address().to_string()),
+            issued: Utc::now(),
+            due: None,
+            start,
+            end,
+            lines,
+            total_duration: TimeDelta::zero(),
+            total: MoneyTotals::default(),
+        }
+        .with_totals())
+    }
+
+    /// Itemises the work slices on an invoice, with its number and dates.
+    /// The statement starts when the earliest work started, and ends when the latest work ended.
+    /// Returns `None` if there isn't an invoice with that number.
+    pub fn invoice_statement(&self, number: u64) -> Option<Statement> {
+        let invoice = self.invoice_from_number(number)?;
+        let mut lines = Vec::new();
+        let mut end = None;
+        for (&id, &amount) in invoice.work_slices().iter().zip(invoice.amounts()) {
+            let project = self
+                .project_id_from_work_slice(id)
+                .and_then(|x| self.project_from_id(x))?;
+            let work_slice = project.work_slice_from_id(id)?.complete()?;
+            end = end.max(Some(work_slice.completion()));
+            lines.push(StatementLine {
+                project_id: project.id(),
+                project_name: project.name().to_string(),
+                work_slice_id: id,
+                date: work_slice.start(),
+                duration: work_slice.duration(),
+                rate: work_slice.payment(),
+                amount,
+                note: work_slice.note().map(str::to_string),
+            });
+        }
+        lines.sort_by_key(|x| x.date);
+        let client = invoice.client().and_then(|x| self.client_from_id(x));
+        Some(
+            Statement {
+                title: format!("Invoice {number}"),
+                invoice_number: Some(number),
+                client_name: client.map(|x| x.name().to_string()),
+                billing_address: client.map(|x| x.billing_address().to_string()),
+                issued: invoice.issued(),
+                due: Some(invoice.due()),
+                start: lines.first().map_or(invoice.issued(), |x| x.date),
+                end: end.unwrap_or(invoice.issued()),
+                lines,
+                total_duration: TimeDelta::zero(),
+                total: MoneyTotals::default(),
+            }
+            .with_totals(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeDelta, Utc};
+
+    use crate::{
+        Annotation, ClientId, Currency, MemoryStorage, Money, Payment, ProjectId, RenderFormat,
+        ReportFilter, State, Statement, Template, TemplateError, WorkSliceId,
+    };
+
+    fn time(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    /// Returns a state with a client, their project, and work on the project in March and April 2024.
+    /// The work in March has a note which needs escaping.
+    fn client_work() -> (State, ClientId, ProjectId, WorkSliceId) {
+        let payment = Some(Payment::Hourly(Money::new(1000, Currency::Gbp)));
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let client = state.new_client(
+            "Client & Co".to_string(),
+            "".to_string(),
+            "1 Street\nTown".to_string(),
+            None,
+        );
+        let project = state.new_project("site".to_string(), "".to_string(), None);
+        state.set_project_client(project, Some(client)).unwrap();
+        let first = state
+            .add_complete_work_slice(
+                project,
+                time("2024-03-01T09:00:00Z"),
+                time("2024-03-01T12:30:00Z"),
+                payment,
+                Annotation::default(),
+            )
+            .unwrap();
+        state
+            .annotate_work_slice(first, Some("fix *all* the <bugs>".to_string()), [].into())
+            .unwrap();
+        state
+            .add_complete_work_slice(
+                project,
+                time("2024-04-02T09:00:00Z"),
+                time("2024-04-02T10:00:00Z"),
+                payment,
+                Annotation::default(),
+            )
+            .unwrap();
+        (state, client, project, first)
+    }
+
+    /// A template which lists each line's work slice, amount and note, and the total.
+    fn list_template() -> Template {
+        "{{title}}\n{{#lines}}\n- {{work_slice}}: {{amount}}{{#note}} ({{note}}){{/note}}\n{{/lines}}\n{{^lines}}\nNothing to bill\n{{/lines}}\n{{! totals }}\nTotal: {{total}}\n"
+            .parse()
+            .unwrap()
+    }
+
+    fn march_statement(state: &State, client: ClientId) -> Statement {
+        state
+            .statement(
+                ReportFilter::Client(client),
+                Some(time("2024-03-01T00:00:00Z")),
+                Some(time("2024-04-01T00:00:00Z")),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn statements_only_include_work_in_their_period() {
+        let (state, client, _, _) = client_work();
+        let statement = march_statement(&state, client);
+        assert_eq!(statement.title, "Client & Co");
+        assert_eq!(statement.lines.len(), 1);
+        assert_eq!(statement.total_duration, TimeDelta::minutes(210));
+    }
+
+    #[test]
+    fn default_markdown_template_escapes_values() {
+        let (state, client, _, _) = client_work();
+        let markdown = march_statement(&state, client).render(
+            &Template::default_for(RenderFormat::Markdown),
+            RenderFormat::Markdown,
+        );
+        assert!(markdown.contains(
+            "| 2024-03-01 | site | fix \\*all\\* the \\<bugs\\> | 3h 30m | £10.00 / hour | £35.00 |\n"
+        ));
+        assert!(markdown.contains("Client & Co<br>1 Street<br>Town"));
+        assert!(!markdown.contains("Due"));
+    }
+
+    #[test]
+    fn default_html_template_escapes_values() {
+        let (state, client, _, _) = client_work();
+        let html = march_statement(&state, client).render(
+            &Template::default_for(RenderFormat::Html),
+            RenderFormat::Html,
+        );
+        assert!(html.contains("<td>fix *all* the &lt;bugs&gt;</td>"));
+        assert!(!html.contains("{{"));
+    }
+
+    #[test]
+    fn invoice_statements_render_the_invoiced_work() {
+        let (mut state, client, _, first) = client_work();
+        state
+            .create_invoice(
+                Some(client),
+                vec![first],
+                time("2024-04-05T00:00:00Z"),
+                time("2024-05-05T00:00:00Z"),
+            )
+            .unwrap();
+        let statement = state.invoice_statement(1).unwrap();
+        assert_eq!(statement.due, Some(time("2024-05-05T00:00:00Z")));
+        assert_eq!(statement.end, time("2024-03-01T12:30:00Z"));
+        assert_eq!(
+            statement.render(&list_template(), RenderFormat::Markdown),
+            "Invoice 1\n- 1: £35.00 (fix \\*all\\* the \\<bugs\\>)\nTotal: £35.00\n"
+        );
+    }
+
+    #[test]
+    fn statements_without_work_use_inverted_lines_section() {
+        let (state, _, project, _) = client_work();
+        let statement = state
+            .statement(
+                ReportFilter::Project(project),
+                Some(time("2025-01-01T00:00:00Z")),
+                Some(time("2025-02-01T00:00:00Z")),
+            )
+            .unwrap();
+        assert_eq!(
+            statement.render(&list_template(), RenderFormat::Markdown),
+            "site\nNothing to bill\nTotal: nothing\n"
+        );
+    }
+
+    #[test]
+    fn invalid_templates_are_rejected() {
+        assert_eq!(
+            "{{title}}\n{{amount}}".parse::<Template>(),
+            Err(TemplateError::UnknownField {
+                line: 2,
+                name: "amount".to_string()
+            })
+        );
+        assert_eq!(
+            "{{#lines}}\n{{date}}\n".parse::<Template>(),
+            Err(TemplateError::UnclosedSection {
+                line: 1,
+                name: "lines".to_string()
+            })
+        );
+        assert_eq!(
+            "{{#due}}{{/lines}}".parse::<Template>(),
+            Err(TemplateError::UnexpectedClose {
+                line: 1,
+                name: "lines".to_string()
+            })
+        );
+        assert_eq!(
+            "{{title".parse::<Template>(),
+            Err(TemplateError::UnclosedTag { line: 1 })
+        );
+    }
+
+    #[test]
+    fn line_amounts_add_up_to_total() {
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        // half an hour at 1p an hour earns half a penny
+        let payment = Some(Payment::Hourly(Money::new(1, Currency::Gbp)));
+        let project = state.new_project("project".to_string(), "".to_string(), payment);
+        let mut work_slices = Vec::new();
+        for day in 1..=3 {
+            let start = time(&format!("2024-03-0{day}T09:00:00Z"));
+            work_slices.push(
+                state
+                    .add_complete_work_slice(
+                        project,
+                        start,
+                        start + TimeDelta::minutes(30),
+                        None,
+                        Annotation::default(),
+                    )
+                    .unwrap(),
+            );
+        }
+        let template: Template = "{{#lines}}\n{{amount}}\n{{/lines}}\n{{total}}\n"
+            .parse()
+            .unwrap();
+
+        let statement = state
+            .statement(ReportFilter::Project(project), None, None)
+            .unwrap();
+        assert_eq!(
+            statement.render(&template, RenderFormat::Markdown),
+            "£0.01\n£0.01\n£0.01\n£0.03\n"
+        );
+        let number = state
+            .create_invoice(
+                None,
+                work_slices,
+                time("2024-04-01T00:00:00Z"),
+                time("2024-05-01T00:00:00Z"),
+            )
+            .unwrap();
+        let statement = state.invoice_statement(number).unwrap();
+        assert_eq!(
+            statement.render(&template, RenderFormat::Markdown),
+            "£0.01\n£0.01\n£0.01\n£0.03\n"
+        );
+        assert_eq!(
+            &statement.total,
+            state.invoice_from_number(number).unwrap().total()
+        );
+    }
+}
@@ -0,0 +1,642 @@
+use crate::{
+    Change, ClientId, DataToProjectError, IncompleteWorkSlice, IncompleteWorkSliceData,
+    InvalidChangeError, Project, ProjectData, ProjectId, ReceivedPaymentId, State, StateData,
+    StateReplayError, Storage, TrashEntry, TrashedItem, WorkPauseError, WorkSliceData, WorkSliceId,
+};
+
+impl State {
+    /// Rebuilds a State by replaying a log of changes, starting with no projects.
+    /// Nothing is loaded from the storage, and the changes are treated as already saved,
+    /// so they won't be returned by `handle_changes` or saved to the storage.
+    pub fn from_changes(
+        changes: Vec<Change>,
+        storage: impl Storage + 'static,
+    ) -> Result<Self, StateReplayError> {
+        Self::from_snapshot_and_changes(StateData::default(), changes, storage)
+    }
+
+    /// Rebuilds a State from a snapshot of its projects and trash,
+    /// followed by the changes which were made after the snapshot was taken.
+    /// Nothing is loaded from the storage, and the changes are treated as already saved,
+    /// so they won't be returned by `handle_changes` or saved to the storage.
+    pub fn from_snapshot_and_changes(
+        initial_data: StateData,
+        changes: Vec<Change>,
+        storage: impl Storage + 'static,
+    ) -> Result<Self, StateReplayError> {
+        let mut state =
+            Self::from_data(initial_data, Box::new(storage)).map_err(StateReplayError::Init)?;
+        for (index, change) in changes.into_iter().enumerate() {
+            state
+                .apply_change(change)
+                .map_err(|error| StateReplayError::InvalidChange { index, error })?;
+        }
+        Ok(state)
+    }
+
+    /// Applies a single change to this state, without recording it as a new change.
+    pub(crate) fn apply_change(&mut self, change: Change) -> Result<(), InvalidChangeError> {
+        match change {
+            Change::ProjectCreated {
+                name,
+                description,
+                default_payment,
+                id,
+            } => {
+                let id = unsafe { ProjectId::new(id) };
+                if self.project_exists(id) {
+                    return Err(InvalidChangeError::DuplicateProjectId);
+                }
+                let mut project = Project::new(name, description, id);
+                project.set_default_payment(default_payment);
+                self.projects.push(project);
+                self.previous_project_id = self.previous_project_id.max(unsafe { id.inner() });
+                Ok(())
+            }
+            Change::ProjectUpdated {
+                id,
+                name,
+                description,
+                ..
+            } => {
+                let project = self
+                    .project_from_id_mut(unsafe { ProjectId::new(id) })
+                    .ok_or(InvalidChangeError::ProjectNotFound)?;
+                project.set_name(name);
+                project.set_description(description);
+                Ok(())
+            }
+            Change::ProjectDefaultPaymentChanged { id, payment, .. } => {
+                self.project_from_id_mut(unsafe { ProjectId::new(id) })
+                    .ok_or(InvalidChangeError::ProjectNotFound)?
+                    .set_default_payment(payment);
+                Ok(())
+            }
+            Change::ProjectClientChanged { id, client, .. } => {
+                let client = client.map(|x| unsafe { ClientId::new(x) });
+                if client.is_some_and(|x| self.client_from_id(x).is_none()) {
+                    return Err(InvalidChangeError::ClientNotFound);
+                }
+                self.project_from_id_mut(unsafe { ProjectId::new(id) })
+                    .ok_or(InvalidChangeError::ProjectNotFound)?
+                    .set_client(client);
+                Ok(())
+            }
+            Change::ClientCreated { client } => {
+                if self
+                    .client_from_id(unsafe { ClientId::new(client.id) })
+                    .is_some()
+                {
+                    return Err(InvalidChangeError::DuplicateClientId);
+                }
+                self.previous_client_id = self.previous_client_id.max(client.id);
+                self.clients.push(client.into());
+                Ok(())
+            }
+            Change::ClientDeleted { client } => {
+                let id = unsafe { ClientId::new(client.id) };
+                let index = self
+                    .clients
+                    .iter()
+                    .position(|x| x.id() == id)
+                    .ok_or(InvalidChangeError::ClientNotFound)?;
+                if self.client_projects(id).next().is_some()
+                    || self.invoices.iter().any(|x| x.client() == Some(id))
+                {
+                    return Err(InvalidChangeError::ClientInUse);
+                }
+                self.clients.remove(index);
+                Ok(())
+            }
+            Change::InvoiceCreated { invoice } => {
+                if self.invoice_from_number(invoice.number).is_some() {
+                    return Err(InvalidChangeError::DuplicateInvoiceNumber);
+                }
+                let invoice = self
+                    .invoice_from_data(invoice)
+                    .map_err(InvalidChangeError::InvalidInvoice)?;
+                let index = self
+                    .invoices
+                    .iter()
+                    .position(|x| x.number() > invoice.number())
+                    .unwrap_or(self.invoices.len());
+                self.previous_invoice_number = self.previous_invoice_number.max(invoice.number());
+                self.invoices.insert(index, invoice);
+                Ok(())
+            }
+            Change::InvoiceDeleted { invoice } => {
+                let index = self
+                    .invoices
+                    .iter()
+                    .position(|x| x.number() == invoice.number)
+                    .ok_or(InvalidChangeError::InvoiceNotFound)?;
+                self.invoices.remove(index);
+                Ok(())
+            }
+            Change::PaymentReceived {
+                project_id,
+                payment,
+            } => {
+                self.check_received_payment_id_unused(payment.id)?;
+                let id = payment.id;
+                self.project_from_id_mut(unsafe { ProjectId::new(project_id) })
+                    .ok_or(InvalidChangeError::ProjectNotFound)?
+                    .insert_received_payment(payment.into());
+                self.previous_received_payment_id = self.previous_received_payment_id.max(id);
+                Ok(())
+            }
+            Change::ReceivedPaymentDeleted {
+                project_id,
+                payment,
+            } => self
+                .project_from_id_mut(unsafe { ProjectId::new(project_id) })
+                .ok_or(InvalidChangeError::ProjectNotFound)?
+                .take_received_payment(unsafe { ReceivedPaymentId::new(payment.id) })
+                .map(|_| ())
+                .ok_or(InvalidChangeError::ReceivedPaymentNotFound),
+            Change::ProjectArchived { id } => {
+                let project = self
+                    .project_from_id_mut(unsafe { ProjectId::new(id) })
+                    .ok_or(InvalidChangeError::ProjectNotFound)?;
+                if project.is_archived() {
+                    return Err(InvalidChangeError::AlreadyArchived);
+                }
+                if project.current_work_slice().is_some() {
+                    return Err(InvalidChangeError::WorkOngoing);
+                }
+                project.set_archived(true);
+                Ok(())
+            }
+            Change::ProjectReopened { id } => {
+                let project = self
+                    .project_from_id_mut(unsafe { ProjectId::new(id) })
+                    .ok_or(InvalidChangeError::ProjectNotFound)?;
+                if !project.is_archived() {
+                    return Err(InvalidChangeError::NotArchived);
+                }
+                project.set_archived(false);
+                Ok(())
+            }
+            Change::ProjectDeleted {
+                project,
+                deleted_at,
+            } => {
+                let id = unsafe { ProjectId::new(project.id) };
+                if self
+                    .project_from_id(id)
+                    .is_some_and(|x| self.project_is_invoiced(x))
+                {
+                    return Err(InvalidChangeError::WorkSliceInvoiced);
+                }
+                match self.projects.iter().position(|x| x.id() == id) {
+                    Some(i) => {
+                        self.projects.remove(i);
+                        if let Some(deleted_at) = deleted_at {
+                            self.add_to_trash(TrashEntry {
+                                deleted_at,
+                                item: TrashedItem::Project(project),
+                            });
+                        }
+                        Ok(())
+                    }
+                    None => Err(InvalidChangeError::ProjectNotFound),
+                }
+            }
+            Change::ProjectRestored {
+                project,
+                deleted_at: None,
+            } => self.insert_project_data(project),
+            Change::ProjectRestored {
+                project,
+                deleted_at: Some(_),
+            } => {
+                let entry = self.take_from_trash(project.id, true)?;
+                self.insert_project_data(project).inspect_err(|_| {
+                    self.add_to_trash(entry);
+                })
+            }
+            Change::WorkSliceStarted {
+                project_id,
+                work_slice_id,
+                start_time,
+                payment,
+                note,
+                tags,
+            } => self.insert_work_slice_data(
+                project_id,
+                WorkSliceData::Incomplete(IncompleteWorkSliceData {
+                    start: start_time,
+                    payment,
+                    breaks: Vec::new(),
+                    paused_at: None,
+                    note,
+                    tags,
+                    id: work_slice_id,
+                }),
+            ),
+            Change::WorkSlicePaused {
+                project_id,
+                work_slice_id,
+                time,
+            } => self
+                .current_work_slice_mut(project_id, work_slice_id)?
+                .pause(time)
+                .map_err(|x| match x {
+                    WorkPauseError::AlreadyPaused => InvalidChangeError::AlreadyPaused,
+                    _ => InvalidChangeError::InvalidBreak,
+                }),
+            Change::WorkSlicePauseReverted {
+                project_id,
+                work_slice_id,
+                time,
+            } => {
+                if self
+                    .current_work_slice_mut(project_id, work_slice_id)?
+                    .revert_pause(time)
+                {
+                    Ok(())
+                } else {
+                    Err(InvalidChangeError::NotPaused)
+                }
+            }
+            Change::WorkSliceResumed {
+                project_id,
+                work_slice_id,
+                paused_at,
+                time,
+            } => {
+                let work_slice = self.current_work_slice_mut(project_id, work_slice_id)?;
+                if work_slice.paused_at() != Some(paused_at) {
+                    return Err(InvalidChangeError::NotPaused);
+                }
+                work_slice
+                    .resume(time)
+                    .map(|_| ())
+                    .map_err(|_| InvalidChangeError::InvalidBreak)
+            }
+            Change::WorkSliceResumeReverted {
+                project_id,
+                work_slice_id,
+                paused_at,
+                time,
+            } => {
+                if self
+                    .current_work_slice_mut(project_id, work_slice_id)?
+                    .revert_resume(paused_at, time)
+                {
+                    Ok(())
+                } else {
+                    Err(InvalidChangeError::InvalidBreak)
+                }
+            }
+            Change::WorkSliceLogged {
+                project_id,
+                work_slice,
+            } => self.insert_work_slice_data(project_id, WorkSliceData::Complete(work_slice)),
+            Change::WorkSliceCompleted {
+                project_id,
+                work_slice_id,
+                end_time,
+                annotation,
+                ..
+            } => {
+                let project = self
+                    .project_from_id_mut(unsafe { ProjectId::new(project_id) })
+                    .ok_or(InvalidChangeError::ProjectNotFound)?;
+                if project
+                    .current_work_slice()
+                    .is_none_or(|x| unsafe { x.id().inner() } != work_slice_id)
+                {
+                    return Err(InvalidChangeError::NoWorkToComplete);
+                }
+                project
+                    .complete_work(end_time)
+                    .map_err(|_| InvalidChangeError::EndTimeTooEarly)?;
+                if let Some(annotation) = annotation {
+                    project.annotate_work_slice(
+                        unsafe { WorkSliceId::new(work_slice_id) },
+                        annotation.note,
+                        annotation.tags,
+                    );
+                }
+                Ok(())
+            }
+            Change::WorkSliceUncompleted {
+                project_id,
+                work_slice_id,
+                annotation,
+                ..
+            } => {
+                self.check_not_invoiced(work_slice_id)?;
+                let project = self
+                    .project_from_id_mut(unsafe { ProjectId::new(project_id) })
+                    .ok_or(InvalidChangeError::ProjectNotFound)?;
+                if project.current_work_slice().is_some() {
+                    return Err(InvalidChangeError::AlreadyStarted);
+                }
+                if project.is_archived() {
+                    return Err(InvalidChangeError::ProjectArchived);
+                }
+                let work_slice = project
+                    .take_complete_work_slice(unsafe { WorkSliceId::new(work_slice_id) })
+                    .ok_or(InvalidChangeError::WorkSliceNotFound)?;
+                let mut work_slice = work_slice.uncomplete();
+                if let Some(annotation) = annotation {
+                    work_slice.annotate(annotation.note, annotation.tags);
+                }
+                project
+                    .start_work(work_slice)
+                    .map_err(|_| InvalidChangeError::AlreadyStarted)
+            }
+            Change::WorkSliceEdited {
+                project_id,
+                work_slice_id,
+                start,
+                end,
+                payment,
+                ..
+            } => {
+                self.check_not_invoiced(work_slice_id)?;
+                let project = self
+                    .project_from_id_mut(unsafe { ProjectId::new(project_id) })
+                    .ok_or(InvalidChangeError::ProjectNotFound)?;
+                let work_slice = project
+                    .complete_work_slice_mut(unsafe { WorkSliceId::new(work_slice_id) })
+                    .ok_or(InvalidChangeError::WorkSliceNotFound)?;
+                work_slice.edit(start, end, payment)?;
+                Ok(())
+            }
+            Change::WorkSliceAnnotated {
+                project_id,
+                work_slice_id,
+                note,
+                tags,
+                ..
+            } => {
+                self.check_not_invoiced(work_slice_id)?;
+                let project = self
+                    .project_from_id_mut(unsafe { ProjectId::new(project_id) })
+                    .ok_or(InvalidChangeError::ProjectNotFound)?;
+                project
+                    .annotate_work_slice(unsafe { WorkSliceId::new(work_slice_id) }, note, tags)
+                    .ok_or(InvalidChangeError::WorkSliceNotFound)?;
+                Ok(())
+            }
+            Change::WorkSliceDeleted {
+                project_id,
+                work_slice,
+                deleted_at,
+            } => {
+                self.check_not_invoiced(work_slice.id())?;
+                let project = self
+                    .project_from_id_mut(unsafe { ProjectId::new(project_id) })
+                    .ok_or(InvalidChangeError::ProjectNotFound)?;
+                if !project.delete_work_slice(unsafe { WorkSliceId::new(work_slice.id()) }) {
+                    return Err(InvalidChangeError::WorkSliceNotFound);
+                }
+                if let Some(deleted_at) = deleted_at {
+                    self.add_to_trash(TrashEntry {
+                        deleted_at,
+                        item: TrashedItem::WorkSlice {
+                            project_id,
+                            work_slice,
+                        },
+                    });
+                }
+                Ok(())
+            }
+            Change::WorkSliceRestored {
+                project_id,
+                work_slice,
+                deleted_at: None,
+            } => self.insert_work_slice_data(project_id, work_slice),
+            Change::WorkSliceRestored {
+                project_id,
+                work_slice,
+                deleted_at: Some(_),
+            } => {
+                let entry = self.take_from_trash(work_slice.id(), false)?;
+                self.insert_work_slice_data(project_id, work_slice)
+                    .inspect_err(|_| {
+                        self.add_to_trash(entry);
+                    })
+            }
+            Change::TrashPurged { entries } => {
+                if !entries.iter().all(|x| self.trash.contains(x)) {
+                    return Err(InvalidChangeError::NotInTrash);
+                }
+                self.trash.retain(|x| !entries.contains(x));
+                Ok(())
+            }
+            Change::TrashPurgeReverted { entries } => {
+                for entry in entries {
+                    self.add_to_trash(entry);
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Fails if the work slice with this ID is on an invoice, so it can't be changed.
+    fn check_not_invoiced(&self, work_slice_id: u64) -> Result<(), InvalidChangeError> {
+        if self.is_invoiced(unsafe { WorkSliceId::new(work_slice_id) }) {
+            return Err(InvalidChangeError::WorkSliceInvoiced);
+        }
+        Ok(())
+    }
+
+    /// Adds a deleted project back, with all of its work slices and received payments.
+    fn insert_project_data(&mut self, project: ProjectData) -> Result<(), InvalidChangeError> {
+        if self.project_exists(unsafe { ProjectId::new(project.id) }) {
+            return Err(InvalidChangeError::DuplicateProjectId);
+        }
+        let work_slice_ids: Vec<_> = project
+            .work_slices
+            .iter()
+            .map(|x| x.id)
+            .chain(project.current_slice.iter().map(|x| x.id))
+            .collect();
+        for &id in &work_slice_ids {
+            self.check_work_slice_id_unused(id)?;
+        }
+        let received_payment_ids: Vec<_> = project.received_payments.iter().map(|x| x.id).collect();
+        for &id in &received_payment_ids {
+            self.check_received_payment_id_unused(id)?;
+        }
+        let project = project.into_project().map_err(|x| match x {
+            DataToProjectError::CompleteWorkSlice(x) => x.into(),
+            DataToProjectError::IncompleteWorkSlice => InvalidChangeError::InvalidStartTime,
+        })?;
+        self.previous_project_id = self
+            .previous_project_id
+            .max(unsafe { project.id().inner() });
+        self.previous_work_slice_id = work_slice_ids
+            .into_iter()
+            .fold(self.previous_work_slice_id, u64::max);
+        self.previous_received_payment_id = received_payment_ids
+            .into_iter()
+            .fold(self.previous_received_payment_id, u64::max);
+        let index = self
+            .projects
+            .iter()
+            .position(|x| x.id() > project.id())
+            .unwrap_or(self.projects.len());
+        self.projects.insert(index, project);
+        Ok(())
+    }
+
+    /// Adds a work slice to a project, as either a complete work slice
+    /// or the project's current work slice.
+    fn insert_work_slice_data(
+        &mut self,
+        project_id: u64,
+        work_slice: WorkSliceData,
+    ) -> Result<(), InvalidChangeError> {
+        let work_slice_id = work_slice.id();
+        self.check_work_slice_id_unused(work_slice_id)?;
+        let project = self
+            .project_from_id_mut(unsafe { ProjectId::new(project_id) })
+            .ok_or(InvalidChangeError::ProjectNotFound)?;
+        match work_slice {
+            WorkSliceData::Complete(data) => {
+                project.insert_complete_work_slice(data.into_work_slice()?);
+            }
+            WorkSliceData::Incomplete(data) => {
+                if project.is_archived() {
+                    return Err(InvalidChangeError::ProjectArchived);
+                }
+                let work_slice = data
+                    .into_work_slice()
+                    .ok_or(InvalidChangeError::InvalidStartTime)?;
+                project
+                    .start_work(work_slice)
+                    .map_err(|_| InvalidChangeError::AlreadyStarted)?;
+            }
+        }
+        self.previous_work_slice_id = self.previous_work_slice_id.max(work_slice_id);
+        Ok(())
+    }
+
+    /// Returns the current work slice of a project, if it has the ID `work_slice_id`.
+    fn current_work_slice_mut(
+        &mut self,
+        project_id: u64,
+        work_slice_id: u64,
+    ) -> Result<&mut IncompleteWorkSlice, InvalidChangeError> {
+        self.project_from_id_mut(unsafe { ProjectId::new(project_id) })
+            .ok_or(InvalidChangeError::ProjectNotFound)?
+            .current_work_slice_mut()
+            .filter(|x| unsafe { x.id().inner() } == work_slice_id)
+            .ok_or(InvalidChangeError::NoWorkToPause)
+    }
+
+    fn check_work_slice_id_unused(&self, id: u64) -> Result<(), InvalidChangeError> {
+        match self.project_id_from_work_slice(unsafe { WorkSliceId::new(id) }) {
+            Some(_) => Err(InvalidChangeError::DuplicateWorkSliceId),
+            None => Ok(()),
+        }
+    }
+
+    fn check_received_payment_id_unused(&self, id: u64) -> Result<(), InvalidChangeError> {
+        match self.received_payment_from_id(unsafe { ReceivedPaymentId::new(id) }) {
+            Some(_) => Err(InvalidChangeError::DuplicateReceivedPaymentId),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use crate::{
+        Annotation, Change, Currency, InvalidChangeError, MemoryStorage, Money, Payment, State,
+        StateReplayError,
+    };
+
+    fn summary(state: &State) -> Vec<(u64, String, Vec<u64>, Option<u64>)> {
+        state
+            .all_projects()
+            .map(|x| unsafe {
+                (
+                    x.id().inner(),
+                    x.name().to_string(),
+                    x.complete_work_slices().map(|x| x.id().inner()).collect(),
+                    x.current_work_slice().map(|x| x.id().inner()),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn replay_matches_original() {
+        let now = Utc::now();
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let first = state.new_project("first".to_string(), "".to_string(), None);
+        let second = state.new_project("second".to_string(), "".to_string(), None);
+        let payment = Payment::Hourly(Money::new(1500, Currency::Gbp));
+        state
+            .start_work(
+                first,
+                Some(payment),
+                now - TimeDelta::hours(3),
+                Annotation::default(),
+            )
+            .unwrap();
+        state
+            .end_work(first, now - TimeDelta::hours(2), None)
+            .unwrap();
+        state
+            .start_work(
+                first,
+                Some(payment),
+                now - TimeDelta::hours(1),
+                Annotation::default(),
+            )
+            .unwrap();
+        state
+            .start_work(
+                second,
+                Some(payment),
+                now - TimeDelta::hours(1),
+                Annotation::default(),
+            )
+            .unwrap();
+        state.delete_project(second);
+        let changes = state.handle_changes();
+
+        let mut replayed = State::from_changes(changes, MemoryStorage::default()).unwrap();
+        assert_eq!(summary(&state), summary(&replayed));
+        assert!(replayed.handle_changes().is_empty());
+        assert_eq!(
+            replayed.new_project("third".to_string(), "".to_string(), None),
+            state.new_project("third".to_string(), "".to_string(), None)
+        );
+    }
+
+    #[test]
+    fn replay_rejects_invalid_changes() {
+        let changes = vec![
+            Change::ProjectCreated {
+                name: "project".to_string(),
+                description: "".to_string(),
+                default_payment: None,
+                id: 1,
+            },
+            Change::WorkSliceCompleted {
+                project_id: 1,
+                work_slice_id: 1,
+                end_time: Utc::now(),
+                old_annotation: None,
+                annotation: None,
+            },
+        ];
+        assert_eq!(
+            State::from_changes(changes, MemoryStorage::default()).err(),
+            Some(StateReplayError::InvalidChange {
+                index: 1,
+                error: InvalidChangeError::NoWorkToComplete
+            })
+        );
+    }
+}
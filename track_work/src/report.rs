@@ -0,0 +1,308 @@
+use std::{fmt::Display, str::FromStr};
+
+use chrono::{DateTime, Datelike, Days, Months, TimeDelta, Utc};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    ClientId, MoneyTotals, Project, ProjectId, ReportError, State, UnknownReportPeriodError,
+};
+
+/// The length of the periods which a report adds up work over.
+/// Periods start at midnight UTC, and weeks are ISO weeks, starting on Monday.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ReportPeriod {
+    #[default]
+    Day,
+    Week,
+    Month,
+}
+impl ReportPeriod {
+    /// Returns the start of the period which `time` is in.
+    pub fn start_of(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let date = time.date_naive();
+        let start = match self {
+            ReportPeriod::Day => date,
+            ReportPeriod::Week => date - Days::new(date.weekday().num_days_from_monday().into()),
+            ReportPeriod::Month => date.with_day(1).unwrap(),
+        };
+        start.and_time(Default::default()).and_utc()
+    }
+
+    /// Returns the start of the period after the one which `time` is in.
+    pub fn end_of(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        let start = self.start_of(time);
+        match self {
+            ReportPeriod::Day => start + Days::new(1),
+            ReportPeriod::Week => start + Days::new(7),
+            ReportPeriod::Month => start + Months::new(1),
+        }
+    }
+
+    /// Returns the name of the period which `time` is in,
+    /// like "2024-03-01" for a day, "2024-W09" for a week or "2024-03" for a month.
+    pub fn name_of(&self, time: DateTime<Utc>) -> String {
+        let date = time.date_naive();
+        match self {
+            ReportPeriod::Day => date.format("%Y-%m-%d").to_string(),
+            ReportPeriod::Week => format!(
+                "{year}-W{week:02}",
+                year = date.iso_week().year(),
+                week = date.iso_week().week()
+            ),
+            ReportPeriod::Month => date.format("%Y-%m").to_string(),
+        }
+    }
+}
+impl Display for ReportPeriod {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                ReportPeriod::Day => "day",
+                ReportPeriod::Week => "week",
+                ReportPeriod::Month => "month",
+            }
+        )
+    }
+}
+impl FromStr for ReportPeriod {
+    type Err = UnknownReportPeriodError;
+
+    /// Parses "day", "week" or "month", or "daily", "weekly" or "monthly".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "day" | "daily" => Ok(ReportPeriod::Day),
+            "week" | "weekly" => Ok(ReportPeriod::Week),
+            "month" | "monthly" => Ok(ReportPeriod::Month),
+            _ => Err(UnknownReportPeriodError),
+        }
+    }
+}
+
+/// Which work is included in a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ReportFilter {
+    /// Work for all projects, including archived ones.
+    #[default]
+    All,
+    /// Work for a single project.
+    Project(ProjectId),
+    /// Work for all of a client's projects.
+    Client(ClientId),
+}
+
+/// The work done in a single period of a report.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportRow {
+    /// When the period starts. If the report starts part way through the period, this is the start of the report.
+    pub start: DateTime<Utc>,
+    /// When the period ends. If the report ends part way through the period, this is the end of the report.
+    pub end: DateTime<Utc>,
+    /// How many work slices were worked on in the period, including ones which were only partly in it.
+    pub work_slices: usize,
+    /// The time worked, not including breaks.
+    pub duration: TimeDelta,
+    pub payment: MoneyTotals,
+}
+
+/// The work done in each period between two times, made by `State::report`.
+/// Work slices which go over the end of a period are split between the periods,
+/// in the same way as `State::work_in_range`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub period: ReportPeriod,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    /// Every period from `start` until `end`, in order, including periods with no work.
+    pub rows: Vec<ReportRow>,
+    /// The time worked over the whole report, not including breaks.
+    pub total_duration: TimeDelta,
+    pub total_payment: MoneyTotals,
+}
+
+impl State {
+    /// Adds up the work done in each period from `start` until `end`, for the projects chosen by `filter`.
+    /// If `start` is `None`, the report starts when the earliest of those projects' work started,
+    /// and if `end` is `None`, it ends now. Ongoing work is included up to now.
+    /// Fails if the project or client in the filter doesn't exist.
+    pub fn report(
+        &self,
+        period: ReportPeriod,
+        filter: ReportFilter,
+        start: Option<DateTime<Utc>>,
+        end: Option<DateTime<Utc>>,
+    ) -> Result<Report, ReportError> {
+        let projects = self.filtered_projects(filter)?;
+        let end = end.unwrap_or_else(Utc::now);
+        let start = start.unwrap_or_else(|| first_work_start(&projects).unwrap_or(end).min(end));
+
+        let mut rows = Vec::new();
+        let mut row_start = start;
+        while row_start < end {
+            let row_end = period.end_of(row_start).min(end);
+            let work: Vec<_> = projects
+                .iter()
+                .flat_map(|x| x.work_in_range(row_start, row_end))
+                .collect();
+            rows.push(ReportRow {
+                start: row_start,
+                end: row_end,
+                work_slices: work.len(),
+                duration: work.iter().map(|x| x.duration).sum(),
+                payment: work.iter().map(|x| x.payment).sum(),
+            });
+            row_start = row_end;
+        }
+        Ok(Report {
+            period,
+            start,
+            end,
+            total_duration: rows.iter().map(|x| x.duration).sum(),
+            total_payment: rows.iter().map(|x| x.payment.clone()).sum(),
+            rows,
+        })
+    }
+
+    /// Returns the projects chosen by a filter, failing if its project or client doesn't exist.
+    pub(crate) fn filtered_projects(
+        &self,
+        filter: ReportFilter,
+    ) -> Result<Vec<&Project>, ReportError> {
+        Ok(match filter {
+            ReportFilter::All => self.all_projects().collect(),
+            ReportFilter::Project(id) => {
+                vec![self
+                    .project_from_id(id)
+                    .ok_or(ReportError::InvalidProjectId)?]
+            }
+            ReportFilter::Client(id) => {
+                self.client_from_id(id)
+                    .ok_or(ReportError::InvalidClientId)?;
+                self.client_projects(id).collect()
+            }
+        })
+    }
+}
+
+/// Returns when the earliest work in any of the projects started, if they have any work.
+pub(crate) fn first_work_start(projects: &[&Project]) -> Option<DateTime<Utc>> {
+    projects
+        .iter()
+        .flat_map(|project| {
+            project
+                .complete_work_slices()
+                .map(|x| x.start())
+                .chain(project.current_work_slice().map(|x| x.start()))
+        })
+        .min()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeDelta, Utc};
+
+    use crate::{
+        Annotation, ClientId, Currency, MemoryStorage, Money, MoneyExact, Payment, ReportError,
+        ReportFilter, ReportPeriod, State,
+    };
+
+    fn time(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn reports_split_work_into_periods() {
+        let gbp = |amount| MoneyExact::from(Money::new(amount, Currency::Gbp));
+        let payment = Some(Payment::Hourly(Money::new(1000, Currency::Gbp)));
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let client = state.new_client("client".to_string(), "".to_string(), "".to_string(), None);
+        let first = state.new_project("first".to_string(), "".to_string(), None);
+        let second = state.new_project("second".to_string(), "".to_string(), None);
+        state.set_project_client(second, Some(client)).unwrap();
+        // Friday 1st March 2024, going over midnight
+        state
+            .add_complete_work_slice(
+                first,
+                time("2024-03-01T22:00:00Z"),
+                time("2024-03-02T02:00:00Z"),
+                payment,
+                Annotation::default(),
+            )
+            .unwrap();
+        // Monday 4th March 2024
+        state
+            .add_complete_work_slice(
+                second,
+                time("2024-03-04T09:00:00Z"),
+                time("2024-03-04T12:00:00Z"),
+                payment,
+                Annotation::default(),
+            )
+            .unwrap();
+
+        let report = state
+            .report(
+                ReportPeriod::Day,
+                ReportFilter::All,
+                None,
+                Some(time("2024-03-05T00:00:00Z")),
+            )
+            .unwrap();
+        assert_eq!(report.start, time("2024-03-01T22:00:00Z"));
+        assert_eq!(report.rows.len(), 4);
+        assert_eq!(report.rows[0].duration, TimeDelta::hours(2));
+        assert_eq!(report.rows[1].start, time("2024-03-02T00:00:00Z"));
+        assert_eq!(report.rows[1].payment.get(Currency::Gbp), gbp(2000));
+        assert_eq!(report.rows[2].work_slices, 0);
+        assert_eq!(report.total_duration, TimeDelta::hours(7));
+        assert_eq!(report.total_payment.get(Currency::Gbp), gbp(7000));
+
+        let report = state
+            .report(
+                ReportPeriod::Week,
+                ReportFilter::All,
+                Some(time("2024-02-26T00:00:00Z")),
+                Some(time("2024-03-11T00:00:00Z")),
+            )
+            .unwrap();
+        assert_eq!(report.rows.len(), 2);
+        assert_eq!(report.rows[0].duration, TimeDelta::hours(4));
+        assert_eq!(report.rows[1].duration, TimeDelta::hours(3));
+        assert_eq!(ReportPeriod::Week.name_of(report.rows[1].start), "2024-W10");
+
+        let report = state
+            .report(
+                ReportPeriod::Month,
+                ReportFilter::Client(client),
+                Some(time("2024-01-15T00:00:00Z")),
+                Some(time("2024-04-01T00:00:00Z")),
+            )
+            .unwrap();
+        assert_eq!(report.rows.len(), 3);
+        assert_eq!(report.rows[1].start, time("2024-02-01T00:00:00Z"));
+        assert_eq!(report.rows[2].work_slices, 1);
+        assert_eq!(report.total_duration, TimeDelta::hours(3));
+
+        let report = state
+            .report(
+                ReportPeriod::Month,
+                ReportFilter::Project(first),
+                None,
+                Some(time("2024-04-01T00:00:00Z")),
+            )
+            .unwrap();
+        assert_eq!(report.total_duration, TimeDelta::hours(4));
+        assert_eq!(
+            state.report(
+                ReportPeriod::Day,
+                ReportFilter::Client(unsafe { ClientId::new(5) }),
+                None,
+                None
+            ),
+            Err(ReportError::InvalidClientId)
+        );
+    }
+}
@@ -0,0 +1,1041 @@
+use std::{collections::BTreeSet, error::Error, path::Path};
+
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+
+use crate::{
+    Annotation, Break, Change, Client, ClientData, CompleteWorkSliceData, IncompleteWorkSliceData,
+    Invoice, InvoiceData, Payment, Project, ProjectData, ReceivedPaymentData, StateData, Storage,
+    StoredData, TrashEntry, TrashedItem, WorkSliceData,
+};
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS projects (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        description TEXT NOT NULL,
+        default_payment TEXT,
+        archived INTEGER NOT NULL DEFAULT 0,
+        client_id INTEGER REFERENCES clients(id)
+    );
+    CREATE TABLE IF NOT EXISTS clients (
+        id INTEGER PRIMARY KEY,
+        name TEXT NOT NULL,
+        contact TEXT NOT NULL,
+        billing_address TEXT NOT NULL,
+        default_payment TEXT
+    );
+    CREATE TABLE IF NOT EXISTS work_slices (
+        id INTEGER PRIMARY KEY,
+        project_id INTEGER NOT NULL REFERENCES projects(id),
+        start TEXT NOT NULL,
+        end TEXT,
+        payment TEXT NOT NULL,
+        breaks TEXT NOT NULL DEFAULT '[]',
+        paused_at TEXT,
+        note TEXT,
+        tags TEXT NOT NULL DEFAULT '[]'
+    );
+    CREATE INDEX IF NOT EXISTS work_slices_project_id ON work_slices(project_id);
+    CREATE TABLE IF NOT EXISTS received_payments (
+        id INTEGER PRIMARY KEY,
+        project_id INTEGER NOT NULL REFERENCES projects(id),
+        amount TEXT NOT NULL,
+        date TEXT NOT NULL,
+        reference TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS invoices (
+        number INTEGER PRIMARY KEY,
+        client_id INTEGER REFERENCES clients(id),
+        issued TEXT NOT NULL,
+        due TEXT NOT NULL,
+        work_slices TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS trash (
+        kind TEXT NOT NULL,
+        item_id INTEGER NOT NULL,
+        deleted_at TEXT NOT NULL,
+        item TEXT NOT NULL,
+        PRIMARY KEY (kind, item_id)
+    );
+    CREATE TABLE IF NOT EXISTS counters (
+        name TEXT PRIMARY KEY,
+        value INTEGER NOT NULL
+    );
+";
+
+/// Stores projects and work slices as rows in a SQLite database.
+/// Each change is saved by updating only the rows it affects.
+/// Work slices with no end are the current work slices of their projects,
+/// and their breaks and tags are stored as JSON, as are the IDs of the work slices on each invoice.
+/// Items in the trash are stored as JSON, keyed by whether they are a project or a work slice.
+/// The highest invoice number used so far is kept in the `counters` table.
+pub struct SqliteStorage {
+    connection: Connection,
+}
+impl SqliteStorage {
+    /// Opens (or creates) the database at `path`, creating the tables if they don't exist.
+    pub fn open(path: impl AsRef<Path>) -> rusqlite::Result<Self> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    /// Uses an existing connection, creating the tables if they don't exist.
+    /// Foreign keys are turned on for the connection, so rows can't refer to rows which don't exist.
+    pub fn from_connection(connection: Connection) -> rusqlite::Result<Self> {
+        connection.pragma_update(None, "foreign_keys", true)?;
+        connection.execute_batch(SCHEMA)?;
+        add_column_if_missing(&connection, "projects", "default_payment", "TEXT")?;
+        add_column_if_missing(
+            &connection,
+            "projects",
+            "archived",
+            "INTEGER NOT NULL DEFAULT 0",
+        )?;
+        add_column_if_missing(
+            &connection,
+            "projects",
+            "client_id",
+            "INTEGER REFERENCES clients(id)",
+        )?;
+        add_column_if_missing(
+            &connection,
+            "work_slices",
+            "breaks",
+            "TEXT NOT NULL DEFAULT '[]'",
+        )?;
+        add_column_if_missing(&connection, "work_slices", "paused_at", "TEXT")?;
+        add_column_if_missing(&connection, "work_slices", "note", "TEXT")?;
+        add_column_if_missing(
+            &connection,
+            "work_slices",
+            "tags",
+            "TEXT NOT NULL DEFAULT '[]'",
+        )?;
+        Ok(Self { connection })
+    }
+}
+impl Storage for SqliteStorage {
+    fn load(&mut self) -> Result<StoredData, Box<dyn Error>> {
+        let mut projects: Vec<ProjectData> = self
+            .connection
+            .prepare(
+                "SELECT id, name, description, default_payment, archived, client_id FROM projects ORDER BY id",
+            )?
+            .query_map([], |row| {
+                Ok((
+                    ProjectData {
+                        id: row.get(0)?,
+                        name: row.get(1)?,
+                        description: row.get(2)?,
+                        work_slices: Vec::new(),
+                        current_slice: None,
+                        default_payment: None,
+                        archived: row.get(4)?,
+                        client: row.get(5)?,
+                        received_payments: Vec::new(),
+                    },
+                    row.get::<_, Option<String>>(3)?,
+                ))
+            })?
+            .map(|row| {
+                let (mut project, default_payment) = row?;
+                project.default_payment = default_payment
+                    .map(|x| serde_json::from_str(&x))
+                    .transpose()?;
+                Ok(project)
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?;
+
+        let mut statement = self
+            .connection
+            .prepare("SELECT id, project_id, start, end, payment, breaks, paused_at, note, tags FROM work_slices ORDER BY id")?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let project_id: u64 = row.get(1)?;
+            let start: DateTime<Utc> = row.get(2)?;
+            let end: Option<DateTime<Utc>> = row.get(3)?;
+            let payment: Payment = serde_json::from_str(&row.get::<_, String>(4)?)?;
+            let breaks: Vec<Break> = serde_json::from_str(&row.get::<_, String>(5)?)?;
+            let note: Option<String> = row.get(7)?;
+            let tags: BTreeSet<String> = serde_json::from_str(&row.get::<_, String>(8)?)?;
+            let project = projects
+                .iter_mut()
+                .find(|x| x.id == project_id)
+                .ok_or("A work slice belongs to a project which doesn't exist")?;
+            match end {
+                Some(end) => project.work_slices.push(CompleteWorkSliceData {
+                    start,
+                    end,
+                    payment,
+                    breaks,
+                    note,
+                    tags,
+                    id: row.get(0)?,
+                }),
+                None => {
+                    project.current_slice = Some(IncompleteWorkSliceData {
+                        start,
+                        payment,
+                        breaks,
+                        paused_at: row.get(6)?,
+                        note,
+                        tags,
+                        id: row.get(0)?,
+                    })
+                }
+            }
+        }
+
+        let mut statement = self.connection.prepare(
+            "SELECT id, project_id, amount, date, reference FROM received_payments ORDER BY id",
+        )?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let project_id: u64 = row.get(1)?;
+            let project = projects
+                .iter_mut()
+                .find(|x| x.id == project_id)
+                .ok_or("A received payment is for a project which doesn't exist")?;
+            project.received_payments.push(ReceivedPaymentData {
+                id: row.get(0)?,
+                amount: serde_json::from_str(&row.get::<_, String>(2)?)?,
+                date: row.get(3)?,
+                reference: row.get(4)?,
+            });
+        }
+
+        let clients = self
+            .connection
+            .prepare(
+                "SELECT id, name, contact, billing_address, default_payment FROM clients ORDER BY id",
+            )?
+            .query_map([], |row| {
+                Ok((
+                    ClientData {
+                        id: row.get(0)?,
+                        name: row.get(1)?,
+                        contact: row.get(2)?,
+                        billing_address: row.get(3)?,
+                        default_payment: None,
+                    },
+                    row.get::<_, Option<String>>(4)?,
+                ))
+            })?
+            .map(|row| {
+                let (mut client, default_payment) = row?;
+                client.default_payment = default_payment
+                    .map(|x| serde_json::from_str(&x))
+                    .transpose()?;
+                Ok(client)
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?;
+
+        let invoices = self
+            .connection
+            .prepare(
+                "SELECT number, client_id, issued, due, work_slices FROM invoices ORDER BY number",
+            )?
+            .query_map([], |row| {
+                Ok((
+                    InvoiceData {
+                        number: row.get(0)?,
+                        client: row.get(1)?,
+                        issued: row.get(2)?,
+                        due: row.get(3)?,
+                        work_slices: Vec::new(),
+                    },
+                    row.get::<_, String>(4)?,
+                ))
+            })?
+            .map(|row| {
+                let (mut invoice, work_slices) = row?;
+                invoice.work_slices = serde_json::from_str(&work_slices)?;
+                Ok(invoice)
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?;
+
+        let trash = self
+            .connection
+            .prepare("SELECT deleted_at, item FROM trash ORDER BY deleted_at, rowid")?
+            .query_map([], |row| Ok((row.get(0)?, row.get::<_, String>(1)?)))?
+            .map(|row| {
+                let (deleted_at, item) = row?;
+                Ok(TrashEntry {
+                    deleted_at,
+                    item: serde_json::from_str(&item)?,
+                })
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?;
+
+        let previous_invoice_number = self
+            .connection
+            .query_row(
+                "SELECT value FROM counters WHERE name = 'previous_invoice_number'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?
+            .unwrap_or(0);
+
+        Ok(StoredData {
+            data: StateData {
+                projects,
+                clients,
+                invoices,
+                trash,
+                previous_invoice_number,
+            },
+            changes: Vec::new(),
+        })
+    }
+
+    fn apply_changes(
+        &mut self,
+        changes: Vec<Change>,
+        _projects: Vec<&Project>,
+        _clients: &[Client],
+        _invoices: &[Invoice],
+        _trash: &[TrashEntry],
+        previous_invoice_number: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        let transaction = self.connection.transaction()?;
+        for change in changes {
+            apply_change(&transaction, change)?;
+        }
+        set_previous_invoice_number(&transaction, previous_invoice_number)?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn snapshot(&mut self, data: StateData) -> Result<(), Box<dyn Error>> {
+        let transaction = self.connection.transaction()?;
+        transaction
+            .execute_batch("DELETE FROM invoices; DELETE FROM received_payments; DELETE FROM work_slices; DELETE FROM projects; DELETE FROM clients; DELETE FROM trash;")?;
+        for client in &data.clients {
+            insert_client(&transaction, client)?;
+        }
+        for project in &data.projects {
+            insert_project(&transaction, project)?;
+        }
+        for invoice in &data.invoices {
+            insert_invoice(&transaction, invoice)?;
+        }
+        for entry in &data.trash {
+            insert_trash_entry(&transaction, entry)?;
+        }
+        set_previous_invoice_number(&transaction, data.previous_invoice_number)?;
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+fn set_previous_invoice_number(transaction: &Transaction, number: u64) -> rusqlite::Result<()> {
+    transaction.execute(
+        "INSERT OR REPLACE INTO counters (name, value) VALUES ('previous_invoice_number', ?1)",
+        params![number],
+    )?;
+    Ok(())
+}
+
+fn apply_change(transaction: &Transaction, change: Change) -> Result<(), Box<dyn Error>> {
+    match change {
+        Change::ProjectCreated {
+            name,
+            description,
+            default_payment,
+            id,
+        } => {
+            transaction.execute(
+                "INSERT INTO projects (id, name, description, default_payment) VALUES (?1, ?2, ?3, ?4)",
+                params![id, name, description, payment_to_json(default_payment)?],
+            )?;
+        }
+        Change::ProjectDefaultPaymentChanged { id, payment, .. } => {
+            transaction.execute(
+                "UPDATE projects SET default_payment = ?2 WHERE id = ?1",
+                params![id, payment_to_json(payment)?],
+            )?;
+        }
+        Change::ProjectUpdated {
+            id,
+            name,
+            description,
+            ..
+        } => {
+            transaction.execute(
+                "UPDATE projects SET name = ?2, description = ?3 WHERE id = ?1",
+                params![id, name, description],
+            )?;
+        }
+        Change::ProjectClientChanged { id, client, .. } => {
+            transaction.execute(
+                "UPDATE projects SET client_id = ?2 WHERE id = ?1",
+                params![id, client],
+            )?;
+        }
+        Change::ClientCreated { client } => insert_client(transaction, &client)?,
+        Change::InvoiceCreated { invoice } => insert_invoice(transaction, &invoice)?,
+        Change::InvoiceDeleted { invoice } => {
+            transaction.execute(
+                "DELETE FROM invoices WHERE number = ?1",
+                params![invoice.number],
+            )?;
+        }
+        Change::PaymentReceived {
+            project_id,
+            payment,
+        } => insert_received_payment(transaction, project_id, &payment)?,
+        Change::ReceivedPaymentDeleted { payment, .. } => {
+            transaction.execute(
+                "DELETE FROM received_payments WHERE id = ?1",
+                params![payment.id],
+            )?;
+        }
+        Change::ClientDeleted { client } => {
+            transaction.execute("DELETE FROM clients WHERE id = ?1", params![client.id])?;
+        }
+        Change::ProjectArchived { id } => {
+            transaction.execute(
+                "UPDATE projects SET archived = 1 WHERE id = ?1",
+                params![id],
+            )?;
+        }
+        Change::ProjectReopened { id } => {
+            transaction.execute(
+                "UPDATE projects SET archived = 0 WHERE id = ?1",
+                params![id],
+            )?;
+        }
+        Change::ProjectDeleted {
+            project,
+            deleted_at,
+        } => {
+            transaction.execute(
+                "DELETE FROM work_slices WHERE project_id = ?1",
+                params![project.id],
+            )?;
+            transaction.execute(
+                "DELETE FROM received_payments WHERE project_id = ?1",
+                params![project.id],
+            )?;
+            transaction.execute("DELETE FROM projects WHERE id = ?1", params![project.id])?;
+            if let Some(deleted_at) = deleted_at {
+                insert_trash_entry(
+                    transaction,
+                    &TrashEntry {
+                        deleted_at,
+                        item: TrashedItem::Project(project),
+                    },
+                )?;
+            }
+        }
+        Change::ProjectRestored {
+            project,
+            deleted_at,
+        } => {
+            insert_project(transaction, &project)?;
+            if deleted_at.is_some() {
+                delete_trash_entry(transaction, &TrashedItem::Project(project))?;
+            }
+        }
+        Change::WorkSliceCompleted {
+            work_slice_id,
+            end_time,
+            annotation,
+            ..
+        } => {
+            transaction.execute(
+                "UPDATE work_slices SET end = ?2 WHERE id = ?1",
+                params![work_slice_id, end_time],
+            )?;
+            // a paused work slice's current break lasts until it's completed
+            update_breaks(transaction, work_slice_id, |breaks, paused_at| {
+                if let Some(start) = paused_at.take() {
+                    breaks.push(Break {
+                        start,
+                        end: end_time,
+                    });
+                }
+            })?;
+            if let Some(annotation) = annotation {
+                update_annotation(transaction, work_slice_id, &annotation)?;
+            }
+        }
+        Change::WorkSliceUncompleted {
+            work_slice_id,
+            end_time,
+            annotation,
+            ..
+        } => {
+            transaction.execute(
+                "UPDATE work_slices SET end = NULL WHERE id = ?1",
+                params![work_slice_id],
+            )?;
+            update_breaks(transaction, work_slice_id, |breaks, paused_at| {
+                if breaks.last().is_some_and(|x| x.end == end_time) {
+                    *paused_at = breaks.pop().map(|x| x.start);
+                }
+            })?;
+            if let Some(annotation) = annotation {
+                update_annotation(transaction, work_slice_id, &annotation)?;
+            }
+        }
+        Change::WorkSlicePaused {
+            work_slice_id,
+            time,
+            ..
+        } => update_breaks(transaction, work_slice_id, |_, paused_at| {
+            *paused_at = Some(time)
+        })?,
+        Change::WorkSlicePauseReverted { work_slice_id, .. } => {
+            update_breaks(transaction, work_slice_id, |_, paused_at| *paused_at = None)?
+        }
+        Change::WorkSliceResumed {
+            work_slice_id,
+            paused_at: start,
+            time,
+            ..
+        } => update_breaks(transaction, work_slice_id, |breaks, paused_at| {
+            breaks.push(Break { start, end: time });
+            *paused_at = None;
+        })?,
+        Change::WorkSliceResumeReverted {
+            work_slice_id,
+            paused_at: start,
+            ..
+        } => update_breaks(transaction, work_slice_id, |breaks, paused_at| {
+            breaks.pop();
+            *paused_at = Some(start);
+        })?,
+        Change::WorkSliceStarted {
+            project_id,
+            work_slice_id,
+            start_time,
+            payment,
+            note,
+            tags,
+        } => insert_work_slice(
+            transaction,
+            project_id,
+            &WorkSliceData::Incomplete(IncompleteWorkSliceData {
+                start: start_time,
+                payment,
+                breaks: Vec::new(),
+                paused_at: None,
+                note,
+                tags,
+                id: work_slice_id,
+            }),
+        )?,
+        Change::WorkSliceLogged {
+            project_id,
+            work_slice,
+        } => insert_work_slice(
+            transaction,
+            project_id,
+            &WorkSliceData::Complete(work_slice),
+        )?,
+        Change::WorkSliceEdited {
+            work_slice_id,
+            start,
+            end,
+            payment,
+            ..
+        } => {
+            transaction.execute(
+                "UPDATE work_slices SET start = ?2, end = ?3, payment = ?4 WHERE id = ?1",
+                params![work_slice_id, start, end, serde_json::to_string(&payment)?],
+            )?;
+        }
+        Change::WorkSliceAnnotated {
+            work_slice_id,
+            note,
+            tags,
+            ..
+        } => update_annotation(transaction, work_slice_id, &Annotation { note, tags })?,
+        Change::WorkSliceDeleted {
+            project_id,
+            work_slice,
+            deleted_at,
+        } => {
+            transaction.execute(
+                "DELETE FROM work_slices WHERE id = ?1",
+                params![work_slice.id()],
+            )?;
+            if let Some(deleted_at) = deleted_at {
+                insert_trash_entry(
+                    transaction,
+                    &TrashEntry {
+                        deleted_at,
+                        item: TrashedItem::WorkSlice {
+                            project_id,
+                            work_slice,
+                        },
+                    },
+                )?;
+            }
+        }
+        Change::WorkSliceRestored {
+            project_id,
+            work_slice,
+            deleted_at,
+        } => {
+            insert_work_slice(transaction, project_id, &work_slice)?;
+            if deleted_at.is_some() {
+                delete_trash_entry(
+                    transaction,
+                    &TrashedItem::WorkSlice {
+                        project_id,
+                        work_slice,
+                    },
+                )?;
+            }
+        }
+        Change::TrashPurged { entries } => {
+            for entry in &entries {
+                delete_trash_entry(transaction, &entry.item)?;
+            }
+        }
+        Change::TrashPurgeReverted { entries } => {
+            for entry in &entries {
+                insert_trash_entry(transaction, entry)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn insert_project(transaction: &Transaction, project: &ProjectData) -> Result<(), Box<dyn Error>> {
+    transaction.execute(
+        "INSERT INTO projects (id, name, description, default_payment, archived, client_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            project.id,
+            project.name,
+            project.description,
+            payment_to_json(project.default_payment)?,
+            project.archived,
+            project.client
+        ],
+    )?;
+    for work_slice in project
+        .work_slices
+        .iter()
+        .cloned()
+        .map(WorkSliceData::Complete)
+        .chain(
+            project
+                .current_slice
+                .iter()
+                .cloned()
+                .map(WorkSliceData::Incomplete),
+        )
+    {
+        insert_work_slice(transaction, project.id, &work_slice)?;
+    }
+    for payment in &project.received_payments {
+        insert_received_payment(transaction, project.id, payment)?;
+    }
+    Ok(())
+}
+
+fn insert_received_payment(
+    transaction: &Transaction,
+    project_id: u64,
+    payment: &ReceivedPaymentData,
+) -> Result<(), Box<dyn Error>> {
+    transaction.execute(
+        "INSERT INTO received_payments (id, project_id, amount, date, reference) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            payment.id,
+            project_id,
+            serde_json::to_string(&payment.amount)?,
+            payment.date,
+            payment.reference
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_client(transaction: &Transaction, client: &ClientData) -> Result<(), Box<dyn Error>> {
+    transaction.execute(
+        "INSERT INTO clients (id, name, contact, billing_address, default_payment) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            client.id,
+            client.name,
+            client.contact,
+            client.billing_address,
+            payment_to_json(client.default_payment)?
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_invoice(transaction: &Transaction, invoice: &InvoiceData) -> Result<(), Box<dyn Error>> {
+    transaction.execute(
+        "INSERT INTO invoices (number, client_id, issued, due, work_slices) VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            invoice.number,
+            invoice.client,
+            invoice.issued,
+            invoice.due,
+            serde_json::to_string(&invoice.work_slices)?
+        ],
+    )?;
+    Ok(())
+}
+
+fn insert_trash_entry(transaction: &Transaction, entry: &TrashEntry) -> Result<(), Box<dyn Error>> {
+    let (kind, id) = trash_key(&entry.item);
+    transaction.execute(
+        "INSERT INTO trash (kind, item_id, deleted_at, item) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            kind,
+            id,
+            entry.deleted_at,
+            serde_json::to_string(&entry.item)?
+        ],
+    )?;
+    Ok(())
+}
+
+fn delete_trash_entry(transaction: &Transaction, item: &TrashedItem) -> Result<(), Box<dyn Error>> {
+    let (kind, id) = trash_key(item);
+    transaction.execute(
+        "DELETE FROM trash WHERE kind = ?1 AND item_id = ?2",
+        params![kind, id],
+    )?;
+    Ok(())
+}
+
+/// Returns the key which a trashed item is stored under.
+fn trash_key(item: &TrashedItem) -> (&'static str, u64) {
+    match item {
+        TrashedItem::Project(project) => ("project", project.id),
+        TrashedItem::WorkSlice { work_slice, .. } => ("work_slice", work_slice.id()),
+    }
+}
+
+fn payment_to_json(payment: Option<Payment>) -> serde_json::Result<Option<String>> {
+    payment.map(|x| serde_json::to_string(&x)).transpose()
+}
+
+/// Adds a column to a table created by an older version of this schema.
+fn add_column_if_missing(
+    connection: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> rusqlite::Result<()> {
+    let exists: bool = connection.query_row(
+        "SELECT EXISTS (SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+        params![table, column],
+        |row| row.get(0),
+    )?;
+    if !exists {
+        connection.execute_batch(&format!(
+            "ALTER TABLE {table} ADD COLUMN {column} {definition}"
+        ))?;
+    }
+    Ok(())
+}
+
+fn insert_work_slice(
+    transaction: &Transaction,
+    project_id: u64,
+    work_slice: &WorkSliceData,
+) -> Result<(), Box<dyn Error>> {
+    let (start, end, payment, breaks, paused_at, note, tags) = match work_slice {
+        WorkSliceData::Complete(x) => (
+            x.start,
+            Some(x.end),
+            x.payment,
+            &x.breaks,
+            None,
+            &x.note,
+            &x.tags,
+        ),
+        WorkSliceData::Incomplete(x) => (
+            x.start,
+            None,
+            x.payment,
+            &x.breaks,
+            x.paused_at,
+            &x.note,
+            &x.tags,
+        ),
+    };
+    transaction.execute(
+        "INSERT INTO work_slices (id, project_id, start, end, payment, breaks, paused_at, note, tags) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        params![
+            work_slice.id(),
+            project_id,
+            start,
+            end,
+            serde_json::to_string(&payment)?,
+            serde_json::to_string(breaks)?,
+            paused_at,
+            note,
+            serde_json::to_string(tags)?
+        ],
+    )?;
+    Ok(())
+}
+
+/// Replaces the note and tags of a work slice.
+fn update_annotation(
+    transaction: &Transaction,
+    work_slice_id: u64,
+    annotation: &Annotation,
+) -> Result<(), Box<dyn Error>> {
+    transaction.execute(
+        "UPDATE work_slices SET note = ?2, tags = ?3 WHERE id = ?1",
+        params![
+            work_slice_id,
+            annotation.note,
+            serde_json::to_string(&annotation.tags)?
+        ],
+    )?;
+    Ok(())
+}
+
+/// Changes the breaks of a work slice, and when its current break started.
+fn update_breaks(
+    transaction: &Transaction,
+    work_slice_id: u64,
+    update: impl FnOnce(&mut Vec<Break>, &mut Option<DateTime<Utc>>),
+) -> Result<(), Box<dyn Error>> {
+    let (breaks, mut paused_at): (String, Option<DateTime<Utc>>) = transaction.query_row(
+        "SELECT breaks, paused_at FROM work_slices WHERE id = ?1",
+        params![work_slice_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let mut breaks: Vec<Break> = serde_json::from_str(&breaks)?;
+    update(&mut breaks, &mut paused_at);
+    transaction.execute(
+        "UPDATE work_slices SET breaks = ?2, paused_at = ?3 WHERE id = ?1",
+        params![work_slice_id, serde_json::to_string(&breaks)?, paused_at],
+    )?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use chrono::{TimeDelta, Utc};
+
+    use rusqlite::{params, Connection};
+
+    use crate::test_util::snapshot;
+    use crate::{
+        Annotation, ClientData, Currency, InvoiceData, MemoryStorage, Money, Payment,
+        SqliteStorage, State, StateData, Storage, StoredData,
+    };
+
+    fn clients(state: &State) -> Vec<ClientData> {
+        state.all_clients().map(ClientData::from).collect()
+    }
+
+    fn invoices(state: &State) -> Vec<InvoiceData> {
+        state.invoices().map(InvoiceData::from).collect()
+    }
+
+    #[test]
+    fn sqlite_storage_round_trip() {
+        let path = std::env::temp_dir().join(format!(
+            "track_work_sqlite_test_{}.sqlite",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let now = Utc::now();
+        let payment = Payment::Hourly(Money::new(1250, Currency::Gbp));
+
+        let mut state = State::new(SqliteStorage::open(&path).unwrap()).unwrap();
+        let first = state.new_project("first".to_string(), "".to_string(), Some(payment));
+        let second = state.new_project("second".to_string(), "".to_string(), None);
+        let third = state.new_project("third".to_string(), "".to_string(), Some(payment));
+        state
+            .start_work(
+                third,
+                None,
+                now - TimeDelta::hours(5),
+                Annotation::default(),
+            )
+            .unwrap();
+        state.pause_work(third, now - TimeDelta::hours(4)).unwrap();
+        let current = state
+            .project_from_id(third)
+            .unwrap()
+            .current_work_slice()
+            .unwrap()
+            .id();
+        state
+            .annotate_work_slice(
+                current,
+                Some("note".to_string()),
+                ["tag".to_string()].into_iter().collect(),
+            )
+            .unwrap();
+        state.save().unwrap();
+        state.resume_work(third, now - TimeDelta::hours(3)).unwrap();
+        state.pause_work(third, now - TimeDelta::hours(2)).unwrap();
+        state
+            .end_work(
+                third,
+                now - TimeDelta::hours(1),
+                Some(Annotation {
+                    note: Some("finished".to_string()),
+                    tags: BTreeSet::new(),
+                }),
+            )
+            .unwrap();
+        state.undo().unwrap();
+        state
+            .start_work(
+                first,
+                None,
+                now - TimeDelta::hours(3),
+                Annotation {
+                    note: Some("started".to_string()),
+                    tags: ["tag".to_string()].into_iter().collect(),
+                },
+            )
+            .unwrap();
+        state
+            .end_work(
+                first,
+                now - TimeDelta::hours(2),
+                Some(Annotation {
+                    note: Some("completed".to_string()),
+                    tags: ["tag".to_string(), "other".to_string()]
+                        .into_iter()
+                        .collect(),
+                }),
+            )
+            .unwrap();
+        state
+            .start_work(
+                first,
+                Some(payment),
+                now - TimeDelta::hours(1),
+                Annotation::default(),
+            )
+            .unwrap();
+        state.rename_project(second, "renamed".to_string()).unwrap();
+        let client = state.new_client(
+            "client".to_string(),
+            "client@example.com".to_string(),
+            "1 Street, Town".to_string(),
+            Some(payment),
+        );
+        state.set_project_client(second, Some(client)).unwrap();
+        let logged = state
+            .add_complete_work_slice(
+                second,
+                now - TimeDelta::hours(6),
+                now - TimeDelta::hours(5),
+                Some(payment),
+                Annotation {
+                    note: Some("logged".to_string()),
+                    tags: ["tag".to_string()].into_iter().collect(),
+                },
+            )
+            .unwrap();
+        state
+            .set_project_default_payment(second, Some(payment))
+            .unwrap();
+        state.archive_project(second).unwrap();
+        state
+            .record_received_payment(
+                second,
+                Money::new(1000, Currency::Gbp),
+                now,
+                "first payment".to_string(),
+            )
+            .unwrap();
+        state.save().unwrap();
+        state.delete_project(second);
+        state.undo().unwrap();
+        state.save().unwrap();
+        state
+            .create_invoice(Some(client), vec![logged], now, now + TimeDelta::days(30))
+            .unwrap();
+        let completed = state
+            .project_from_id(first)
+            .unwrap()
+            .complete_work_slices()
+            .next()
+            .unwrap()
+            .id();
+        let deleted_invoice = state
+            .create_invoice(None, vec![completed], now, now + TimeDelta::days(30))
+            .unwrap();
+        state.delete_invoice(deleted_invoice);
+        let mistake = state
+            .record_received_payment(first, Money::new(1, Currency::Gbp), now, "".to_string())
+            .unwrap();
+        state.delete_received_payment(mistake);
+        state
+            .record_received_payment(first, Money::new(500, Currency::Gbp), now, "".to_string())
+            .unwrap();
+        state.delete_project(first);
+        let expected = snapshot(&state);
+        let expected_clients = clients(&state);
+        let expected_invoices = invoices(&state);
+        let trash = state.trash().to_vec();
+        drop(state);
+
+        let mut state = State::new(SqliteStorage::open(&path).unwrap()).unwrap();
+        assert_eq!(snapshot(&state), expected);
+        assert_eq!(clients(&state), expected_clients);
+        assert_eq!(invoices(&state), expected_invoices);
+        assert_eq!(state.trash(), trash);
+        assert_eq!(state.previous_invoice_number(), deleted_invoice);
+        state.save_snapshot().unwrap();
+        drop(state);
+
+        let state = State::new(SqliteStorage::open(&path).unwrap()).unwrap();
+        assert_eq!(snapshot(&state), expected);
+        assert_eq!(clients(&state), expected_clients);
+        assert_eq!(invoices(&state), expected_invoices);
+        assert_eq!(state.trash(), trash);
+        assert_eq!(state.previous_invoice_number(), deleted_invoice);
+        drop(state);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn foreign_keys_are_enforced() {
+        let storage =
+            SqliteStorage::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        let result = storage.connection.execute(
+            "INSERT INTO work_slices (id, project_id, start, payment) VALUES (1, 1, ?1, '')",
+            params![Utc::now()],
+        );
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn projects_are_listed_in_the_same_order_as_other_storages() {
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        state.new_project("first".to_string(), "".to_string(), None);
+        state.new_project("second".to_string(), "".to_string(), None);
+        let mut data = StateData {
+            projects: snapshot(&state),
+            ..Default::default()
+        };
+        data.projects.reverse();
+
+        let memory = State::new(MemoryStorage::new(StoredData {
+            data: data.clone(),
+            changes: Vec::new(),
+        }))
+        .unwrap();
+        let mut storage =
+            SqliteStorage::from_connection(Connection::open_in_memory().unwrap()).unwrap();
+        storage.snapshot(data).unwrap();
+        let sqlite = State::new(storage).unwrap();
+        assert_eq!(snapshot(&memory), snapshot(&state));
+        assert_eq!(snapshot(&sqlite), snapshot(&state));
+    }
+}
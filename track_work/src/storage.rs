@@ -0,0 +1,740 @@
+use std::{cell::RefCell, error::Error, rc::Rc};
+
+#[cfg(feature = "serde")]
+use std::{
+    fs::File,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+#[cfg(feature = "serde")]
+use chrono::Utc;
+
+use crate::{Change, Client, Invoice, Project, StateData, TrashEntry};
+#[cfg(feature = "serde")]
+use crate::{ClientData, FileModifiedError, InvoiceData, ProjectData};
+
+/// Everything that a `Storage` has saved:
+/// a snapshot of all of the projects, clients, invoices and the trash, and the changes made after the snapshot was taken.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StoredData {
+    pub data: StateData,
+    pub changes: Vec<Change>,
+}
+impl StoredData {
+    /// Returns whether nothing has been saved: no data in the snapshot and no changes after it.
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty() && self.changes.is_empty()
+    }
+}
+
+/// Somewhere that a `State` can be loaded from and saved to,
+/// like a file or a database.
+pub trait Storage {
+    /// Loads everything which has been saved.
+    fn load(&mut self) -> Result<StoredData, Box<dyn Error>>;
+
+    /// Saves changes which have been made to a `State`.
+    /// `projects`, `clients`, `invoices`, `trash` and `previous_invoice_number` hold all of the projects,
+    /// clients, invoices, the trash and the highest invoice number used after the changes were made,
+    /// for storage which can't apply the changes one by one.
+    fn apply_changes(
+        &mut self,
+        changes: Vec<Change>,
+        projects: Vec<&Project>,
+        clients: &[Client],
+        invoices: &[Invoice],
+        trash: &[TrashEntry],
+        previous_invoice_number: u64,
+    ) -> Result<(), Box<dyn Error>>;
+
+    /// Overwrites everything which has been saved with the given projects, clients, invoices and trash.
+    fn snapshot(&mut self, data: StateData) -> Result<(), Box<dyn Error>>;
+}
+impl<S: Storage + ?Sized> Storage for Box<S> {
+    fn load(&mut self) -> Result<StoredData, Box<dyn Error>> {
+        (**self).load()
+    }
+
+    fn apply_changes(
+        &mut self,
+        changes: Vec<Change>,
+        projects: Vec<&Project>,
+        clients: &[Client],
+        invoices: &[Invoice],
+        trash: &[TrashEntry],
+        previous_invoice_number: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        (**self).apply_changes(
+            changes,
+            projects,
+            clients,
+            invoices,
+            trash,
+            previous_invoice_number,
+        )
+    }
+
+    fn snapshot(&mut self, data: StateData) -> Result<(), Box<dyn Error>> {
+        (**self).snapshot(data)
+    }
+}
+
+/// Stores data in memory, which is lost when the program ends.
+/// Clones of a `MemoryStorage` share the same data,
+/// so a clone can be used to look at what a `State` has saved.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    data: Rc<RefCell<StoredData>>,
+}
+impl MemoryStorage {
+    /// Returns a new storage which holds the given data.
+    pub fn new(data: StoredData) -> Self {
+        Self {
+            data: Rc::new(RefCell::new(data)),
+        }
+    }
+
+    /// Returns a copy of everything in this storage.
+    pub fn data(&self) -> StoredData {
+        self.data.borrow().clone()
+    }
+}
+impl Storage for MemoryStorage {
+    fn load(&mut self) -> Result<StoredData, Box<dyn Error>> {
+        Ok(self.data())
+    }
+
+    fn apply_changes(
+        &mut self,
+        changes: Vec<Change>,
+        _projects: Vec<&Project>,
+        _clients: &[Client],
+        _invoices: &[Invoice],
+        _trash: &[TrashEntry],
+        _previous_invoice_number: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        self.data.borrow_mut().changes.extend(changes);
+        Ok(())
+    }
+
+    fn snapshot(&mut self, data: StateData) -> Result<(), Box<dyn Error>> {
+        *self.data.borrow_mut() = StoredData {
+            data,
+            changes: Vec::new(),
+        };
+        Ok(())
+    }
+}
+
+/// Stores all projects, clients, invoices and the trash in a JSON file, which is rewritten every time changes are saved.
+/// Optionally, changes can instead be appended to a journal file
+/// (with one JSON-encoded change per line), which is folded back into the
+/// JSON file once it gets long enough.
+///
+/// The JSON file is replaced atomically (by writing to a temporary file and renaming it),
+/// and a lock file is held from loading until this storage is dropped,
+/// so that two programs can't load and save the same data at the same time.
+/// Saving fails if the files were changed by something else since they were loaded.
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+pub struct JsonFileStorage {
+    path: PathBuf,
+    journal: Option<Journal>,
+    backups: usize,
+    lock: Option<File>,
+    /// The version of the JSON file which was last loaded or saved, if it has been.
+    version: Option<FileVersion>,
+}
+#[cfg(feature = "serde")]
+#[derive(Debug)]
+struct Journal {
+    path: PathBuf,
+    compact_after: usize,
+    len: usize,
+    version: Option<FileVersion>,
+}
+/// Used to detect whether a file has been changed.
+#[cfg(feature = "serde")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileVersion {
+    len: u64,
+    modified: SystemTime,
+}
+#[cfg(feature = "serde")]
+impl FileVersion {
+    /// Returns the current version of a file, or `None` if it doesn't exist.
+    fn of(path: &Path) -> std::io::Result<Option<Self>> {
+        match std::fs::metadata(path) {
+            Ok(metadata) => Ok(Some(Self {
+                len: metadata.len(),
+                modified: metadata.modified()?,
+            })),
+            Err(x) if x.kind() == ErrorKind::NotFound => Ok(None),
+            Err(x) => Err(x),
+        }
+    }
+
+    /// Fails if the file at `path` isn't the same version as `expected`,
+    /// unless `expected` is `None`, in which case this always succeeds.
+    fn check(path: &Path, expected: Option<Self>) -> Result<(), FileModifiedError> {
+        match expected {
+            Some(expected) if FileVersion::of(path).ok().flatten() != Some(expected) => {
+                Err(FileModifiedError {
+                    path: path.to_path_buf(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+}
+#[cfg(feature = "serde")]
+impl JsonFileStorage {
+    /// Returns a storage which saves all projects, clients, invoices and the trash to the JSON file at `path`.
+    /// The file is created when loading if it doesn't exist.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            journal: None,
+            backups: 0,
+            lock: None,
+            version: None,
+        }
+    }
+
+    /// Makes this storage append changes to the journal file at `path`,
+    /// instead of rewriting the whole JSON file.
+    /// Once the journal holds `compact_after` changes, it is folded into the JSON file.
+    pub fn with_journal(self, path: impl Into<PathBuf>, compact_after: usize) -> Self {
+        Self {
+            journal: Some(Journal {
+                path: path.into(),
+                compact_after,
+                len: 0,
+                version: None,
+            }),
+            ..self
+        }
+    }
+
+    /// Makes this storage copy the JSON file to a timestamped backup before overwriting it,
+    /// keeping only the `count` most recent backups.
+    /// The backups are put next to the JSON file, with `.<timestamp>.bak` added to its name.
+    pub fn with_backups(self, count: usize) -> Self {
+        Self {
+            backups: count,
+            ..self
+        }
+    }
+
+    /// Waits until no other storage is using these files,
+    /// then stops any other storage from using them until this one is dropped.
+    fn lock(&mut self) -> std::io::Result<()> {
+        if self.lock.is_none() {
+            let file = File::create(Self::with_suffix(&self.path, ".lock"))?;
+            file.lock()?;
+            self.lock = Some(file);
+        }
+        Ok(())
+    }
+
+    fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+        let mut path = path.as_os_str().to_owned();
+        path.push(suffix);
+        PathBuf::from(path)
+    }
+
+    /// Replaces the JSON file, without leaving a partly written file if this fails.
+    fn write_data(&mut self, data: &StateData) -> Result<(), Box<dyn Error>> {
+        let temp_path = self.write_temp_data(data)?;
+        std::fs::rename(&temp_path, &self.path)?;
+        self.version = FileVersion::of(&self.path)?;
+        Ok(())
+    }
+
+    /// Writes the data which will replace the JSON file to a temporary file next to it,
+    /// and returns that file's path.
+    fn write_temp_data(&mut self, data: &StateData) -> Result<PathBuf, Box<dyn Error>> {
+        self.lock()?;
+        FileVersion::check(&self.path, self.version)?;
+        self.back_up()?;
+        let temp_path = Self::with_suffix(&self.path, ".tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(serde_json::to_string(data)?.as_bytes())?;
+        file.sync_all()?;
+        Ok(temp_path)
+    }
+
+    /// Finishes or undoes folding the journal into the JSON file, if that was interrupted.
+    /// While folding, the journal is moved aside between writing the new JSON file and replacing the old one with it,
+    /// so the temporary JSON file still being there means that the journal's changes aren't in the JSON file yet.
+    fn recover_compaction(&self, journal: &Path) -> std::io::Result<()> {
+        let compacting = Self::with_suffix(journal, ".compacting");
+        if !compacting.exists() {
+            return Ok(());
+        }
+        let temp_path = Self::with_suffix(&self.path, ".tmp");
+        if temp_path.exists() {
+            std::fs::rename(&compacting, journal)?;
+            std::fs::remove_file(&temp_path)
+        } else {
+            std::fs::remove_file(&compacting)
+        }
+    }
+
+    /// Copies the JSON file to a new backup, and deletes the oldest backups
+    /// so that there are at most `self.backups` of them.
+    fn back_up(&self) -> std::io::Result<()> {
+        if self.backups == 0 || !self.path.exists() {
+            return Ok(());
+        }
+        let timestamp = Utc::now().format("%Y%m%dT%H%M%S%.6fZ");
+        std::fs::copy(
+            &self.path,
+            Self::with_suffix(&self.path, &format!(".{timestamp}.bak")),
+        )?;
+
+        let directory = match self.path.parent() {
+            Some(x) if !x.as_os_str().is_empty() => x,
+            _ => Path::new("."),
+        };
+        let prefix = match self.path.file_name() {
+            Some(x) => format!("{}.", x.to_string_lossy()),
+            None => return Ok(()),
+        };
+        let mut backups: Vec<_> = std::fs::read_dir(directory)?
+            .filter_map(Result::ok)
+            .map(|x| x.file_name().to_string_lossy().into_owned())
+            .filter(|x| x.starts_with(&prefix) && x.ends_with(".bak"))
+            .collect();
+        backups.sort();
+        for old in &backups[..backups.len().saturating_sub(self.backups)] {
+            std::fs::remove_file(directory.join(old))?;
+        }
+        Ok(())
+    }
+}
+#[cfg(feature = "serde")]
+impl Storage for JsonFileStorage {
+    fn load(&mut self) -> Result<StoredData, Box<dyn Error>> {
+        self.lock()?;
+        if let Some(journal) = &self.journal {
+            self.recover_compaction(&journal.path)?;
+        }
+        let file = match std::fs::read_to_string(&self.path) {
+            Ok(x) => x,
+            Err(x) if x.kind() == ErrorKind::NotFound => {
+                let data = StateData::default();
+                self.write_data(&data)?;
+                serde_json::to_string(&data)?
+            }
+            Err(x) => return Err(Box::new(x)),
+        };
+        let data = serde_json::from_str(&file)?;
+        self.version = FileVersion::of(&self.path)?;
+
+        let mut changes = Vec::new();
+        if let Some(journal) = &mut self.journal {
+            let mut file = match std::fs::read_to_string(&journal.path) {
+                Ok(x) => x,
+                Err(x) if x.kind() == ErrorKind::NotFound => String::new(),
+                Err(x) => return Err(Box::new(x)),
+            };
+            // the last change may have only been partly written, if saving was interrupted,
+            // so it's removed before anything else is appended after it
+            let complete = file.rfind('\n').map_or(0, |i| i + 1);
+            if complete < file.len() {
+                std::fs::OpenOptions::new()
+                    .write(true)
+                    .open(&journal.path)?
+                    .set_len(complete as u64)?;
+                file.truncate(complete);
+            }
+            for line in file.lines().filter(|x| !x.trim().is_empty()) {
+                changes.push(serde_json::from_str(line)?);
+            }
+            journal.len = changes.len();
+            journal.version = FileVersion::of(&journal.path)?;
+        }
+
+        Ok(StoredData { data, changes })
+    }
+
+    fn apply_changes(
+        &mut self,
+        changes: Vec<Change>,
+        projects: Vec<&Project>,
+        clients: &[Client],
+        invoices: &[Invoice],
+        trash: &[TrashEntry],
+        previous_invoice_number: u64,
+    ) -> Result<(), Box<dyn Error>> {
+        self.lock()?;
+        match &mut self.journal {
+            Some(journal) if journal.len + changes.len() < journal.compact_after => {
+                FileVersion::check(&journal.path, journal.version)?;
+                let mut lines = String::new();
+                for change in &changes {
+                    lines += &serde_json::to_string(change)?;
+                    lines += "\n";
+                }
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&journal.path)?;
+                file.write_all(lines.as_bytes())?;
+                file.sync_all()?;
+                journal.len += changes.len();
+                journal.version = FileVersion::of(&journal.path)?;
+                Ok(())
+            }
+            _ => self.snapshot(StateData {
+                projects: projects.into_iter().map(ProjectData::from).collect(),
+                clients: clients.iter().map(ClientData::from).collect(),
+                invoices: invoices.iter().map(InvoiceData::from).collect(),
+                trash: trash.to_vec(),
+                previous_invoice_number,
+            }),
+        }
+    }
+
+    fn snapshot(&mut self, data: StateData) -> Result<(), Box<dyn Error>> {
+        let Some(journal) = &self.journal else {
+            return self.write_data(&data);
+        };
+        FileVersion::check(&journal.path, journal.version)?;
+        let journal_path = journal.path.clone();
+        let compacting = Self::with_suffix(&journal_path, ".compacting");
+        let temp_path = self.write_temp_data(&data)?;
+
+        // if this is interrupted, `load` uses which files are left to tell whether the journal was folded in
+        match std::fs::rename(&journal_path, &compacting) {
+            Err(x) if x.kind() != ErrorKind::NotFound => return Err(Box::new(x)),
+            _ => {}
+        }
+        std::fs::rename(&temp_path, &self.path)?;
+        self.version = FileVersion::of(&self.path)?;
+        std::fs::write(&journal_path, "")?;
+        if let Some(journal) = &mut self.journal {
+            journal.len = 0;
+            journal.version = FileVersion::of(&journal_path)?;
+        }
+        match std::fs::remove_file(&compacting) {
+            Err(x) if x.kind() != ErrorKind::NotFound => Err(Box::new(x)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use std::{cell::RefCell, error::Error, rc::Rc};
+
+    use crate::test_util::snapshot;
+    use crate::{
+        Annotation, Change, Client, Currency, Invoice, InvoiceData, MemoryStorage, Money, Payment,
+        Project, State, StateData, Storage, StoredData, TrashEntry,
+    };
+    #[cfg(feature = "serde")]
+    use crate::{FileModifiedError, JsonFileStorage};
+
+    #[test]
+    fn memory_storage_round_trip() {
+        let storage = MemoryStorage::default();
+        let mut state = State::new(storage.clone()).unwrap();
+        let project = state.new_project("project".to_string(), "".to_string(), None);
+        state
+            .start_work(
+                project,
+                Some(Payment::Hourly(Money::new(1000, Currency::Gbp))),
+                Utc::now() - TimeDelta::hours(1),
+                Annotation::default(),
+            )
+            .unwrap();
+        let expected = snapshot(&state);
+        drop(state);
+        assert_eq!(storage.data().changes.len(), 2);
+
+        let mut state = State::new(storage.clone()).unwrap();
+        assert_eq!(snapshot(&state), expected);
+
+        state.save_snapshot().unwrap();
+        assert!(storage.data().changes.is_empty());
+        assert_eq!(storage.data().data.projects, expected);
+    }
+
+    #[test]
+    fn stored_data_with_only_invoices_is_not_empty() {
+        assert!(StoredData::default().is_empty());
+
+        let mut stored = StoredData::default();
+        stored.data.invoices.push(InvoiceData {
+            number: 1,
+            client: None,
+            issued: Utc::now(),
+            due: Utc::now(),
+            work_slices: Vec::new(),
+        });
+        assert!(!stored.is_empty());
+
+        let mut stored = StoredData::default();
+        stored.data.previous_invoice_number = 3;
+        assert!(!stored.is_empty());
+    }
+
+    /// A storage which can't save anything.
+    struct FailingStorage;
+    impl Storage for FailingStorage {
+        fn load(&mut self) -> Result<StoredData, Box<dyn Error>> {
+            Ok(StoredData::default())
+        }
+
+        fn apply_changes(
+            &mut self,
+            _changes: Vec<Change>,
+            _projects: Vec<&Project>,
+            _clients: &[Client],
+            _invoices: &[Invoice],
+            _trash: &[TrashEntry],
+            _previous_invoice_number: u64,
+        ) -> Result<(), Box<dyn Error>> {
+            Err("storage is read only".into())
+        }
+
+        fn snapshot(&mut self, _data: StateData) -> Result<(), Box<dyn Error>> {
+            Err("storage is read only".into())
+        }
+    }
+
+    #[test]
+    fn dropping_a_state_reports_save_errors() {
+        let errors = Rc::new(RefCell::new(Vec::new()));
+        let mut state = State::new(FailingStorage).unwrap();
+        let handler_errors = errors.clone();
+        state.on_drop_save_error(move |err| handler_errors.borrow_mut().push(err.to_string()));
+        state.new_project("project".to_string(), "".to_string(), None);
+        drop(state);
+        assert_eq!(*errors.borrow(), ["storage is read only"]);
+
+        // without a handler, the error is only printed
+        let mut state = State::new(FailingStorage).unwrap();
+        state.new_project("project".to_string(), "".to_string(), None);
+        drop(state);
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn json_storage_backups_and_modification() {
+        let directory =
+            std::env::temp_dir().join(format!("track_work_json_test_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir(&directory).unwrap();
+        let path = directory.join("save.json");
+
+        let mut state = State::new(JsonFileStorage::new(&path).with_backups(2)).unwrap();
+        for i in 0..4 {
+            state.new_project(format!("project {i}"), "".to_string(), None);
+            state.save().unwrap();
+        }
+        let backups = std::fs::read_dir(&directory)
+            .unwrap()
+            .filter(|x| x.as_ref().unwrap().path().extension().unwrap() == "bak")
+            .count();
+        assert_eq!(backups, 2);
+
+        std::fs::write(&path, "[]").unwrap();
+        state.new_project("lost".to_string(), "".to_string(), None);
+        let err = state.save().unwrap_err();
+        assert!(err.downcast_ref::<FileModifiedError>().is_some());
+        state.handle_changes();
+        drop(state);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn json_storage_loads_list_of_projects() {
+        let path = std::env::temp_dir().join(format!(
+            "track_work_json_list_test_{}.json",
+            std::process::id()
+        ));
+        std::fs::write(
+            &path,
+            r#"[{"name":"old","description":"","work_slices":[],"current_slice":null,"id":3}]"#,
+        )
+        .unwrap();
+
+        let mut state = State::new(JsonFileStorage::new(&path)).unwrap();
+        assert_eq!(state.all_projects().count(), 1);
+        assert!(state.trash().is_empty());
+        let project = state.all_project_ids().next().unwrap();
+        state.delete_project(project);
+        state.save().unwrap();
+        drop(state);
+
+        let state = State::new(JsonFileStorage::new(&path)).unwrap();
+        assert_eq!(state.all_projects().count(), 0);
+        assert_eq!(state.trash().len(), 1);
+        drop(state);
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(path.with_extension("json.lock"));
+    }
+
+    /// Returns a new empty directory for a test to put its files in.
+    #[cfg(feature = "serde")]
+    fn test_directory(name: &str) -> std::path::PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("track_work_{name}_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        std::fs::create_dir(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn json_storage_journal_round_trip() {
+        let directory = test_directory("journal_test");
+        let path = directory.join("save.json");
+        let journal = directory.join("journal.jsonl");
+        let storage = || JsonFileStorage::new(&path).with_journal(&journal, 3);
+
+        let mut state = State::new(storage()).unwrap();
+        let project = state.new_project("first".to_string(), "".to_string(), None);
+        state.save().unwrap();
+        state
+            .rename_project(project, "renamed".to_string())
+            .unwrap();
+        state.save().unwrap();
+        let expected = snapshot(&state);
+        drop(state);
+
+        // the changes are only in the journal, which is replayed when loading
+        let data: StateData =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(data.projects.is_empty());
+        assert_eq!(
+            std::fs::read_to_string(&journal).unwrap().lines().count(),
+            2
+        );
+        let mut state = State::new(storage()).unwrap();
+        assert_eq!(snapshot(&state), expected);
+
+        // the third change fills the journal, so it's folded into the JSON file
+        state.new_project("second".to_string(), "".to_string(), None);
+        state.save().unwrap();
+        let expected = snapshot(&state);
+        drop(state);
+        let data: StateData =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(data.projects, expected);
+        assert_eq!(std::fs::read_to_string(&journal).unwrap(), "");
+        assert_eq!(snapshot(&State::new(storage()).unwrap()), expected);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn json_storage_removes_partly_written_change() {
+        let directory = test_directory("torn_journal_test");
+        let path = directory.join("save.json");
+        let journal = directory.join("journal.jsonl");
+        let storage = || JsonFileStorage::new(&path).with_journal(&journal, 10);
+
+        let mut state = State::new(storage()).unwrap();
+        state.new_project("first".to_string(), "".to_string(), None);
+        state.save().unwrap();
+        drop(state);
+        let saved = std::fs::read_to_string(&journal).unwrap();
+        std::fs::write(&journal, format!("{saved}{{\"ProjectCreated\":{{\"na")).unwrap();
+
+        // the partly written change is ignored, and removed so the next change starts on its own line
+        let mut state = State::new(storage()).unwrap();
+        assert_eq!(std::fs::read_to_string(&journal).unwrap(), saved);
+        state.new_project("second".to_string(), "".to_string(), None);
+        state.save().unwrap();
+        let expected = snapshot(&state);
+        drop(state);
+        assert_eq!(
+            std::fs::read_to_string(&journal).unwrap().lines().count(),
+            2
+        );
+        assert_eq!(snapshot(&State::new(storage()).unwrap()), expected);
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    /// Saves two changes to the journal, and returns the expected projects and the journal's contents.
+    #[cfg(feature = "serde")]
+    fn journal_before_compaction(
+        storage: impl Fn() -> JsonFileStorage,
+        journal: &std::path::Path,
+    ) -> (Vec<crate::ProjectData>, String) {
+        let mut state = State::new(storage()).unwrap();
+        let project = state.new_project("first".to_string(), "".to_string(), None);
+        state.save().unwrap();
+        state
+            .rename_project(project, "renamed".to_string())
+            .unwrap();
+        state.save().unwrap();
+        (snapshot(&state), std::fs::read_to_string(journal).unwrap())
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn interrupted_compaction_before_replacing_data_keeps_journal() {
+        let directory = test_directory("compaction_before_test");
+        let path = directory.join("save.json");
+        let journal = directory.join("journal.jsonl");
+        let storage = || JsonFileStorage::new(&path).with_journal(&journal, 3);
+        let (expected, journal_contents) = journal_before_compaction(storage, &journal);
+
+        // the new JSON file was written and the journal moved aside, but the old JSON file wasn't replaced
+        std::fs::rename(&journal, directory.join("journal.jsonl.compacting")).unwrap();
+        std::fs::write(directory.join("save.json.tmp"), "{\"projects\":[").unwrap();
+        assert_eq!(snapshot(&State::new(storage()).unwrap()), expected);
+        assert_eq!(std::fs::read_to_string(&journal).unwrap(), journal_contents);
+        assert!(!directory.join("journal.jsonl.compacting").exists());
+        assert!(!directory.join("save.json.tmp").exists());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn interrupted_compaction_after_replacing_data_discards_journal() {
+        let directory = test_directory("compaction_after_test");
+        let path = directory.join("save.json");
+        let journal = directory.join("journal.jsonl");
+        let storage = || JsonFileStorage::new(&path).with_journal(&journal, 3);
+        let (expected, journal_contents) = journal_before_compaction(storage, &journal);
+        State::new(storage()).unwrap().save_snapshot().unwrap();
+
+        // the JSON file was replaced, but the old journal wasn't removed
+        std::fs::remove_file(&journal).unwrap();
+        std::fs::write(directory.join("journal.jsonl.compacting"), journal_contents).unwrap();
+        assert_eq!(snapshot(&State::new(storage()).unwrap()), expected);
+        assert_eq!(std::fs::read_to_string(&journal).unwrap_or_default(), "");
+        assert!(!directory.join("journal.jsonl.compacting").exists());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn corrupt_data_reports_parse_error() {
+        let err = serde_json::from_str::<StateData>(r#"{"projects":[{"name":"half"#).unwrap_err();
+        assert!(err.is_eof(), "{err}");
+        let err = serde_json::from_str::<StateData>(r#"[{"name":3}]"#).unwrap_err();
+        assert!(
+            err.to_string().contains("invalid type: integer `3`"),
+            "{err}"
+        );
+        let err = serde_json::from_str::<StateData>(r#"{"projects":[],"trash":{}}"#).unwrap_err();
+        assert!(err.to_string().contains("invalid type: map"), "{err}");
+    }
+}
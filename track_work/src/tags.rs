@@ -0,0 +1,308 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use chrono::TimeDelta;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{AnnotateWorkSliceError, Change, MoneyTotals, State, WorkSliceId};
+
+/// A note and tags which describe what a work slice is for.
+/// These can be given when work is started, completed or logged, or changed later.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Annotation {
+    pub note: Option<String>,
+    pub tags: BTreeSet<String>,
+}
+
+/// The work done with a certain tag, added up over all complete work slices which have it.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TagTotal {
+    /// How many complete work slices have the tag.
+    pub work_slices: usize,
+    /// The time worked, not including breaks.
+    pub duration: TimeDelta,
+    pub payment: MoneyTotals,
+}
+
+impl State {
+    /// Replaces the note and tags of a work slice, which can be complete or ongoing.
+    /// Fails if the work slice doesn't exist, or if it's on an invoice.
+    pub fn annotate_work_slice(
+        &mut self,
+        id: WorkSliceId,
+        note: Option<String>,
+        tags: BTreeSet<String>,
+    ) -> Result<(), AnnotateWorkSliceError> {
+        let project_id = self
+            .project_id_from_work_slice(id)
+            .ok_or(AnnotateWorkSliceError::WorkSliceNotFound)?;
+        if self.is_invoiced(id) {
+            return Err(AnnotateWorkSliceError::Invoiced);
+        }
+        let (old_note, old_tags) = self
+            .project_from_id_mut(project_id)
+            .and_then(|x| x.annotate_work_slice(id, note.clone(), tags.clone()))
+            .ok_or(AnnotateWorkSliceError::WorkSliceNotFound)?;
+        if (&old_note, &old_tags) != (&note, &tags) {
+            self.record_change(Change::WorkSliceAnnotated {
+                project_id: unsafe { project_id.inner() },
+                work_slice_id: unsafe { id.inner() },
+                old_note,
+                old_tags,
+                note,
+                tags,
+            });
+        }
+        Ok(())
+    }
+
+    /// Adds up the complete work slices with each tag, in all projects.
+    /// Work slices with several tags are counted towards each of them.
+    pub fn tag_totals(&self) -> BTreeMap<String, TagTotal> {
+        let mut totals = BTreeMap::<String, TagTotal>::new();
+        for work_slice in self.all_projects().flat_map(|x| x.complete_work_slices()) {
+            for tag in work_slice.tags() {
+                let total = totals.entry(tag.clone()).or_default();
+                total.work_slices += 1;
+                total.duration += work_slice.duration();
+                total.payment = std::mem::take(&mut total.payment) + work_slice.calculate_payment();
+            }
+        }
+        totals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use chrono::{DateTime, TimeDelta, Utc};
+
+    use crate::test_util::{assert_replays, snapshot};
+    use crate::{
+        AnnotateWorkSliceError, Annotation, Change, Currency, MemoryStorage, Money, Payment,
+        ProjectId, State, WorkSliceId,
+    };
+
+    fn tags(tags: &[&str]) -> BTreeSet<String> {
+        tags.iter().map(|x| x.to_string()).collect()
+    }
+
+    fn annotation(note: &str, tags: &[&str]) -> Annotation {
+        Annotation {
+            note: Some(note.to_string()),
+            tags: self::tags(tags),
+        }
+    }
+
+    /// Creates a project with a default payment, and returns its ID.
+    fn new_project(state: &mut State) -> ProjectId {
+        let payment = Payment::Hourly(Money::new(1000, Currency::Gbp));
+        state.new_project("project".to_string(), "".to_string(), Some(payment))
+    }
+
+    /// Returns a state with a project which has two complete work slices
+    /// and one ongoing one, none of which are annotated.
+    fn project_with_work(now: DateTime<Utc>) -> (State, ProjectId, [WorkSliceId; 3]) {
+        let hours = TimeDelta::hours;
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let project = new_project(&mut state);
+        let first = state
+            .add_complete_work_slice(
+                project,
+                now - hours(5),
+                now - hours(4),
+                None,
+                Annotation::default(),
+            )
+            .unwrap();
+        let second = state
+            .add_complete_work_slice(
+                project,
+                now - hours(3),
+                now - hours(1),
+                None,
+                Annotation::default(),
+            )
+            .unwrap();
+        state
+            .start_work(project, None, now - hours(1), Annotation::default())
+            .unwrap();
+        let current = state
+            .project_from_id(project)
+            .unwrap()
+            .current_work_slice()
+            .unwrap()
+            .id();
+        (state, project, [first, second, current])
+    }
+
+    #[test]
+    fn annotate_work_slices() {
+        let (mut state, _, [first, _, current]) = project_with_work(Utc::now());
+        let unannotated = snapshot(&state);
+        state
+            .annotate_work_slice(first, Some("meeting".to_string()), tags(&["admin"]))
+            .unwrap();
+        state
+            .annotate_work_slice(current, None, tags(&["design"]))
+            .unwrap();
+        assert_eq!(
+            state.annotate_work_slice(unsafe { WorkSliceId::new(100) }, None, tags(&[])),
+            Err(AnnotateWorkSliceError::WorkSliceNotFound)
+        );
+        let work_slice = state.work_slice_from_id(first).unwrap();
+        assert_eq!(work_slice.note(), Some("meeting"));
+        assert_eq!(work_slice.tags(), &tags(&["admin"]));
+        assert_replays(&mut state);
+
+        state.undo().unwrap();
+        state.undo().unwrap();
+        assert_eq!(snapshot(&state), unannotated);
+    }
+
+    #[test]
+    fn tag_totals_add_up_complete_work() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let (mut state, project, [first, second, current]) = project_with_work(now);
+        state
+            .annotate_work_slice(first, None, tags(&["admin"]))
+            .unwrap();
+        state
+            .annotate_work_slice(second, None, tags(&["admin", "design"]))
+            .unwrap();
+        state
+            .annotate_work_slice(current, None, tags(&["design"]))
+            .unwrap();
+
+        let totals = state.tag_totals();
+        assert_eq!(totals.keys().collect::<Vec<_>>(), ["admin", "design"]);
+        assert_eq!(totals["admin"].work_slices, 2);
+        assert_eq!(totals["admin"].duration, hours(3));
+        assert_eq!(
+            totals["admin"].payment.get(Currency::Gbp),
+            Money::new(3000, Currency::Gbp).into()
+        );
+        // the ongoing work isn't counted until it's complete
+        assert_eq!(totals["design"].work_slices, 1);
+        assert_eq!(totals["design"].duration, hours(2));
+        state.end_work(project, now, None).unwrap();
+        assert_eq!(state.tag_totals()["design"].work_slices, 2);
+    }
+
+    #[test]
+    fn annotations_given_with_work_are_recorded_with_it() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let project = new_project(&mut state);
+        let logged = state
+            .add_complete_work_slice(
+                project,
+                now - hours(5),
+                now - hours(4),
+                None,
+                annotation("logged", &["admin"]),
+            )
+            .unwrap();
+        state
+            .start_work(
+                project,
+                None,
+                now - hours(2),
+                annotation("started", &["design"]),
+            )
+            .unwrap();
+        let current = state
+            .project_from_id(project)
+            .unwrap()
+            .current_work_slice()
+            .unwrap();
+        assert_eq!(current.note(), Some("started"));
+        assert_eq!(current.tags(), &tags(&["design"]));
+        let current = current.id();
+        state
+            .end_work(
+                project,
+                now - hours(1),
+                Some(annotation("completed", &["design", "review"])),
+            )
+            .unwrap();
+
+        let work_slice = state.work_slice_from_id(logged).unwrap();
+        assert_eq!(work_slice.note(), Some("logged"));
+        assert_eq!(work_slice.tags(), &tags(&["admin"]));
+        let work_slice = state.work_slice_from_id(current).unwrap();
+        assert_eq!(work_slice.note(), Some("completed"));
+        assert_eq!(work_slice.tags(), &tags(&["design", "review"]));
+        // creating the project, then logging, starting and completing work
+        assert_eq!(state.undo_history().len(), 4);
+        assert_replays(&mut state);
+    }
+
+    #[test]
+    fn annotations_given_with_work_are_undone_in_one_step() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let project = new_project(&mut state);
+        let mut snapshots = vec![snapshot(&state)];
+        state
+            .add_complete_work_slice(
+                project,
+                now - hours(5),
+                now - hours(4),
+                None,
+                annotation("logged", &["admin"]),
+            )
+            .unwrap();
+        snapshots.push(snapshot(&state));
+        state
+            .start_work(
+                project,
+                None,
+                now - hours(2),
+                annotation("started", &["design"]),
+            )
+            .unwrap();
+        snapshots.push(snapshot(&state));
+        state
+            .end_work(
+                project,
+                now - hours(1),
+                Some(annotation("completed", &["review"])),
+            )
+            .unwrap();
+
+        while let Some(expected) = snapshots.pop() {
+            state.undo().unwrap();
+            assert_eq!(snapshot(&state), expected);
+        }
+    }
+
+    #[test]
+    fn unchanged_annotations_are_not_recorded_when_completing_work() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let project = new_project(&mut state);
+        let started = annotation("started", &["design"]);
+        state
+            .start_work(project, None, now - hours(2), started.clone())
+            .unwrap();
+        state.handle_changes();
+        state
+            .end_work(project, now - hours(1), Some(started))
+            .unwrap();
+        assert!(matches!(
+            state.handle_changes()[..],
+            [Change::WorkSliceCompleted {
+                old_annotation: None,
+                annotation: None,
+                ..
+            }]
+        ));
+    }
+}
@@ -0,0 +1,17 @@
+//! Helpers shared by the tests of several modules.
+
+use crate::{MemoryStorage, ProjectData, State};
+
+/// Returns the data of every project, for comparing states.
+pub(crate) fn snapshot(state: &State) -> Vec<ProjectData> {
+    state.all_projects().map(ProjectData::from).collect()
+}
+
+/// Replays the changes which haven't been handled yet into a new state,
+/// checks that it has the same projects as `state`, and returns it.
+pub(crate) fn assert_replays(state: &mut State) -> State {
+    let changes = state.handle_changes();
+    let replayed = State::from_changes(changes, MemoryStorage::default()).unwrap();
+    assert_eq!(snapshot(&replayed), snapshot(state));
+    replayed
+}
@@ -0,0 +1,285 @@
+use chrono::{DateTime, TimeDelta, Utc};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Change, InvalidChangeError, ProjectData, ProjectId, RestoreError, State, WorkSliceData,
+    WorkSliceId,
+};
+
+/// Something which was deleted, and can be restored from the trash.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TrashedItem {
+    /// A project, with all of the work slices it had when it was deleted.
+    Project(ProjectData),
+    /// A work slice, which is restored to the project with the ID `project_id`.
+    WorkSlice {
+        project_id: u64,
+        work_slice: WorkSliceData,
+    },
+}
+
+/// Returns true if a change deletes, restores or purges the given item.
+fn refers_to(change: &Change, item: &TrashedItem) -> bool {
+    match change {
+        Change::ProjectDeleted { project, .. } | Change::ProjectRestored { project, .. } => {
+            matches!(item, TrashedItem::Project(x) if x.id == project.id)
+        }
+        Change::WorkSliceDeleted { work_slice, .. }
+        | Change::WorkSliceRestored { work_slice, .. } => matches!(
+            item,
+            TrashedItem::WorkSlice { work_slice: x, .. } if x.id() == work_slice.id()
+        ),
+        Change::TrashPurged { entries } | Change::TrashPurgeReverted { entries } => {
+            entries.iter().any(|x| x.item == *item)
+        }
+        _ => false,
+    }
+}
+
+/// An item in the trash, and when it was deleted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TrashEntry {
+    pub deleted_at: DateTime<Utc>,
+    pub item: TrashedItem,
+}
+
+impl State {
+    /// Returns everything in the trash, in the order it was deleted.
+    pub fn trash(&self) -> &[TrashEntry] {
+        &self.trash
+    }
+
+    /// Puts a deleted project back, with its original ID and all of its work slices.
+    /// Fails if the project isn't in the trash.
+    pub fn restore_project(&mut self, id: ProjectId) -> Result<(), RestoreError> {
+        let id = unsafe { id.inner() };
+        let entry = self
+            .trash
+            .iter()
+            .find(|x| matches!(&x.item, TrashedItem::Project(project) if project.id == id))
+            .ok_or(RestoreError::NotInTrash)?;
+        let deleted_at = Some(entry.deleted_at);
+        let TrashedItem::Project(project) = entry.item.clone() else {
+            unreachable!()
+        };
+        self.apply_and_record(Change::ProjectRestored {
+            project,
+            deleted_at,
+        })
+    }
+
+    /// Puts a deleted work slice back into its project, with its original ID.
+    /// Fails if the work slice isn't in the trash, or if it can't be put back,
+    /// e.g. because its project has been deleted too.
+    pub fn restore_work_slice(&mut self, id: WorkSliceId) -> Result<(), RestoreError> {
+        let id = unsafe { id.inner() };
+        let entry = self
+            .trash
+            .iter()
+            .find(|x| {
+                matches!(&x.item, TrashedItem::WorkSlice { work_slice, .. } if work_slice.id() == id)
+            })
+            .ok_or(RestoreError::NotInTrash)?;
+        let deleted_at = Some(entry.deleted_at);
+        let TrashedItem::WorkSlice {
+            project_id,
+            work_slice,
+        } = entry.item.clone()
+        else {
+            unreachable!()
+        };
+        self.apply_and_record(Change::WorkSliceRestored {
+            project_id,
+            work_slice,
+            deleted_at,
+        })
+    }
+
+    /// Permanently deletes everything in the trash, and returns how many items were deleted.
+    pub fn purge_trash(&mut self) -> usize {
+        let entries = std::mem::take(&mut self.trash);
+        let count = entries.len();
+        if count > 0 {
+            self.record_change(Change::TrashPurged { entries });
+        }
+        count
+    }
+
+    /// Permanently deletes everything which was put in the trash before `time`,
+    /// and returns how many items were deleted.
+    pub fn purge_trash_before(&mut self, time: DateTime<Utc>) -> usize {
+        let entries = self.take_trash_before(time);
+        let count = entries.len();
+        if count > 0 {
+            self.record_change(Change::TrashPurged { entries });
+        }
+        count
+    }
+
+    /// Permanently deletes everything which has been in the trash for longer than `retention`,
+    /// and returns how many items were deleted.
+    /// Unlike `purge_trash_before`, this isn't added to the undo history,
+    /// so it can be run automatically without getting in the way of undoing other changes.
+    /// Changes in the undo or redo history which refer to deleted items can't be undone or redone any more,
+    /// so they are removed from the history, along with every change that would have to be undone or redone after them.
+    pub fn expire_trash(&mut self, retention: TimeDelta) -> usize {
+        let entries = self.take_trash_before(Utc::now() - retention);
+        let count = entries.len();
+        if count > 0 {
+            for history in [&mut self.undo_history, &mut self.redo_history] {
+                if let Some(index) = history
+                    .iter()
+                    .rposition(|x| entries.iter().any(|e| refers_to(x, &e.item)))
+                {
+                    history.drain(..=index);
+                }
+            }
+            self.changes.push(Change::TrashPurged { entries });
+        }
+        count
+    }
+
+    fn take_trash_before(&mut self, time: DateTime<Utc>) -> Vec<TrashEntry> {
+        let (entries, kept) = std::mem::take(&mut self.trash)
+            .into_iter()
+            .partition(|x| x.deleted_at < time);
+        self.trash = kept;
+        entries
+    }
+
+    /// Adds an entry to the trash, keeping the trash in the order things were deleted.
+    pub(crate) fn add_to_trash(&mut self, entry: TrashEntry) {
+        let index = self
+            .trash
+            .iter()
+            .position(|x| x.deleted_at > entry.deleted_at)
+            .unwrap_or(self.trash.len());
+        self.trash.insert(index, entry);
+    }
+
+    /// Removes the trash entry holding the project with this ID,
+    /// or the work slice with this ID if `project` is false.
+    pub(crate) fn take_from_trash(
+        &mut self,
+        id: u64,
+        project: bool,
+    ) -> Result<TrashEntry, InvalidChangeError> {
+        let index = self
+            .trash
+            .iter()
+            .position(|x| match &x.item {
+                TrashedItem::Project(data) => project && data.id == id,
+                TrashedItem::WorkSlice { work_slice, .. } => !project && work_slice.id() == id,
+            })
+            .ok_or(InvalidChangeError::NotInTrash)?;
+        Ok(self.trash.remove(index))
+    }
+
+    fn apply_and_record(&mut self, change: Change) -> Result<(), RestoreError> {
+        self.apply_change(change.clone())
+            .map_err(RestoreError::Conflict)?;
+        self.record_change(change);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeDelta, Utc};
+
+    use crate::test_util::{assert_replays, snapshot};
+    use crate::{
+        Annotation, Currency, HistoryError, InvalidChangeError, MemoryStorage, Money, Payment,
+        ProjectId, RestoreError, State, WorkSliceId,
+    };
+
+    /// Returns a state with two projects, the first of which has a complete work slice.
+    fn projects_with_work() -> (State, [ProjectId; 2], WorkSliceId) {
+        let now = Utc::now();
+        let payment = Payment::Hourly(Money::new(1000, Currency::Gbp));
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let project = state.new_project("project".to_string(), "".to_string(), None);
+        let other = state.new_project("other".to_string(), "".to_string(), None);
+        let work_slice = state
+            .add_complete_work_slice(
+                project,
+                now - TimeDelta::hours(2),
+                now - TimeDelta::hours(1),
+                Some(payment),
+                Annotation::default(),
+            )
+            .unwrap();
+        (state, [project, other], work_slice)
+    }
+
+    #[test]
+    fn delete_and_restore_from_trash() {
+        let (mut state, [project, other], work_slice) = projects_with_work();
+        let original = snapshot(&state);
+
+        assert!(state.delete_work_slice(work_slice));
+        assert!(state.delete_project(project));
+        assert!(state.delete_project(other));
+        assert_eq!(state.trash().len(), 3);
+        assert_eq!(
+            state.restore_work_slice(work_slice),
+            Err(RestoreError::Conflict(InvalidChangeError::ProjectNotFound))
+        );
+        state.restore_project(other).unwrap();
+        state.restore_project(project).unwrap();
+        state.restore_work_slice(work_slice).unwrap();
+        assert_eq!(
+            state.restore_project(project),
+            Err(RestoreError::NotInTrash)
+        );
+        assert_eq!(snapshot(&state), original);
+        assert!(state.trash().is_empty());
+    }
+
+    #[test]
+    fn purge_and_expire_replayed_trash() {
+        let (mut state, [_, other], _) = projects_with_work();
+        state.delete_project(other);
+        let mut replayed = assert_replays(&mut state);
+        assert_eq!(replayed.trash(), state.trash());
+        assert_eq!(
+            replayed.purge_trash_before(Utc::now() - TimeDelta::hours(1)),
+            0
+        );
+        assert_eq!(replayed.purge_trash(), 1);
+        replayed.undo().unwrap();
+        assert_eq!(replayed.trash(), state.trash());
+        assert_eq!(replayed.expire_trash(TimeDelta::zero()), 1);
+        assert!(replayed.trash().is_empty());
+    }
+
+    #[test]
+    fn expiring_trash_drops_history_which_refers_to_it() {
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let project = state.new_project("project".to_string(), "".to_string(), None);
+        state.delete_project(project);
+        let other = state.new_project("other".to_string(), "".to_string(), None);
+        state.delete_project(other);
+        state.undo().unwrap();
+        assert_eq!(state.expire_trash(TimeDelta::zero()), 1);
+        assert!(state.trash().is_empty());
+        // only creating `other` doesn't depend on the expired project
+        assert_eq!(state.undo_history().len(), 1);
+        assert_eq!(state.redo_history().len(), 1);
+        state.redo().unwrap();
+        state.undo().unwrap();
+        state.undo().unwrap();
+        assert_eq!(state.undo(), Err(HistoryError::NothingToUndo));
+
+        let again = state.new_project("again".to_string(), "".to_string(), None);
+        state.delete_project(again);
+        state.restore_project(again).unwrap();
+        state.undo().unwrap();
+        assert_eq!(state.expire_trash(TimeDelta::zero()), 1);
+        assert_eq!(state.redo(), Err(HistoryError::NothingToRedo));
+        assert_eq!(state.undo(), Err(HistoryError::NothingToUndo));
+    }
+}
@@ -1,6 +1,13 @@
+use std::collections::BTreeSet;
+
 use chrono::{DateTime, TimeDelta, Utc};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
-use crate::payment::{MoneyExact, Payment};
+use crate::{
+    payment::{MoneyExact, Payment},
+    DataToCompleteWorkSliceError, WorkPauseError, WorkResumeError,
+};
 
 /// Represents a reference to a work slice
 /// which may or may not be complete
@@ -45,8 +52,8 @@ impl<'a> WorkSlice<'a> {
         }
     }
 
-    /// Gets the duration of this work slice.
-    /// This returns the time between `Utc::now()` and the start of the work slice,
+    /// Gets the duration of this work slice, not including its breaks.
+    /// This returns the time worked between the start of the work slice and `Utc::now()`,
     /// if the work slice is incomplete.
     pub fn duration(&self) -> TimeDelta {
         match self {
@@ -79,16 +86,78 @@ impl<'a> WorkSlice<'a> {
             WorkSlice::Incomplete(x) => x.id(),
         }
     }
+
+    /// Returns the breaks which have been taken during this work slice, in order.
+    /// This doesn't include the current break of a paused work slice.
+    pub fn breaks(&self) -> &'a [Break] {
+        match self {
+            WorkSlice::Complete(x) => x.breaks(),
+            WorkSlice::Incomplete(x) => x.breaks(),
+        }
+    }
+
+    /// Returns the note describing what this work slice was for, if it has one.
+    pub fn note(&self) -> Option<&'a str> {
+        match self {
+            WorkSlice::Complete(x) => x.note(),
+            WorkSlice::Incomplete(x) => x.note(),
+        }
+    }
+
+    /// Returns the tags of this work slice.
+    pub fn tags(&self) -> &'a BTreeSet<String> {
+        match self {
+            WorkSlice::Complete(x) => x.tags(),
+            WorkSlice::Incomplete(x) => x.tags(),
+        }
+    }
+}
+
+/// A break taken during a work slice, which isn't counted as time worked.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Break {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+impl Break {
+    /// Returns how long this break lasted.
+    pub fn duration(&self) -> TimeDelta {
+        self.end - self.start
+    }
+}
+
+/// Returns true if the breaks are in order, don't overlap,
+/// and are all between `start` and `end` (if there is an end).
+fn breaks_are_valid(start: DateTime<Utc>, end: Option<DateTime<Utc>>, breaks: &[Break]) -> bool {
+    let mut previous = start;
+    for x in breaks {
+        if x.start < previous || x.end < x.start {
+            return false;
+        }
+        previous = x.end;
+    }
+    end.is_none_or(|end| previous <= end)
+}
+
+/// Returns the total length of the breaks.
+fn total_break_time(breaks: &[Break]) -> TimeDelta {
+    breaks.iter().map(Break::duration).sum()
 }
 
 /// Represents the id of a work slice.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub struct WorkSliceId(u64);
 impl WorkSliceId {
+    /// # Safety
+    /// The ID must refer to a work slice which exists (or existed) in a `State`,
+    /// otherwise it won't be found by any lookups.
     pub unsafe fn new(id: u64) -> Self {
         Self(id)
     }
 
+    /// # Safety
+    /// The raw ID is only meaningful to the `State` that created it.
     pub unsafe fn inner(&self) -> u64 {
         self.0
     }
@@ -100,6 +169,11 @@ impl WorkSliceId {
 pub struct IncompleteWorkSlice {
     start: DateTime<Utc>,
     payment: Payment,
+    breaks: Vec<Break>,
+    /// When the current break started, if this work slice is paused.
+    paused_at: Option<DateTime<Utc>>,
+    note: Option<String>,
+    tags: BTreeSet<String>,
     id: WorkSliceId,
 }
 impl IncompleteWorkSlice {
@@ -118,21 +192,146 @@ impl IncompleteWorkSlice {
     pub fn id(&self) -> WorkSliceId {
         self.id
     }
+
+    /// Returns the breaks which have been taken during this work slice, in order.
+    /// This doesn't include the current break, if this work slice is paused.
+    pub fn breaks(&self) -> &[Break] {
+        &self.breaks
+    }
+
+    /// Returns when the current break started, if this work slice is paused.
+    pub fn paused_at(&self) -> Option<DateTime<Utc>> {
+        self.paused_at
+    }
+
+    /// Returns true if this work slice is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused_at.is_some()
+    }
+
+    /// Returns the note describing what this work slice is for, if it has one.
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    /// Returns the tags of this work slice.
+    pub fn tags(&self) -> &BTreeSet<String> {
+        &self.tags
+    }
 }
 impl IncompleteWorkSlice {
     /// Constructs a new incomplete work slice, if `start <= Utc::now()`
     /// and fails otherwise.
     pub(super) fn new(start: DateTime<Utc>, payment: Payment, id: WorkSliceId) -> Option<Self> {
         if start <= Utc::now() {
-            Some(Self { start, payment, id })
+            Some(Self {
+                start,
+                payment,
+                breaks: Vec::new(),
+                paused_at: None,
+                note: None,
+                tags: BTreeSet::new(),
+                id,
+            })
         } else {
             None
         }
     }
 
-    /// Returns how much time has passed since the start of this work slice.
+    /// Gives this work slice the breaks which have been taken so far,
+    /// and the start of the current break if it's paused.
+    /// Fails if the breaks aren't in order after the start of this work slice,
+    /// or if the current break starts before the others have ended.
+    pub(super) fn with_breaks(
+        self,
+        breaks: Vec<Break>,
+        paused_at: Option<DateTime<Utc>>,
+    ) -> Option<Self> {
+        let end_of_breaks = breaks.last().map_or(self.start, |x| x.end);
+        if breaks_are_valid(self.start, None, &breaks)
+            && paused_at.is_none_or(|x| x >= end_of_breaks)
+        {
+            Some(Self {
+                breaks,
+                paused_at,
+                ..self
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Replaces the note and tags of this work slice, and returns the old ones.
+    pub(super) fn annotate(
+        &mut self,
+        note: Option<String>,
+        tags: BTreeSet<String>,
+    ) -> (Option<String>, BTreeSet<String>) {
+        (
+            std::mem::replace(&mut self.note, note),
+            std::mem::replace(&mut self.tags, tags),
+        )
+    }
+
+    /// Returns how much time has been worked since the start of this work slice,
+    /// not including breaks. This stops increasing while the work slice is paused.
     pub fn duration(&self) -> TimeDelta {
-        Utc::now() - self.start
+        self.paused_at.unwrap_or_else(Utc::now) - self.start - total_break_time(&self.breaks)
+    }
+
+    /// Starts a break, which lasts until the work slice is resumed.
+    /// Fails if it's already paused, or if `time` is in the future or before
+    /// the start of this work slice or the end of its last break.
+    pub(super) fn pause(&mut self, time: DateTime<Utc>) -> Result<(), WorkPauseError> {
+        if self.is_paused() {
+            return Err(WorkPauseError::AlreadyPaused);
+        }
+        let end_of_breaks = self.breaks.last().map_or(self.start, |x| x.end);
+        if time < end_of_breaks || time > Utc::now() {
+            return Err(WorkPauseError::InvalidTime);
+        }
+        self.paused_at = Some(time);
+        Ok(())
+    }
+
+    /// Ends the current break, and returns when it started.
+    /// Fails if this work slice isn't paused, or if `time` is in the future or before the break started.
+    pub(super) fn resume(&mut self, time: DateTime<Utc>) -> Result<DateTime<Utc>, WorkResumeError> {
+        let paused_at = self.paused_at.ok_or(WorkResumeError::NotPaused)?;
+        if time < paused_at || time > Utc::now() {
+            return Err(WorkResumeError::InvalidTime);
+        }
+        self.breaks.push(Break {
+            start: paused_at,
+            end: time,
+        });
+        self.paused_at = None;
+        Ok(paused_at)
+    }
+
+    /// Undoes a pause at `time`, as if this work slice had never been paused.
+    /// Returns false if it wasn't paused at that time.
+    pub(super) fn revert_pause(&mut self, time: DateTime<Utc>) -> bool {
+        if self.paused_at != Some(time) {
+            return false;
+        }
+        self.paused_at = None;
+        true
+    }
+
+    /// Undoes resuming at `time` after being paused at `paused_at`, so this work slice is paused again.
+    /// Returns false if that isn't its last break, or if it's paused.
+    pub(super) fn revert_resume(&mut self, paused_at: DateTime<Utc>, time: DateTime<Utc>) -> bool {
+        let last_break = Break {
+            start: paused_at,
+            end: time,
+        };
+        if self.is_paused() || self.breaks.last() != Some(&last_break) {
+            return false;
+        }
+        self.breaks.pop();
+        self.paused_at = Some(paused_at);
+        true
     }
 
     /// Calculates how much money has been earned by this work slice so far.
@@ -142,7 +341,10 @@ impl IncompleteWorkSlice {
     }
 
     /// Attempts to make a complete work slice out of this one, consuming it,
-    /// and returns this work slice if that fails because the end time is before the start time.
+    /// and returns this work slice if that fails because the end time is before the start time,
+    /// or before the start of the current break or the end of the last one.
+    /// If this work slice is paused, the current break ends at `end`.
+    #[allow(clippy::result_large_err)]
     pub(super) fn complete(
         self,
         end: DateTime<Utc>,
@@ -173,6 +375,9 @@ pub struct CompleteWorkSlice {
     start: DateTime<Utc>,
     end: DateTime<Utc>,
     payment: Payment,
+    breaks: Vec<Break>,
+    note: Option<String>,
+    tags: BTreeSet<String>,
     id: WorkSliceId,
 }
 impl CompleteWorkSlice {
@@ -198,19 +403,46 @@ impl CompleteWorkSlice {
     pub fn completion(&self) -> DateTime<Utc> {
         self.end
     }
+
+    /// Returns the breaks which were taken during this work slice, in order.
+    pub fn breaks(&self) -> &[Break] {
+        &self.breaks
+    }
+
+    /// Returns the note describing what this work slice was for, if it has one.
+    pub fn note(&self) -> Option<&str> {
+        self.note.as_deref()
+    }
+
+    /// Returns the tags of this work slice.
+    pub fn tags(&self) -> &BTreeSet<String> {
+        &self.tags
+    }
 }
 impl CompleteWorkSlice {
     /// Constructs a complete work slice out of an incomplete one,
-    /// if the end time is later than the start time.
+    /// if the end time is later than the start time and isn't before any of its breaks end.
+    /// If the incomplete work slice is paused, its current break ends at `end`.
+    #[allow(clippy::result_large_err)]
     pub(super) fn new(
         work_slice: IncompleteWorkSlice,
         end: DateTime<Utc>,
     ) -> Result<CompleteWorkSlice, IncompleteWorkSlice> {
-        if end > work_slice.start {
+        let mut breaks = work_slice.breaks.clone();
+        if let Some(paused_at) = work_slice.paused_at {
+            breaks.push(Break {
+                start: paused_at,
+                end,
+            });
+        }
+        if end > work_slice.start && breaks_are_valid(work_slice.start, Some(end), &breaks) {
             Ok(Self {
                 end,
                 start: work_slice.start,
                 payment: work_slice.payment,
+                breaks,
+                note: work_slice.note,
+                tags: work_slice.tags,
                 id: work_slice.id,
             })
         } else {
@@ -218,9 +450,84 @@ impl CompleteWorkSlice {
         }
     }
 
-    /// Returns the time between the end and the start of this work slice.
+    /// Gives this work slice the breaks which were taken during it,
+    /// but fails if they aren't in order between its start and end.
+    pub(super) fn with_breaks(self, breaks: Vec<Break>) -> Option<Self> {
+        if breaks_are_valid(self.start, Some(self.end), &breaks) {
+            Some(Self { breaks, ..self })
+        } else {
+            None
+        }
+    }
+
+    /// Checks that this work slice could be moved to a new start and end:
+    /// the start can't be in the future, the end must be after the start,
+    /// and all of its breaks must still be between the start and the end.
+    pub(super) fn check_edit(
+        &self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    ) -> Result<(), DataToCompleteWorkSliceError> {
+        if start > Utc::now() {
+            Err(DataToCompleteWorkSliceError::StartTimeAfterNow)
+        } else if end <= start {
+            Err(DataToCompleteWorkSliceError::EndTimeBeforeStart)
+        } else if !breaks_are_valid(start, Some(end), &self.breaks) {
+            Err(DataToCompleteWorkSliceError::InvalidBreaks)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Changes the start, end and payment of this work slice, if `check_edit` allows it.
+    /// Nothing is changed if this fails.
+    pub(super) fn edit(
+        &mut self,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        payment: Payment,
+    ) -> Result<(), DataToCompleteWorkSliceError> {
+        self.check_edit(start, end)?;
+        self.start = start;
+        self.end = end;
+        self.payment = payment;
+        Ok(())
+    }
+
+    /// Turns this back into an incomplete work slice, forgetting its end time.
+    /// If its last break lasted until the end, it was paused when it was completed,
+    /// so it's paused again.
+    pub(super) fn uncomplete(mut self) -> IncompleteWorkSlice {
+        let paused_at = match self.breaks.last() {
+            Some(x) if x.end == self.end => self.breaks.pop().map(|x| x.start),
+            _ => None,
+        };
+        IncompleteWorkSlice {
+            start: self.start,
+            payment: self.payment,
+            breaks: self.breaks,
+            paused_at,
+            note: self.note,
+            tags: self.tags,
+            id: self.id,
+        }
+    }
+
+    /// Replaces the note and tags of this work slice, and returns the old ones.
+    pub(super) fn annotate(
+        &mut self,
+        note: Option<String>,
+        tags: BTreeSet<String>,
+    ) -> (Option<String>, BTreeSet<String>) {
+        (
+            std::mem::replace(&mut self.note, note),
+            std::mem::replace(&mut self.tags, tags),
+        )
+    }
+
+    /// Returns the time between the start and the end of this work slice, not including breaks.
     pub fn duration(&self) -> TimeDelta {
-        self.end - self.start
+        self.end - self.start - total_break_time(&self.breaks)
     }
 
     /// Returns the amount of money earned by this work slice.
@@ -235,3 +542,270 @@ impl PartialEq for CompleteWorkSlice {
     }
 }
 impl Eq for CompleteWorkSlice {}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeDelta, Utc};
+
+    use crate::{
+        AddWorkSliceError, Annotation, CompleteWorkSliceData, Currency, EditWorkSliceError,
+        MemoryStorage, Money, OverlapPolicy, Payment, State, WorkPauseError, WorkResumeError,
+        WorkSliceData, WorkSliceId, WorkStartError,
+    };
+
+    fn payment() -> Payment {
+        Payment::Hourly(Money::new(1000, Currency::Gbp))
+    }
+
+    /// Returns a state with a project which has a complete work slice from `start` until `end`.
+    fn logged_work(start: DateTime<Utc>, end: DateTime<Utc>) -> (State, WorkSliceId) {
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let project = state.new_project("project".to_string(), "".to_string(), Some(payment()));
+        let id = state
+            .add_complete_work_slice(project, start, end, None, Annotation::default())
+            .unwrap();
+        (state, id)
+    }
+
+    fn data(state: &State, id: WorkSliceId) -> CompleteWorkSliceData {
+        match WorkSliceData::from(state.work_slice_from_id(id).unwrap()) {
+            WorkSliceData::Complete(x) => x,
+            WorkSliceData::Incomplete(_) => panic!("The work slice should be complete"),
+        }
+    }
+
+    #[test]
+    fn edit_work_slice() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let (mut state, id) = logged_work(now - hours(3), now - hours(2));
+        let fixed = Payment::Fixed(Money::new(5000, Currency::Gbp));
+        state
+            .edit_work_slice(id, now - hours(4), now - hours(1), fixed)
+            .unwrap();
+        let edited = data(&state, id);
+        assert_eq!(edited.start, now - hours(4));
+        assert_eq!(edited.end, now - hours(1));
+        assert_eq!(edited.payment, fixed);
+    }
+
+    #[test]
+    fn edits_with_invalid_times_are_rejected() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let (mut state, id) = logged_work(now - hours(3), now - hours(2));
+        let original = data(&state, id);
+        assert_eq!(
+            state.edit_work_slice(id, now + hours(1), now + hours(2), payment()),
+            Err(EditWorkSliceError::StartTimeAfterNow)
+        );
+        assert_eq!(
+            state.edit_work_slice(id, now - hours(2), now - hours(3), payment()),
+            Err(EditWorkSliceError::EndTimeBeforeStart)
+        );
+        assert_eq!(
+            state.edit_work_slice(id, now - hours(2), now - hours(2), payment()),
+            Err(EditWorkSliceError::EndTimeBeforeStart)
+        );
+        // a rejected edit doesn't change anything, or record a change
+        assert_eq!(data(&state, id), original);
+        assert_eq!(state.undo_history().len(), 2);
+    }
+
+    #[test]
+    fn edits_must_keep_breaks_inside_the_work_slice() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let project = state.new_project("project".to_string(), "".to_string(), Some(payment()));
+        state
+            .start_work(project, None, now - hours(4), Annotation::default())
+            .unwrap();
+        state.pause_work(project, now - hours(3)).unwrap();
+        state.resume_work(project, now - hours(2)).unwrap();
+        state.end_work(project, now - hours(1), None).unwrap();
+        let id = state
+            .project_from_id(project)
+            .unwrap()
+            .complete_work_slices()
+            .next()
+            .unwrap()
+            .id();
+        let original = data(&state, id);
+
+        assert_eq!(
+            state.edit_work_slice(id, now - TimeDelta::minutes(150), now - hours(1), payment()),
+            Err(EditWorkSliceError::BreakOutsideWorkSlice)
+        );
+        assert_eq!(
+            state.edit_work_slice(id, now - hours(4), now - TimeDelta::minutes(150), payment()),
+            Err(EditWorkSliceError::BreakOutsideWorkSlice)
+        );
+        assert_eq!(data(&state, id), original);
+        state
+            .edit_work_slice(id, now - hours(5), now, payment())
+            .unwrap();
+    }
+
+    #[test]
+    fn logged_work_cannot_start_in_the_future() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let (mut state, _) = logged_work(now - hours(3), now - hours(2));
+        let project = state.all_projects().next().unwrap().id();
+        assert_eq!(
+            state.add_complete_work_slice(
+                project,
+                now + hours(1),
+                now + hours(2),
+                None,
+                Annotation::default()
+            ),
+            Err(AddWorkSliceError::StartTimeAfterNow)
+        );
+        assert_eq!(state.undo_history().len(), 2);
+    }
+
+    #[test]
+    fn logged_work_must_end_after_it_starts() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let (mut state, _) = logged_work(now - hours(3), now - hours(2));
+        let project = state.all_projects().next().unwrap().id();
+        for end in [now - hours(6), now - hours(5)] {
+            assert_eq!(
+                state.add_complete_work_slice(
+                    project,
+                    now - hours(5),
+                    end,
+                    None,
+                    Annotation::default()
+                ),
+                Err(AddWorkSliceError::EndTimeBeforeStart)
+            );
+        }
+        assert_eq!(state.undo_history().len(), 2);
+        let project = state.project_from_id(project).unwrap();
+        assert_eq!(project.complete_work_slices().count(), 1);
+    }
+
+    #[test]
+    fn logged_work_follows_the_overlap_policy() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let (mut state, existing) = logged_work(now - hours(3), now - hours(1));
+        let project = state.all_projects().next().unwrap().id();
+        let log = |state: &mut State, policy| {
+            state.set_overlap_policy(policy);
+            state.add_complete_work_slice(
+                project,
+                now - hours(4),
+                now - hours(2),
+                None,
+                Annotation::default(),
+            )
+        };
+
+        assert_eq!(
+            log(&mut state, OverlapPolicy::Forbid),
+            Err(AddWorkSliceError::Overlap(vec![existing]))
+        );
+        assert_eq!(state.undo_history().len(), 2);
+
+        let warned = log(&mut state, OverlapPolicy::Warn).unwrap();
+        let warnings = state.take_overlap_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].first, warnings[0].second), (existing, warned));
+
+        log(&mut state, OverlapPolicy::Allow).unwrap();
+        assert!(state.take_overlap_warnings().is_empty());
+        let project = state.project_from_id(project).unwrap();
+        assert_eq!(project.complete_work_slices().count(), 3);
+    }
+
+    #[test]
+    fn pause_and_resume_work() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let project = state.new_project("project".to_string(), "".to_string(), Some(payment()));
+        assert_eq!(
+            state.pause_work(project, now),
+            Err(WorkPauseError::NoWorkToPause)
+        );
+        state
+            .start_work(project, None, now - hours(6), Annotation::default())
+            .unwrap();
+        assert_eq!(
+            state.resume_work(project, now),
+            Err(WorkResumeError::NotPaused)
+        );
+        assert_eq!(
+            state.pause_work(project, now - hours(7)),
+            Err(WorkPauseError::InvalidTime)
+        );
+        state.pause_work(project, now - hours(5)).unwrap();
+        assert_eq!(
+            state.pause_work(project, now - hours(4)),
+            Err(WorkPauseError::AlreadyPaused)
+        );
+        assert_eq!(
+            state.resume_work(project, now - hours(6)),
+            Err(WorkResumeError::InvalidTime)
+        );
+        state.resume_work(project, now - hours(3)).unwrap();
+        state.pause_work(project, now - hours(2)).unwrap();
+
+        let current = state.project_from_id(project).unwrap().current_work_slice();
+        let current = current.unwrap();
+        assert!(current.is_paused());
+        assert_eq!(current.breaks().len(), 1);
+        assert_eq!(current.duration(), hours(2));
+        assert_eq!(
+            current.calculate_payment_so_far(),
+            Money::new(2000, Currency::Gbp).into()
+        );
+
+        state.end_work(project, now - hours(1), None).unwrap();
+        let complete = state
+            .project_from_id(project)
+            .unwrap()
+            .complete_work_slices()
+            .next()
+            .unwrap();
+        assert_eq!(complete.breaks().len(), 2);
+        assert_eq!(complete.duration(), hours(2));
+        assert_eq!(
+            complete.calculate_payment(),
+            Money::new(2000, Currency::Gbp).into()
+        );
+    }
+
+    #[test]
+    fn work_uses_the_project_default_payment() {
+        let now = Utc::now();
+        let hours = TimeDelta::hours;
+        let mut state = State::new(MemoryStorage::default()).unwrap();
+        let project = state.new_project("project".to_string(), "".to_string(), None);
+        assert_eq!(
+            state.start_work(project, None, now - hours(2), Annotation::default()),
+            Err(WorkStartError::NoPayment)
+        );
+        state
+            .set_project_default_payment(project, Some(payment()))
+            .unwrap();
+        let logged = state
+            .add_complete_work_slice(
+                project,
+                now - hours(5),
+                now - hours(4),
+                None,
+                Annotation::default(),
+            )
+            .unwrap();
+        assert_eq!(
+            state.work_slice_from_id(logged).unwrap().payment_rate(),
+            payment()
+        );
+    }
+}